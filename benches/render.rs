@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use ray::angle::Angle;
+use ray::bvh::Bvh;
+use ray::cam::Camera;
+use ray::colorspace::ColorSpace;
+use ray::geometry::{sphere, Geometry, Intersect, Intersection};
+use ray::material::{dielectric, diffuse, metal};
+use ray::point::Point3;
+use ray::ray::Ray;
+use ray::render::{render_tile, Scene, Settings};
+use ray::sampler::reseed;
+use ray::tile::Tile;
+use ray::vec::Vec3;
+use ray::world::world_v4;
+
+const SEED: u64 = 42;
+const RAYS: usize = 4096;
+
+fn camera() -> Camera {
+    Camera::new(
+        Angle::Deg(20.),
+        16. / 9.,
+        0.07,
+        Point3(13., 2., 3.),
+        Point3(0., 0., 0.),
+        Vec3(0., 1., 0.),
+    )
+}
+
+fn world() -> Vec<Arc<Geometry>> {
+    reseed(SEED);
+    world_v4()
+}
+
+//rayons primaires répartis sur toute l'image, toujours les mêmes d'une exécution à l'autre
+fn camera_rays() -> Vec<Ray> {
+    let camera = camera();
+    reseed(SEED);
+    let side = (RAYS as f64).sqrt() as usize;
+    (0..RAYS)
+        .map(|i| {
+            let u = (i % side) as f64 / side as f64;
+            let v = (i / side) as f64 / side as f64;
+            camera.ray(u, v)
+        })
+        .collect()
+}
+
+fn sphere_intersection(c: &mut Criterion) {
+    let rays = camera_rays();
+    let mut group = c.benchmark_group("sphere");
+    group.throughput(Throughput::Elements(rays.len() as u64));
+    //la grosse sphère centrale de world_v4: environ un tiers des rayons la touchent
+    let glass = sphere(0., 1., 0., 1., dielectric(1.5));
+    group.bench_function("intersect", |b| {
+        b.iter(|| {
+            for ray in &rays {
+                black_box(glass.intersect(ray, 0.001, f64::INFINITY));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn traversal(c: &mut Criterion) {
+    let rays = camera_rays();
+    let objects = world();
+    let bvh = Bvh::new(objects.clone());
+    let mut group = c.benchmark_group("traversal");
+    group.throughput(Throughput::Elements(rays.len() as u64));
+    group.bench_function("world_v4/list", |b| {
+        b.iter(|| {
+            for ray in &rays {
+                black_box(objects[..].intersect(ray, 0.001, f64::INFINITY));
+            }
+        })
+    });
+    group.bench_function("world_v4/bvh", |b| {
+        b.iter(|| {
+            for ray in &rays {
+                black_box(bvh.intersect(ray, 0.001, f64::INFINITY));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn scatter(c: &mut Criterion) {
+    let ray = Ray {
+        origin: Point3(0., 1., 5.),
+        direction: Vec3(0.1, -0.05, -1.),
+    };
+    let mut group = c.benchmark_group("scatter");
+    group.throughput(Throughput::Elements(1));
+    for (name, material) in [
+        ("diffuse", diffuse(0.4, 0.2, 0.1)),
+        ("metal", metal(0.7, 0.6, 0.5, 0.3)),
+        ("dielectric", dielectric(1.5)),
+    ] {
+        let hit = Intersection::new(
+            &ray,
+            4.,
+            Point3(0.4, 0.8, 1.),
+            Vec3(0.4, -0.2, 1.).unit(),
+            material,
+        );
+        group.bench_with_input(BenchmarkId::from_parameter(name), &hit, |b, hit| {
+            b.iter(|| black_box(hit.material.scatter(hit, &ray)))
+        });
+    }
+    group.finish();
+}
+
+//petit rendu complet à graine fixe, mono thread; le débit est en rayons caméra par seconde
+fn render(c: &mut Criterion) {
+    let settings = Settings {
+        width: 64,
+        height: 36,
+        samples_per_pixel: 4,
+        max_reflection: 50,
+        tile_size: 64,
+        threads: 1,
+        seed: SEED,
+        aovs: Vec::new(),
+        working_space: ColorSpace::Srgb,
+        spectral: false,
+        toon: false,
+    };
+    let scene = Scene {
+        camera: camera(),
+        world: Bvh::new(world()),
+    };
+    let tile = Tile {
+        x: 0,
+        y: 0,
+        width: settings.width,
+        height: settings.height,
+    };
+    let mut group = c.benchmark_group("render");
+    group.throughput(Throughput::Elements(
+        (settings.width * settings.height * settings.samples_per_pixel) as u64,
+    ));
+    group.sample_size(10);
+    group.bench_function("world_v4/64x36/4spp", |b| {
+        b.iter(|| render_tile(&scene, &settings, 0, settings.samples_per_pixel, &tile))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, sphere_intersection, traversal, scatter, render);
+criterion_main!(benches);
@@ -21,3 +21,25 @@ impl Angle {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Angle::Deg(180.).rad(), PI);
+        assert_eq!(Angle::Rad(PI / 2.).deg(), 90.);
+        assert_eq!(Angle::Deg(20.).deg(), 20.);
+        assert_eq!(Angle::Rad(1.).rad(), 1.);
+    }
+
+    proptest! {
+        #[test]
+        fn degrees_round_trip(deg in -720.0..720.) {
+            prop_assert!((Angle::Rad(Angle::Deg(deg).rad()).deg() - deg).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::hash::{Hash, Hasher};
+
+use crate::checkpoint::SceneHasher;
+use crate::color::Color;
+use crate::colorspace::ColorSpace;
+use crate::geometry::Intersection;
+use crate::ray::Ray;
+
+//variables de sortie (aov): canaux en plus de l'image, remplis à partir de la première intersection
+//du rayon caméra et de l'état du chemin. sans intersection, toutes les valeurs sont nulles
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Aov {
+    //distance de la caméra au point touché
+    Depth,
+    Position,
+    //normale orientée vers la caméra
+    Normal,
+    //couleur du matériau
+    Albedo,
+    //indice de l'objet + 1, 0 pour le fond
+    ObjectId,
+    //hash des paramètres du matériau, 0 pour le fond: deux objets de même matériau ont le même identifiant
+    MaterialId,
+    Uv,
+    //lumière arrivée après au plus un rebond (fond vu directement compris)
+    Direct,
+    //lumière arrivée après plusieurs rebonds
+    Indirect,
+    //contribution du ciel, seule source de lumière des scènes
+    LightSky,
+}
+
+//le détail du chemin d'un échantillon, dont les aovs sont extraits
+pub struct Path {
+    pub color: Color,
+    //nombre de rebonds avant que le chemin atteigne le ciel ou soit absorbé
+    pub bounces: u16,
+    pub primary: Option<Intersection>,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 10] = [
+        Aov::Depth,
+        Aov::Position,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Uv,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::LightSky,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Uv => "uv",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::LightSky => "light_sky",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Aov> {
+        Aov::ALL.iter().copied().find(|aov| aov.name() == name)
+    }
+
+    //nom des composantes, dans l'ordre des valeurs
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Position | Aov::Normal => &["X", "Y", "Z"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Uv => &["U", "V"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect | Aov::LightSky => &["R", "G", "B"],
+        }
+    }
+
+    //les identifiants ne peuvent pas être moyennés: on garde celui du premier échantillon du pixel
+    pub fn averaged(self) -> bool {
+        !matches!(self, Aov::ObjectId | Aov::MaterialId)
+    }
+
+    pub fn code(self) -> u8 {
+        Aov::ALL.iter().position(|aov| *aov == self).unwrap() as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Aov> {
+        Aov::ALL.get(code as usize).copied()
+    }
+
+    //ajoute à `values` les composantes de l'aov pour un échantillon
+    //les couleurs du chemin sont déjà dans l'espace de travail `space`, l'albedo y est converti
+    pub fn push(self, ray: &Ray, path: &Path, space: ColorSpace, values: &mut Vec<f64>) {
+        let direct = path.bounces <= 1;
+        let color = |keep: bool| {
+            if keep {
+                path.color
+            } else {
+                Color::EMPTY
+            }
+        };
+        let Some(hit) = &path.primary else {
+            match self {
+                Aov::Direct | Aov::LightSky => push_color(color(true), values),
+                _ => values.extend(std::iter::repeat_n(0., self.channels().len())),
+            }
+            return;
+        };
+        match self {
+            Aov::Depth => values.push(hit.factor * ray.direction.len()),
+            Aov::Position => values.extend([hit.hit_point.0, hit.hit_point.1, hit.hit_point.2]),
+            Aov::Normal => values.extend([hit.normale.0, hit.normale.1, hit.normale.2]),
+            Aov::Albedo => push_color(space.from_srgb(hit.material.color), values),
+            Aov::ObjectId => values.push(hit.object as f64 + 1.),
+            Aov::MaterialId => {
+                let mut hasher = SceneHasher::new();
+                hit.material.hash(&mut hasher);
+                //24 bits: l'identifiant reste exact une fois écrit en flottant 32 bits
+                values.push(((hasher.finish() & 0xff_ffff) | 1) as f64)
+            }
+            Aov::Uv => values.extend([hit.uv.0, hit.uv.1]),
+            Aov::Direct => push_color(color(direct), values),
+            Aov::Indirect => push_color(color(!direct), values),
+            Aov::LightSky => push_color(color(true), values),
+        }
+    }
+}
+
+fn push_color(color: Color, values: &mut Vec<f64>) {
+    values.extend([color.red, color.green, color.blue]);
+}
+
+//nombre de valeurs par pixel pour une liste d'aovs
+pub fn stride(aovs: &[Aov]) -> usize {
+    aovs.iter().map(|aov| aov.channels().len()).sum()
+}
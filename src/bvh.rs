@@ -0,0 +1,480 @@
+use std::sync::Arc;
+
+use crate::geometry::{Aabb, Geometry, Intersect, Intersection};
+use crate::point::Point3;
+use crate::ray::Ray;
+use crate::stats;
+use crate::vec::Vec3;
+
+//BVH 4 voies: chaque noeud stocke les boites de ses 4 enfants en SoA (toutes les coordonnées min x ensemble, etc.)
+//pour tester un rayon contre les 4 boites en une seule passe vectorielle (AVX si disponible, sinon scalaire)
+//les feuilles contiennent jusqu'à 4 objets; quand ce sont des sphères, un pré-test vectoriel (discriminant)
+//élimine celles qui ne sont pas touchées avant le calcul exact de l'intersection
+const WIDTH: usize = 4;
+const STACK_SIZE: usize = 256;
+
+//index de l'objet, sa boite englobante et le centre de celle-ci
+type Primitive = (u32, Aabb, Point3);
+
+#[derive(Copy, Clone)]
+enum Child {
+    Empty,
+    Node(u32),
+    Leaf(u32),
+}
+
+struct Node {
+    min: [[f64; WIDTH]; 3],
+    max: [[f64; WIDTH]; 3],
+    children: [Child; WIDTH],
+}
+
+struct Leaf {
+    objects: [u32; WIDTH],
+    count: usize,
+    spheres: Option<SpherePack>,
+}
+
+//centres et rayons des sphères d'une feuille, en SoA
+struct SpherePack {
+    centre: [[f64; WIDTH]; 3],
+    radius2: [f64; WIDTH],
+}
+
+pub struct Bvh {
+    objects: Vec<Arc<Geometry>>,
+    nodes: Vec<Node>,
+    leaves: Vec<Leaf>,
+    root: Child,
+    simd: bool,
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<Arc<Geometry>>) -> Bvh {
+        let mut primitives: Vec<Primitive> = objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| {
+                let bounds = object.bounds();
+                (i as u32, bounds, bounds.centre())
+            })
+            .collect();
+        let mut bvh = Bvh {
+            objects,
+            nodes: Vec::new(),
+            leaves: Vec::new(),
+            root: Child::Empty,
+            simd: simd_available(),
+        };
+        if !primitives.is_empty() {
+            bvh.root = bvh.build(&mut primitives);
+        }
+        bvh
+    }
+
+    pub fn objects(&self) -> &[Arc<Geometry>] {
+        &self.objects
+    }
+
+    fn build(&mut self, primitives: &mut [Primitive]) -> Child {
+        if primitives.len() <= WIDTH {
+            return self.leaf(primitives);
+        }
+        //on coupe à la médiane selon le plus grand axe, puis chaque moitié de la même façon: 4 groupes
+        let (left, right) = split(primitives);
+        let mut groups = Vec::with_capacity(WIDTH);
+        for half in [left, right] {
+            if half.len() > WIDTH {
+                let (a, b) = split(half);
+                groups.push(a);
+                groups.push(b);
+            } else {
+                groups.push(half);
+            }
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            min: [[0.; WIDTH]; 3],
+            max: [[0.; WIDTH]; 3],
+            children: [Child::Empty; WIDTH],
+        });
+        for (lane, group) in groups.into_iter().enumerate() {
+            let bounds = group
+                .iter()
+                .skip(1)
+                .fold(group[0].1, |bounds, primitive| bounds.union(&primitive.1));
+            let child = self.build(group);
+            let node = &mut self.nodes[index];
+            node.children[lane] = child;
+            node.min[0][lane] = bounds.min.0;
+            node.min[1][lane] = bounds.min.1;
+            node.min[2][lane] = bounds.min.2;
+            node.max[0][lane] = bounds.max.0;
+            node.max[1][lane] = bounds.max.1;
+            node.max[2][lane] = bounds.max.2;
+        }
+        Child::Node(index as u32)
+    }
+
+    fn leaf(&mut self, primitives: &[Primitive]) -> Child {
+        let mut leaf = Leaf {
+            objects: [0; WIDTH],
+            count: primitives.len(),
+            spheres: Some(SpherePack {
+                centre: [[0.; WIDTH]; 3],
+                radius2: [0.; WIDTH],
+            }),
+        };
+        for (lane, (object, _, _)) in primitives.iter().enumerate() {
+            leaf.objects[lane] = *object;
+            match (&mut leaf.spheres, &*self.objects[*object as usize]) {
+                (Some(pack), Geometry::Sphere(sphere)) => {
+                    pack.centre[0][lane] = sphere.centre.0;
+                    pack.centre[1][lane] = sphere.centre.1;
+                    pack.centre[2][lane] = sphere.centre.2;
+                    pack.radius2[lane] = sphere.radius * sphere.radius;
+                }
+                (spheres, _) => *spheres = None,
+            }
+        }
+        self.leaves.push(leaf);
+        Child::Leaf(self.leaves.len() as u32 - 1)
+    }
+
+    fn intersect_leaf(
+        &self,
+        leaf: &Leaf,
+        ray: &Ray,
+        t_min: f64,
+        closest: &mut f64,
+        ret: &mut Option<Intersection>,
+    ) -> u64 {
+        let mut tests = 0;
+        let candidates = match &leaf.spheres {
+            Some(pack) => self.sphere_candidates(pack, ray),
+            None => [true; WIDTH],
+        };
+        for (object, candidate) in leaf.objects.iter().zip(candidates).take(leaf.count) {
+            if candidate {
+                tests += 1;
+                if let Some(mut hit) =
+                    self.objects[*object as usize].intersect(ray, t_min, *closest)
+                {
+                    *closest = hit.factor;
+                    hit.object = *object;
+                    *ret = Some(hit);
+                }
+            }
+        }
+        tests
+    }
+
+    fn sphere_candidates(&self, pack: &SpherePack, ray: &Ray) -> [bool; WIDTH] {
+        #[cfg(target_arch = "x86_64")]
+        if self.simd {
+            // SAFETY: simd n'est vrai que si le processeur supporte AVX
+            return unsafe { avx::sphere_candidates(pack, ray) };
+        }
+        let mut candidates = [false; WIDTH];
+        for (lane, candidate) in candidates.iter_mut().enumerate() {
+            let x = Vec3::points(
+                Point3(
+                    pack.centre[0][lane],
+                    pack.centre[1][lane],
+                    pack.centre[2][lane],
+                ),
+                ray.origin,
+            );
+            let a = ray.direction.sqr_len();
+            let h = x.scalar_product(ray.direction);
+            let c = x.sqr_len() - pack.radius2[lane];
+            *candidate = h * h - a * c >= 0.;
+        }
+        candidates
+    }
+
+    //retourne pour chaque enfant la distance d'entrée dans sa boite, ou None s'il n'est pas touché
+    fn intersect_boxes(
+        &self,
+        node: &Node,
+        ray: &Ray,
+        inverse: &Vec3,
+        t_min: f64,
+        t_max: f64,
+    ) -> [Option<f64>; WIDTH] {
+        #[cfg(target_arch = "x86_64")]
+        if self.simd {
+            // SAFETY: simd n'est vrai que si le processeur supporte AVX
+            return unsafe { avx::intersect_boxes(node, ray, inverse, t_min, t_max) };
+        }
+        let origin = [ray.origin.0, ray.origin.1, ray.origin.2];
+        let inverse = [inverse.0, inverse.1, inverse.2];
+        let mut hits = [None; WIDTH];
+        for (lane, hit) in hits.iter_mut().enumerate() {
+            let (mut near, mut far) = (t_min, t_max);
+            for axis in 0..3 {
+                let t0 = (node.min[axis][lane] - origin[axis]) * inverse[axis];
+                let t1 = (node.max[axis][lane] - origin[axis]) * inverse[axis];
+                near = max(near, min(t0, t1));
+                far = min(far, max(t0, t1));
+            }
+            if near <= far {
+                *hit = Some(near);
+            }
+        }
+        hits
+    }
+}
+
+impl Intersect for Bvh {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+        let inverse = Vec3(
+            1. / ray.direction.0,
+            1. / ray.direction.1,
+            1. / ray.direction.2,
+        );
+        let mut closest = t_max;
+        let mut ret = None;
+
+        //pile des enfants à visiter avec leur distance d'entrée
+        let mut stack = [(Child::Empty, 0.); STACK_SIZE];
+        stack[0] = (self.root, t_min);
+        let mut size = 1;
+        //compteurs locaux, reportés une seule fois dans les statistiques du thread
+        let (mut visits, mut tests) = (0, 0);
+        while size > 0 {
+            size -= 1;
+            let (child, near) = stack[size];
+            //un objet plus proche a été trouvé depuis que cet enfant a été empilé
+            if near > closest {
+                continue;
+            }
+            match child {
+                Child::Empty => {}
+                Child::Leaf(index) => {
+                    visits += 1;
+                    tests += self.intersect_leaf(
+                        &self.leaves[index as usize],
+                        ray,
+                        t_min,
+                        &mut closest,
+                        &mut ret,
+                    );
+                }
+                Child::Node(index) => {
+                    visits += 1;
+                    let node = &self.nodes[index as usize];
+                    let hits = self.intersect_boxes(node, ray, &inverse, t_min, closest);
+                    //on empile les plus lointains d'abord pour visiter le plus proche en premier
+                    let mut order: [(usize, f64); WIDTH] = [(0, 0.); WIDTH];
+                    let mut count = 0;
+                    for (lane, hit) in hits.iter().enumerate() {
+                        let near = match (hit, node.children[lane]) {
+                            (Some(near), Child::Node(_)) | (Some(near), Child::Leaf(_)) => *near,
+                            _ => continue,
+                        };
+                        let mut i = count;
+                        while i > 0 && order[i - 1].1 < near {
+                            order[i] = order[i - 1];
+                            i -= 1;
+                        }
+                        order[i] = (lane, near);
+                        count += 1;
+                    }
+                    for (lane, near) in order.iter().take(count) {
+                        stack[size] = (node.children[*lane], *near);
+                        size += 1;
+                    }
+                }
+            }
+        }
+        stats::record_traversal(visits, tests);
+        ret
+    }
+}
+
+fn split(primitives: &mut [Primitive]) -> (&mut [Primitive], &mut [Primitive]) {
+    let (min, max) = primitives.iter().fold(
+        (
+            Point3(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Point3(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        ),
+        |(min, max), (_, _, c)| {
+            (
+                Point3(min.0.min(c.0), min.1.min(c.1), min.2.min(c.2)),
+                Point3(max.0.max(c.0), max.1.max(c.1), max.2.max(c.2)),
+            )
+        },
+    );
+    let extent = Vec3::points(min, max);
+    let axis = if extent.0 >= extent.1 && extent.0 >= extent.2 {
+        0
+    } else if extent.1 >= extent.2 {
+        1
+    } else {
+        2
+    };
+    let coordinate = |p: &Point3| match axis {
+        0 => p.0,
+        1 => p.1,
+        _ => p.2,
+    };
+    let middle = primitives.len() / 2;
+    primitives.select_nth_unstable_by(middle, |a, b| coordinate(&a.2).total_cmp(&coordinate(&b.2)));
+    primitives.split_at_mut(middle)
+}
+
+//min et max comme _mm256_min_pd et _mm256_max_pd: avec un NaN, le second opérande est retourné.
+//un rayon parallèle à un axe qui rase une face donne 0 * inf = NaN, les deux chemins doivent alors s'accorder
+fn min(a: f64, b: f64) -> f64 {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+fn max(a: f64, b: f64) -> f64 {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+fn simd_available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx {
+    use std::arch::x86_64::*;
+
+    use super::{Node, SpherePack, WIDTH};
+    use crate::ray::Ray;
+    use crate::vec::Vec3;
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn intersect_boxes(
+        node: &Node,
+        ray: &Ray,
+        inverse: &Vec3,
+        t_min: f64,
+        t_max: f64,
+    ) -> [Option<f64>; WIDTH] {
+        let origin = [ray.origin.0, ray.origin.1, ray.origin.2];
+        let inverse = [inverse.0, inverse.1, inverse.2];
+        let mut near = _mm256_set1_pd(t_min);
+        let mut far = _mm256_set1_pd(t_max);
+        for axis in 0..3 {
+            let o = _mm256_set1_pd(origin[axis]);
+            let inv = _mm256_set1_pd(inverse[axis]);
+            let t0 = _mm256_mul_pd(
+                _mm256_sub_pd(_mm256_loadu_pd(node.min[axis].as_ptr()), o),
+                inv,
+            );
+            let t1 = _mm256_mul_pd(
+                _mm256_sub_pd(_mm256_loadu_pd(node.max[axis].as_ptr()), o),
+                inv,
+            );
+            near = _mm256_max_pd(near, _mm256_min_pd(t0, t1));
+            far = _mm256_min_pd(far, _mm256_max_pd(t0, t1));
+        }
+        let mask = _mm256_movemask_pd(_mm256_cmp_pd(near, far, _CMP_LE_OQ));
+        let mut nears = [0.; WIDTH];
+        _mm256_storeu_pd(nears.as_mut_ptr(), near);
+        let mut hits = [None; WIDTH];
+        for (lane, hit) in hits.iter_mut().enumerate() {
+            if mask & (1 << lane) != 0 {
+                *hit = Some(nears[lane]);
+            }
+        }
+        hits
+    }
+
+    //mêmes opérations, dans le même ordre, que Sphere::hit: le test est exact et ne rejette aucune sphère touchée
+    #[target_feature(enable = "avx")]
+    pub unsafe fn sphere_candidates(pack: &SpherePack, ray: &Ray) -> [bool; WIDTH] {
+        let dx = _mm256_set1_pd(ray.direction.0);
+        let dy = _mm256_set1_pd(ray.direction.1);
+        let dz = _mm256_set1_pd(ray.direction.2);
+        let xx = _mm256_sub_pd(
+            _mm256_set1_pd(ray.origin.0),
+            _mm256_loadu_pd(pack.centre[0].as_ptr()),
+        );
+        let xy = _mm256_sub_pd(
+            _mm256_set1_pd(ray.origin.1),
+            _mm256_loadu_pd(pack.centre[1].as_ptr()),
+        );
+        let xz = _mm256_sub_pd(
+            _mm256_set1_pd(ray.origin.2),
+            _mm256_loadu_pd(pack.centre[2].as_ptr()),
+        );
+        let a = _mm256_set1_pd(ray.direction.sqr_len());
+        let h = _mm256_add_pd(
+            _mm256_add_pd(_mm256_mul_pd(xx, dx), _mm256_mul_pd(xy, dy)),
+            _mm256_mul_pd(xz, dz),
+        );
+        let x2 = _mm256_add_pd(
+            _mm256_add_pd(_mm256_mul_pd(xx, xx), _mm256_mul_pd(xy, xy)),
+            _mm256_mul_pd(xz, xz),
+        );
+        let c = _mm256_sub_pd(x2, _mm256_loadu_pd(pack.radius2.as_ptr()));
+        let d = _mm256_sub_pd(_mm256_mul_pd(h, h), _mm256_mul_pd(a, c));
+        let mask = _mm256_movemask_pd(_mm256_cmp_pd(d, _mm256_setzero_pd(), _CMP_GE_OQ));
+        let mut candidates = [false; WIDTH];
+        for (lane, candidate) in candidates.iter_mut().enumerate() {
+            *candidate = mask & (1 << lane) != 0;
+        }
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalar_and_simd_box_tests_agree_on_grazing_rays() {
+        //le cube unité, un cube posé sur sa face supérieure, un cube accolé sur x et un cube éloigné
+        let node = Node {
+            min: [[0., 0., 1., 5.], [0., 1., 0., 5.], [0., 0., 0., 5.]],
+            max: [[1., 1., 2., 6.], [1., 2., 1., 6.], [1., 1., 1., 6.]],
+            children: [Child::Empty; WIDTH],
+        };
+        let scalar = Bvh {
+            simd: false,
+            ..Bvh::new(Vec::new())
+        };
+        let simd = Bvh {
+            simd: true,
+            ..Bvh::new(Vec::new())
+        };
+        //rayons parallèles aux axes, dont l'origine est sur le plan d'une face
+        let rays = [
+            (Point3(0.5, 1., -1.), Vec3(0., 0., 1.)),
+            (Point3(0.5, 0., -1.), Vec3(0., 0., 1.)),
+            (Point3(-1., 0.5, 1.), Vec3(1., 0., 0.)),
+            (Point3(1., 0.5, -1.), Vec3(0., 0., 1.)),
+            (Point3(0.5, -1., 0.), Vec3(0., 1., 0.)),
+            (Point3(0.5, 3., 1.), Vec3(0., -1., 0.)),
+        ];
+        for (origin, direction) in rays {
+            let inverse = Vec3(1. / direction.0, 1. / direction.1, 1. / direction.2);
+            let ray = Ray { origin, direction };
+            let expected = scalar.intersect_boxes(&node, &ray, &inverse, 0.001, f64::INFINITY);
+            if simd_available() {
+                let hits = simd.intersect_boxes(&node, &ray, &inverse, 0.001, f64::INFINITY);
+                assert_eq!(hits, expected);
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::hash::{Hash, Hasher};
+
 use crate::angle::Angle;
 use crate::point::Point3;
 use crate::ray::Ray;
@@ -8,13 +10,24 @@ pub struct Camera {
     origin: Point3,
     u: Vec3,
     v: Vec3,
-    w: Vec3,
     h_vect: Vec3,
     v_vect: Vec3,
     ll_corner: Point3,
     lens_radius: f64
 }
 
+impl Hash for Camera {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.origin.hash(state);
+        self.u.hash(state);
+        self.v.hash(state);
+        self.h_vect.hash(state);
+        self.v_vect.hash(state);
+        self.ll_corner.hash(state);
+        self.lens_radius.to_bits().hash(state);
+    }
+}
+
 impl Camera {
     pub fn new(
         vertical_field_of_view: Angle,
@@ -40,7 +53,6 @@ impl Camera {
             origin: cam_origin,
             u,
             v,
-            w: w_unit,
             h_vect,
             v_vect,
             ll_corner: cam_origin - (h_vect / 2.) - (v_vect / 2.) - focus_dist* w_unit,
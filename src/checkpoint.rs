@@ -0,0 +1,237 @@
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::aov::{self, Aov};
+use crate::color::Color;
+use crate::colorspace::ColorSpace;
+use crate::film::Film;
+use crate::render::{Scene, Settings};
+
+const MAGIC: &[u8; 8] = b"RAYCKPT\0";
+//version 2: ajout des aovs
+const VERSION: u32 = 2;
+
+//hash FNV-1a 64 bits: contrairement au DefaultHasher de std, il est stable d'une version de rust à l'autre,
+//un checkpoint reste donc valide après une recompilation
+pub struct SceneHasher(u64);
+
+impl SceneHasher {
+    pub fn new() -> SceneHasher {
+        SceneHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Default for SceneHasher {
+    fn default() -> Self {
+        SceneHasher::new()
+    }
+}
+
+impl Hasher for SceneHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+//état d'un rendu progressif interrompu: le film accumulé (sommes et nombres d'échantillons)
+//et l'état du générateur, c'est à dire la graine et le nombre de passes déjà rendues
+pub struct Checkpoint {
+    pub scene_hash: u64,
+    pub seed: u64,
+    pub passes: u32,
+    pub samples_per_pixel: u32,
+    pub film: Film,
+}
+
+impl Checkpoint {
+    //écriture atomique (fichier temporaire puis renommage): un crash pendant l'écriture ne détruit pas le checkpoint précédent
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut tmp = PathBuf::from(path);
+        tmp.set_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.scene_hash.to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.passes.to_le_bytes())?;
+        writer.write_all(&self.samples_per_pixel.to_le_bytes())?;
+        writer.write_all(&self.film.width().to_le_bytes())?;
+        writer.write_all(&self.film.height().to_le_bytes())?;
+        writer.write_all(&(self.film.aovs().len() as u32).to_le_bytes())?;
+        for aov in self.film.aovs() {
+            writer.write_all(&[aov.code()])?;
+        }
+        let (pixels, samples) = self.film.raw();
+        for (color, samples) in pixels.iter().zip(samples) {
+            writer.write_all(&color.red.to_le_bytes())?;
+            writer.write_all(&color.green.to_le_bytes())?;
+            writer.write_all(&color.blue.to_le_bytes())?;
+            writer.write_all(&samples.to_le_bytes())?;
+        }
+        for value in self.film.raw_layers() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        std::fs::rename(&tmp, path)
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Checkpoint> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a checkpoint file"));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != 1 && version != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported checkpoint version {}", version),
+            ));
+        }
+        let scene_hash = read_u64(&mut reader)?;
+        let seed = read_u64(&mut reader)?;
+        let passes = read_u32(&mut reader)?;
+        let samples_per_pixel = read_u32(&mut reader)?;
+        let width = read_u32(&mut reader)?;
+        let height = read_u32(&mut reader)?;
+        let aovs = if version >= 2 {
+            read_aovs(&mut reader)?
+        } else {
+            Vec::new()
+        };
+
+        //les dimensions de l'en-tête doivent correspondre à la taille des données qui suivent:
+        //un fichier tronqué ou corrompu est refusé avant toute allocation
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                "checkpoint size does not match its header",
+            )
+        };
+        let size = (width as usize)
+            .checked_mul(height as usize)
+            .ok_or_else(invalid)?;
+        let pixel_bytes = 3 * 8 + 4 + 8 * aov::stride(&aovs) as u64;
+        let expected = (size as u64).checked_mul(pixel_bytes).ok_or_else(invalid)?;
+        if length.checked_sub(reader.stream_position()?) != Some(expected) {
+            return Err(invalid());
+        }
+        let mut pixels = Vec::with_capacity(size);
+        let mut samples = Vec::with_capacity(size);
+        for _ in 0..size {
+            pixels.push(Color::new(
+                read_f64(&mut reader)?,
+                read_f64(&mut reader)?,
+                read_f64(&mut reader)?,
+            ));
+            samples.push(read_u32(&mut reader)?);
+        }
+        let layers = (0..size * aov::stride(&aovs))
+            .map(|_| read_f64(&mut reader))
+            .collect::<std::io::Result<_>>()?;
+        Ok(Checkpoint {
+            scene_hash,
+            seed,
+            passes,
+            samples_per_pixel,
+            film: Film::from_raw(width, height, pixels, samples, aovs, layers),
+        })
+    }
+}
+
+//liste d'aovs: nombre puis code de chaque aov
+pub fn read_aovs<R: Read>(reader: &mut R) -> std::io::Result<Vec<Aov>> {
+    let count = read_u32(reader)?;
+    (0..count)
+        .map(|_| {
+            let mut code = [0];
+            reader.read_exact(&mut code)?;
+            Aov::from_code(code[0]).ok_or_else(|| Error::new(ErrorKind::InvalidData, "unknown aov"))
+        })
+        .collect()
+}
+
+pub fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub fn read_f64<R: Read>(reader: &mut R) -> std::io::Result<f64> {
+    Ok(f64::from_bits(read_u64(reader)?))
+}
+
+//identifie la scène: caméra, objets et réglages qui changent l'image
+pub fn scene_hash(scene: &Scene, settings: &Settings) -> u64 {
+    let mut hasher = SceneHasher::new();
+    scene.camera.hash(&mut hasher);
+    scene.world.objects().hash(&mut hasher);
+    settings.width.hash(&mut hasher);
+    settings.height.hash(&mut hasher);
+    settings.max_reflection.hash(&mut hasher);
+    //le rendu en sRGB garde le hash d'avant les espaces de travail, les anciens checkpoints restent valides
+    if settings.working_space != ColorSpace::Srgb {
+        settings.working_space.code().hash(&mut hasher);
+    }
+    if settings.spectral {
+        "spectral".hash(&mut hasher);
+    }
+    if settings.toon {
+        "toon".hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_rejects_sizes_that_do_not_match_the_header() {
+        let path = std::env::temp_dir().join(format!("ray-checkpoint-{}.bin", std::process::id()));
+        let checkpoint = Checkpoint {
+            scene_hash: 1,
+            seed: 2,
+            passes: 3,
+            samples_per_pixel: 4,
+            film: Film::with_aovs(3, 2, &[Aov::Depth]),
+        };
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!((loaded.film.width(), loaded.film.height()), (3, 2));
+        assert_eq!(loaded.film.aovs(), &[Aov::Depth]);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        //données tronquées
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(Checkpoint::load(&path).is_err());
+        //dimensions énormes, dont le produit déborde en 32 bits
+        let width_offset = 8 + 4 + 8 + 8 + 4 + 4;
+        bytes[width_offset..width_offset + 8].copy_from_slice(&[0xff; 8]);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(Checkpoint::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
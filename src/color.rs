@@ -1,7 +1,10 @@
+use std::hash::{Hash, Hasher};
 use std::ops::{Add, Div, Mul, Range};
-use rand::{random, Rng};
+use rand::Rng;
 
-#[derive(Copy, Clone)]
+use crate::sampler::{random, rng};
+
+#[derive(Copy, Clone, Debug)]
 pub struct Color {
     pub red: f64,
     pub green: f64,
@@ -15,8 +18,9 @@ impl Color {
         Color { red, green, blue }
     }
 
+    //composantes ramenées à [0, 1] puis multipliées par range et tronquées
     pub fn scale(&self, range: f64) -> (u32, u32, u32) {
-        let c = range * *self;
+        let c = range * self.map_each(|v| v.clamp(0., 1.));
         (c.red as u32, c.green as u32, c.blue as u32)
     }
 
@@ -35,13 +39,22 @@ impl Color {
     }
     pub fn random_range(range: Range<f64>) -> Color{
         Color{
-            red: rand::thread_rng().gen_range(range.clone()),
-            green: rand::thread_rng().gen_range(range.clone()),
-            blue: rand::thread_rng().gen_range(range)
+            red: rng().gen_range(range.clone()),
+            green: rng().gen_range(range.clone()),
+            blue: rng().gen_range(range)
         }
     }
 }
 
+//hash des bits des composantes, pour identifier une scène (cf checkpoint)
+impl Hash for Color {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.red.to_bits().hash(state);
+        self.green.to_bits().hash(state);
+        self.blue.to_bits().hash(state);
+    }
+}
+
 impl Div<f64> for Color {
     type Output = Self;
 
@@ -97,3 +110,33 @@ impl Add<Color> for Color {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_truncates() {
+        assert_eq!(Color::new(1., 0.5, 0.).scale(255.99), (255, 127, 0));
+        assert_eq!(Color::new(1.5, -0.5, 0.).scale(255.99), (255, 0, 0));
+    }
+
+    #[test]
+    fn operations_are_component_wise() {
+        let Color { red, green, blue } =
+            Color::new(0.5, 0.25, 1.) * Color::new(0.5, 2., 0.) + Color::new(1., 1., 1.) / 2.;
+        assert_eq!((red, green, blue), (0.75, 1., 0.5));
+        let Color { red, green, blue } = 2. * Color::new(1., 4., 9.).map_each(f64::sqrt);
+        assert_eq!((red, green, blue), (2., 4., 6.));
+    }
+
+    #[test]
+    fn random_range_stays_in_range() {
+        for _ in 0..1000 {
+            let Color { red, green, blue } = Color::random_range(0.5..1.);
+            for v in [red, green, blue] {
+                assert!((0.5..1.).contains(&v));
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::color::Color;
+
+//espaces de couleurs linéaires dans lesquels le rendu peut se faire.
+//les couleurs des scènes (matériaux, ciel) sont données en sRGB linéaire et converties vers l'espace de travail;
+//le film est exprimé dans l'espace de travail, les images 8 bits sont toujours écrites en sRGB
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum ColorSpace {
+    //primaires Rec.709, blanc D65
+    #[default]
+    Srgb,
+    //primaires AP1 d'ACES, blanc D60
+    AcesCg,
+    //primaires Rec.2020, blanc D65
+    Rec2020,
+}
+
+pub type Matrix = [[f64; 3]; 3];
+
+const IDENTITY: Matrix = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+//sRGB -> ACEScg, avec adaptation chromatique de Bradford D65 -> D60
+const SRGB_TO_ACESCG: Matrix = [
+    [0.6130974, 0.3395231, 0.0473795],
+    [0.0701937, 0.9163539, 0.0134524],
+    [0.0206156, 0.1095698, 0.8698147],
+];
+const SRGB_TO_REC2020: Matrix = [
+    [0.6274039, 0.3292830, 0.0433131],
+    [0.0690973, 0.9195404, 0.0113623],
+    [0.0163914, 0.0880133, 0.8955953],
+];
+
+impl ColorSpace {
+    pub const ALL: [ColorSpace; 3] = [ColorSpace::Srgb, ColorSpace::AcesCg, ColorSpace::Rec2020];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::AcesCg => "acescg",
+            ColorSpace::Rec2020 => "rec2020",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<ColorSpace> {
+        ColorSpace::ALL.iter().copied().find(|s| s.name() == name)
+    }
+
+    pub fn code(self) -> u8 {
+        ColorSpace::ALL.iter().position(|s| *s == self).unwrap() as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<ColorSpace> {
+        ColorSpace::ALL.get(code as usize).copied()
+    }
+
+    //matrice de conversion sRGB linéaire -> cet espace
+    pub fn from_srgb_matrix(self) -> Matrix {
+        match self {
+            ColorSpace::Srgb => IDENTITY,
+            ColorSpace::AcesCg => SRGB_TO_ACESCG,
+            ColorSpace::Rec2020 => SRGB_TO_REC2020,
+        }
+    }
+
+    //matrice de conversion de cet espace vers un autre
+    pub fn matrix_to(self, to: ColorSpace) -> Matrix {
+        product(&to.from_srgb_matrix(), &invert(&self.from_srgb_matrix()))
+    }
+
+    pub fn from_srgb(self, color: Color) -> Color {
+        match self {
+            ColorSpace::Srgb => color,
+            _ => multiply(&self.from_srgb_matrix(), color),
+        }
+    }
+
+    pub fn to_srgb(self, color: Color) -> Color {
+        match self {
+            ColorSpace::Srgb => color,
+            _ => multiply(&self.matrix_to(ColorSpace::Srgb), color),
+        }
+    }
+
+    //coordonnées xy des primaires rouge, verte, bleue puis du blanc (attribut chromaticities de l'EXR)
+    pub fn chromaticities(self) -> [f64; 8] {
+        match self {
+            ColorSpace::Srgb => [0.64, 0.33, 0.30, 0.60, 0.15, 0.06, 0.3127, 0.3290],
+            ColorSpace::AcesCg => [0.713, 0.293, 0.165, 0.830, 0.128, 0.044, 0.32168, 0.33767],
+            ColorSpace::Rec2020 => [0.708, 0.292, 0.170, 0.797, 0.131, 0.046, 0.3127, 0.3290],
+        }
+    }
+}
+
+pub fn multiply(m: &Matrix, c: Color) -> Color {
+    let row = |r: &[f64; 3]| r[0] * c.red + r[1] * c.green + r[2] * c.blue;
+    Color::new(row(&m[0]), row(&m[1]), row(&m[2]))
+}
+
+fn product(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+//inverse par les cofacteurs, les matrices de conversion sont toujours inversibles
+pub(crate) fn invert(m: &Matrix) -> Matrix {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let determinant = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum::<f64>();
+    let mut inverse = [[0.; 3]; 3];
+    for (i, row) in inverse.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = cofactor(j, i) / determinant;
+        }
+    }
+    inverse
+}
+
+//fonction de transfert sRGB (OETF): valeur linéaire dans [0, 1] -> valeur encodée
+pub fn srgb_encode(v: f64) -> f64 {
+    if v <= 0.0031308 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1. / 2.4) - 0.055
+    }
+}
+
+//inverse de srgb_encode: valeur encodée dans [0, 1] -> valeur linéaire
+pub fn srgb_decode(v: f64) -> f64 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+//couleur 8 bits encodée en sRGB (textures, nuanciers) -> sRGB linéaire, utilisable pour les matériaux
+pub fn decode_srgb8(red: u8, green: u8, blue: u8) -> Color {
+    let decode = |v: u8| srgb_decode(v as f64 / 255.);
+    Color::new(decode(red), decode(green), decode(blue))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_function_round_trips() {
+        for i in 0..=255u8 {
+            let v = i as f64 / 255.;
+            assert!((srgb_encode(srgb_decode(v)) - v).abs() < 1e-12);
+        }
+        assert!((srgb_encode(0.18) - 0.4614).abs() < 1e-4);
+        let Color { red, green, blue } = decode_srgb8(255, 128, 0);
+        assert!(red == 1. && (green - 0.2158).abs() < 1e-4 && blue == 0.);
+    }
+
+    #[test]
+    fn conversions_keep_white_and_round_trip() {
+        let white = Color::new(1., 1., 1.);
+        for space in ColorSpace::ALL {
+            let Color { red, green, blue } = space.from_srgb(white);
+            for v in [red, green, blue] {
+                assert!((v - 1.).abs() < 1e-4, "{:?}", space);
+            }
+            let Color { red, green, blue } =
+                space.to_srgb(space.from_srgb(Color::new(0.2, 0.5, 0.9)));
+            assert!(
+                (red - 0.2).abs() < 1e-12
+                    && (green - 0.5).abs() < 1e-12
+                    && (blue - 0.9).abs() < 1e-12
+            );
+            assert_eq!(ColorSpace::from_code(space.code()), Some(space));
+            assert_eq!(ColorSpace::parse(space.name()), Some(space));
+        }
+        //un rouge sRGB pur est à l'intérieur des gamuts plus larges
+        let red = ColorSpace::Rec2020.from_srgb(Color::new(1., 0., 0.));
+        assert!(red.red < 1. && red.green > 0. && red.blue > 0.);
+    }
+}
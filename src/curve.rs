@@ -0,0 +1,338 @@
+use std::hash::{Hash, Hasher};
+
+use crate::geometry::{Aabb, Geometry, Intersection};
+use crate::material::GenericMaterial;
+use crate::microfacet::frame;
+use crate::point::Point3;
+use crate::ray::Ray;
+use crate::vec::Vec3;
+
+//profondeur maximale de subdivision de la courbe
+const MAX_DEPTH: u32 = 10;
+
+//courbe de Bézier cubique d'épaisseur variable (cheveux, poils, brins d'herbe), intersectée par subdivision
+//dans le repère du rayon (Nakamaru et Ohno 2002, comme pbrt): la courbe est un ruban tourné vers le rayon.
+//u va du premier au dernier point de contrôle, v d'un bord du ruban à l'autre
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CurveShape {
+    //ruban plat, la normale fait face au rayon
+    Flat,
+    //même ruban, la normale est celle d'un cylindre de même épaisseur
+    Cylinder,
+}
+
+pub struct Curve {
+    pub control: [Point3; 4],
+    //épaisseurs aux deux extrémités, interpolées le long de u
+    pub width: [f64; 2],
+    pub shape: CurveShape,
+    pub(crate) material: GenericMaterial,
+}
+
+pub fn curve(
+    control: [Point3; 4],
+    width: [f64; 2],
+    shape: CurveShape,
+    material: GenericMaterial,
+) -> Geometry {
+    Geometry::Curve(Curve {
+        control,
+        width,
+        shape,
+        material,
+    })
+}
+
+impl Hash for Curve {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.control.hash(state);
+        self.width.map(f64::to_bits).hash(state);
+        self.shape.hash(state);
+        self.material.hash(state);
+    }
+}
+
+impl Curve {
+    //boîte de la courbe elle-même, élargie de la demi-épaisseur maximale: chaque coordonnée atteint ses extremums
+    //aux extrémités ou là où sa dérivée (un polynôme du second degré) s'annule
+    pub(crate) fn bounds(&self) -> Aabb {
+        let half = self.width[0].max(self.width[1]) / 2.;
+        let cp = self.control.map(|p| Vec3(p.0, p.1, p.2));
+        let mut parameters = vec![0., 1.];
+        for axis in 0..3 {
+            let coordinate = |v: Vec3| [v.0, v.1, v.2][axis];
+            //dérivée / 3 = a u² + b u + c
+            let d = [
+                coordinate(cp[1] - cp[0]),
+                coordinate(cp[2] - cp[1]),
+                coordinate(cp[3] - cp[2]),
+            ];
+            let (a, b, c) = (d[0] - 2. * d[1] + d[2], 2. * (d[1] - d[0]), d[0]);
+            if a.abs() < 1e-12 {
+                if b != 0. {
+                    parameters.push(-c / b);
+                }
+            } else if b * b >= 4. * a * c {
+                let root = (b * b - 4. * a * c).sqrt();
+                parameters.extend([(-b - root) / (2. * a), (-b + root) / (2. * a)]);
+            }
+        }
+        let first = cp[0];
+        let (min, max) = parameters
+            .into_iter()
+            .filter(|u| (0. ..=1.).contains(u))
+            .map(|u| bezier(&cp, u).0)
+            .fold((first, first), |(min, max), p| {
+                (
+                    Vec3(min.0.min(p.0), min.1.min(p.1), min.2.min(p.2)),
+                    Vec3(max.0.max(p.0), max.1.max(p.1), max.2.max(p.2)),
+                )
+            });
+        Aabb {
+            min: Point3(min.0 - half, min.1 - half, min.2 - half),
+            max: Point3(max.0 + half, max.1 + half, max.2 + half),
+        }
+    }
+
+    pub(crate) fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+        //repère du rayon: le rayon est l'axe z, les distances sont celles de l'espace
+        let length = ray.direction.len();
+        let chord = Vec3::points(self.control[0], self.control[3]);
+        let (x, y, z) = frame(ray.direction / length, chord);
+        let cp = self.control.map(|p| {
+            let v = Vec3::points(ray.origin, p);
+            Vec3(
+                v.scalar_product(x),
+                v.scalar_product(y),
+                v.scalar_product(z),
+            )
+        });
+        //assez de subdivisions pour que chaque morceau s'écarte de sa corde de moins de 5% de l'épaisseur
+        let curvature = (0..2)
+            .map(|i| cp[i] - 2. * cp[i + 1] + cp[i + 2])
+            .map(|d| d.0.abs().max(d.1.abs()).max(d.2.abs()))
+            .fold(0., f64::max);
+        let epsilon = self.width[0].max(self.width[1]) * 0.05;
+        let depth = if curvature > 0. && epsilon > 0. {
+            ((2f64.sqrt() * 6. * curvature / (8. * epsilon)).log2() / 2.)
+                .clamp(0., MAX_DEPTH as f64) as u32
+        } else {
+            0
+        };
+        let range = (t_min * length, t_max * length);
+        let (distance, u) = self.recurse(&cp, (0., 1.), depth, range)?;
+        Some(self.intersection(ray, distance / length, u))
+    }
+
+    //plus proche intersection du rayon avec le morceau de courbe entre u.0 et u.1: distance le long du rayon et u
+    fn recurse(
+        &self,
+        cp: &[Vec3; 4],
+        u: (f64, f64),
+        depth: u32,
+        range: (f64, f64),
+    ) -> Option<(f64, f64)> {
+        let half = self.width_at(u.0).max(self.width_at(u.1)) / 2.;
+        let (min, max) = cp[1..].iter().fold((cp[0], cp[0]), |(min, max), p| {
+            (
+                Vec3(min.0.min(p.0), min.1.min(p.1), min.2.min(p.2)),
+                Vec3(max.0.max(p.0), max.1.max(p.1), max.2.max(p.2)),
+            )
+        });
+        if min.0 - half > 0.
+            || max.0 + half < 0.
+            || min.1 - half > 0.
+            || max.1 + half < 0.
+            || min.2 - half > range.1
+            || max.2 + half < range.0
+        {
+            return None;
+        }
+        if depth > 0 {
+            let (first, second) = split(cp);
+            let middle = (u.0 + u.1) / 2.;
+            let near = self.recurse(&first, (u.0, middle), depth - 1, range);
+            let range = (range.0, near.map_or(range.1, |(distance, _)| distance));
+            return self
+                .recurse(&second, (middle, u.1), depth - 1, range)
+                .or(near);
+        }
+        //le rayon doit passer entre les perpendiculaires aux extrémités du morceau
+        let edge = |a: Vec3, b: Vec3| (b.1 - a.1) * -a.1 + a.0 * (a.0 - b.0);
+        if edge(cp[0], cp[1]) < 0. || edge(cp[3], cp[2]) < 0. {
+            return None;
+        }
+        //paramètre du point du morceau le plus proche du rayon, approché sur sa corde
+        let (dx, dy) = (cp[3].0 - cp[0].0, cp[3].1 - cp[0].1);
+        let denominator = dx * dx + dy * dy;
+        if denominator == 0. {
+            return None;
+        }
+        let w = (-cp[0].0 * dx - cp[0].1 * dy) / denominator;
+        let hit_u = (u.0 + (u.1 - u.0) * w).clamp(u.0, u.1);
+        let (point, _) = bezier(cp, w.clamp(0., 1.));
+        let width = self.width_at(hit_u);
+        if point.0 * point.0 + point.1 * point.1 > width * width / 4.
+            || point.2 < range.0
+            || point.2 > range.1
+        {
+            return None;
+        }
+        Some((point.2, hit_u))
+    }
+
+    fn intersection(&self, ray: &Ray, t: f64, u: f64) -> Intersection {
+        let hit_point = ray.at(t);
+        let origin = Point3(0., 0., 0.);
+        let cp = self.control.map(|p| Vec3::points(origin, p));
+        let (centre, derivative) = bezier(&cp, u);
+        let tangent = derivative.unit();
+        //normale du ruban: vers le rayon, perpendiculaire à la courbe
+        let towards = -ray.direction.unit();
+        let mut flat = towards - towards.scalar_product(tangent) * tangent;
+        if flat.sqr_len() < 1e-12 {
+            flat = frame(tangent, Vec3(0., 0., 0.)).0;
+        }
+        let flat = flat.unit();
+        //décalage signé depuis l'axe de la courbe, dans [-1, 1]
+        let side = flat.cross_product(tangent);
+        let offset = Vec3::points(origin + centre, hit_point).scalar_product(side);
+        let h = (2. * offset / self.width_at(u)).clamp(-1., 1.);
+        let normale = match self.shape {
+            CurveShape::Flat => flat,
+            CurveShape::Cylinder => (1. - h * h).sqrt() * flat + h * side,
+        };
+        let mut intersection = Intersection::new(ray, t, hit_point, normale, self.material.clone());
+        intersection.uv = (u, (h + 1.) / 2.);
+        intersection.tangent = tangent;
+        intersection
+    }
+
+    fn width_at(&self, u: f64) -> f64 {
+        self.width[0] + (self.width[1] - self.width[0]) * u
+    }
+}
+
+//point et dérivée de la courbe en u (algorithme de de Casteljau)
+fn bezier(cp: &[Vec3; 4], u: f64) -> (Vec3, Vec3) {
+    let lerp = |a: Vec3, b: Vec3| (1. - u) * a + u * b;
+    let a = [lerp(cp[0], cp[1]), lerp(cp[1], cp[2]), lerp(cp[2], cp[3])];
+    let b = [lerp(a[0], a[1]), lerp(a[1], a[2])];
+    (lerp(b[0], b[1]), 3. * (b[1] - b[0]))
+}
+
+//les deux moitiés de la courbe
+fn split(cp: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let middle = |a: Vec3, b: Vec3| 0.5 * (a + b);
+    let a = [
+        middle(cp[0], cp[1]),
+        middle(cp[1], cp[2]),
+        middle(cp[2], cp[3]),
+    ];
+    let b = [middle(a[0], a[1]), middle(a[1], a[2])];
+    let c = middle(b[0], b[1]);
+    ([cp[0], a[0], b[0], c], [c, b[1], a[2], cp[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Face;
+    use crate::material::diffuse;
+
+    //arc dans le plan xy, de x = -1 à x = 1, qui s'effile
+    fn arc(shape: CurveShape) -> Curve {
+        Curve {
+            control: [
+                Point3(-1., 0., 0.),
+                Point3(-0.5, 0.5, 0.),
+                Point3(0.5, 0.5, 0.),
+                Point3(1., 0., 0.),
+            ],
+            width: [0.2, 0.1],
+            shape,
+            material: diffuse(0.5, 0.5, 0.5),
+        }
+    }
+
+    fn towards_z(x: f64, y: f64) -> Ray {
+        Ray {
+            origin: Point3(x, y, -2.),
+            direction: Vec3(0., 0., 2.),
+        }
+    }
+
+    #[test]
+    fn rays_hit_the_curve_within_its_width() {
+        let curve = arc(CurveShape::Flat);
+        //sommet de l'arc: y = 0.375 en u = 0.5, épaisseur 0.15
+        let hit = curve
+            .hit(&towards_z(0., 0.375), 0.001, f64::INFINITY)
+            .unwrap();
+        assert!((hit.factor - 1.).abs() < 1e-9);
+        assert!((hit.uv.0 - 0.5).abs() < 1e-3);
+        assert!((hit.uv.1 - 0.5).abs() < 1e-3);
+        assert!((hit.normale.2 + 1.).abs() < 1e-9);
+        assert!((hit.tangent.0 - 1.).abs() < 1e-6);
+        assert!(matches!(hit.face, Face::Front));
+        //bord du ruban
+        let edge = curve
+            .hit(&towards_z(0., 0.44), 0.001, f64::INFINITY)
+            .unwrap();
+        assert!(edge.uv.1 < 0.1 || edge.uv.1 > 0.9);
+        assert!(curve
+            .hit(&towards_z(0., 0.46), 0.001, f64::INFINITY)
+            .is_none());
+        //au-delà des extrémités, et hors de l'intervalle
+        assert!(curve
+            .hit(&towards_z(1.2, 0.), 0.001, f64::INFINITY)
+            .is_none());
+        assert!(curve.hit(&towards_z(0., 0.375), 0.001, 0.9).is_none());
+    }
+
+    #[test]
+    fn curves_taper_along_u() {
+        let curve = arc(CurveShape::Flat);
+        let cp = curve.control.map(|p| Vec3(p.0, p.1, p.2));
+        for u in [0.1, 0.3, 0.9] {
+            //de part et d'autre du bord, perpendiculairement à la courbe
+            let (p, d) = bezier(&cp, u);
+            let across = Vec3(-d.1, d.0, 0.).unit();
+            let half = curve.width_at(u) / 2.;
+            let inside = p + 0.9 * half * across;
+            let outside = p + 1.1 * half * across;
+            assert!(curve
+                .hit(&towards_z(inside.0, inside.1), 0.001, 10.)
+                .is_some());
+            assert!(curve
+                .hit(&towards_z(outside.0, outside.1), 0.001, 10.)
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn cylinder_normals_turn_towards_the_edges() {
+        let curve = arc(CurveShape::Cylinder);
+        let centre = curve.hit(&towards_z(0., 0.375), 0.001, 10.).unwrap();
+        assert!((centre.normale.2 + 1.).abs() < 1e-6);
+        let edge = curve.hit(&towards_z(0., 0.445), 0.001, 10.).unwrap();
+        //normale presque perpendiculaire au rayon, vers l'extérieur de l'arc
+        assert!(edge.normale.2 > -0.4 && edge.normale.1 > 0.9);
+        assert!((edge.normale.len() - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bounds_contain_the_curve() {
+        let curve = arc(CurveShape::Flat);
+        let bounds = curve.bounds();
+        let cp = curve.control.map(|p| Vec3(p.0, p.1, p.2));
+        for i in 0..=20 {
+            let (p, _) = bezier(&cp, i as f64 / 20.);
+            assert!(p.0 - 0.1 >= bounds.min.0 && p.0 + 0.1 <= bounds.max.0);
+            assert!(p.1 - 0.1 >= bounds.min.1 && p.1 + 0.1 <= bounds.max.1);
+        }
+        //au plus près de la courbe: sommet de l'arc en 0.375, extrémités en x = ±1
+        assert!((bounds.max.1 - 0.475).abs() < 1e-12);
+        assert!((bounds.min.0 + 1.1).abs() < 1e-12 && (bounds.max.0 - 1.1).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::aov::Aov;
+use crate::color::Color;
+use crate::film::Film;
+
+//débruitage par ondelettes à trous guidé par les aovs (Dammertz et al., "Edge-avoiding à-trous wavelet transform").
+//chaque itération est un flou 5x5 dont les pas s'espacent (1, 2, 4, ...): les poids chutent entre pixels
+//de couleurs, normales, albedos ou profondeurs différents, ce qui préserve les contours.
+//l'écart de couleur toléré suit la variance locale du bruit, estimée sur un voisinage 3x3 puis filtrée avec l'image.
+//l'éclairage est filtré séparément de l'albedo (couleur / albedo) pour ne pas flouter les couleurs des objets.
+//le fond, sans bruit, n'est pas filtré. sans aov albedo, normal ou depth, le filtre se contente des guides disponibles
+
+const ITERATIONS: u32 = 5;
+//noyau B3-spline
+const KERNEL: [f64; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+//écart de luminance toléré, en nombre d'écarts types du bruit
+const SIGMA_LUMINANCE: f64 = 4.;
+const SIGMA_ALBEDO: f64 = 0.1;
+//exposant du produit scalaire des normales
+const SIGMA_NORMAL: i32 = 64;
+//la longueur de la normale moyenne est la fraction du pixel couverte par un objet: un pixel de bord
+//à moitié sur le ciel ne se mélange pas aux pixels entièrement couverts
+const SIGMA_COVERAGE: f64 = 0.1;
+//écart relatif de profondeur toléré
+const SIGMA_DEPTH: f64 = 0.05;
+
+struct Guide {
+    albedo: Option<Vec<[f64; 3]>>,
+    normal: Option<Vec<[f64; 3]>>,
+    depth: Option<Vec<f64>>,
+}
+
+//retourne un film dont l'image est débruitée; nombres d'échantillons et aovs sont conservés
+pub fn denoise(film: &Film) -> Film {
+    let (width, height) = (film.width() as usize, film.height() as usize);
+    let layer = |aov| {
+        film.aovs().contains(&aov).then(|| {
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x as u32, y as u32)))
+                .map(|(x, y)| film.aov(aov, x, y).unwrap())
+                .collect::<Vec<_>>()
+        })
+    };
+    let guide = Guide {
+        albedo: layer(Aov::Albedo),
+        normal: layer(Aov::Normal),
+        depth: layer(Aov::Depth).map(|depth| depth.iter().map(|d| d[0]).collect()),
+    };
+    let albedo = |index: usize| match &guide.albedo {
+        Some(albedo) => albedo[index].map(|a| if a > 0.01 { a } else { 1. }),
+        None => [1.; 3],
+    };
+
+    //éclairage: couleur divisée par l'albedo du pixel
+    let mut lighting: Vec<[f64; 3]> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let c = film.pixel(x as u32, y as u32);
+            let a = albedo(y * width + x);
+            [c.red / a[0], c.green / a[1], c.blue / a[2]]
+        })
+        .collect();
+    let mut variance = spatial_variance(&lighting, width, height);
+    for iteration in 0..ITERATIONS {
+        (lighting, variance) = filter(&lighting, &variance, &guide, width, height, iteration);
+    }
+
+    let (sums, samples) = film.raw();
+    let pixels = lighting
+        .iter()
+        .enumerate()
+        .map(|(index, l)| {
+            let a = albedo(index);
+            Color::new(l[0] * a[0], l[1] * a[1], l[2] * a[2]) * samples[index] as f64
+        })
+        .collect();
+    debug_assert_eq!(sums.len(), width * height);
+    Film::from_raw(
+        film.width(),
+        film.height(),
+        pixels,
+        samples.to_vec(),
+        film.aovs().to_vec(),
+        film.raw_layers().to_vec(),
+    )
+    .in_space(film.space())
+}
+
+//une itération: retourne l'image filtrée et sa variance
+fn filter(
+    input: &[[f64; 3]],
+    variance: &[f64],
+    guide: &Guide,
+    width: usize,
+    height: usize,
+    iteration: u32,
+) -> (Vec<[f64; 3]>, Vec<f64>) {
+    let step = 1 << iteration;
+    let mut output = Vec::with_capacity(input.len());
+    let mut output_variance = Vec::with_capacity(input.len());
+    for y in 0..height {
+        for x in 0..width {
+            let p = y * width + x;
+            if guide.background(p) {
+                output.push(input[p]);
+                output_variance.push(variance[p]);
+                continue;
+            }
+            let tolerance = SIGMA_LUMINANCE * variance[p].sqrt() + 1e-4;
+            let mut sum = [0.; 3];
+            let mut sum_variance = 0.;
+            let mut total = 0.;
+            for (j, ky) in KERNEL.iter().enumerate() {
+                let qy = y as isize + (j as isize - 2) * step;
+                if qy < 0 || qy >= height as isize {
+                    continue;
+                }
+                for (i, kx) in KERNEL.iter().enumerate() {
+                    let qx = x as isize + (i as isize - 2) * step;
+                    if qx < 0 || qx >= width as isize {
+                        continue;
+                    }
+                    let q = qy as usize * width + qx as usize;
+                    let weight = kx
+                        * ky
+                        * (-(luminance(&input[p]) - luminance(&input[q])).abs() / tolerance).exp()
+                        * guide.weight(p, q);
+                    for (s, v) in sum.iter_mut().zip(input[q]) {
+                        *s += weight * v;
+                    }
+                    sum_variance += weight * weight * variance[q];
+                    total += weight;
+                }
+            }
+            //le pixel central a toujours un poids non nul
+            output.push(sum.map(|s| s / total));
+            output_variance.push(sum_variance / (total * total));
+        }
+    }
+    (output, output_variance)
+}
+
+//variance de la luminance sur le voisinage 3x3 de chaque pixel
+fn spatial_variance(input: &[[f64; 3]], width: usize, height: usize) -> Vec<f64> {
+    let mut variance = Vec::with_capacity(input.len());
+    for y in 0..height {
+        for x in 0..width {
+            let (mut sum, mut sum2, mut count) = (0., 0., 0.);
+            for qy in y.saturating_sub(1)..(y + 2).min(height) {
+                for qx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let l = luminance(&input[qy * width + qx]);
+                    sum += l;
+                    sum2 += l * l;
+                    count += 1.;
+                }
+            }
+            let mean = sum / count;
+            variance.push((sum2 / count - mean * mean).max(0.));
+        }
+    }
+    variance
+}
+
+fn luminance(c: &[f64; 3]) -> f64 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+impl Guide {
+    //pixel où aucun échantillon n'a touché d'objet
+    fn background(&self, p: usize) -> bool {
+        self.normal
+            .as_ref()
+            .is_some_and(|normal| normal[p] == [0.; 3])
+    }
+
+    fn weight(&self, p: usize, q: usize) -> f64 {
+        let mut weight = 1.;
+        if let Some(albedo) = &self.albedo {
+            weight *= gaussian(distance2(&albedo[p], &albedo[q]), SIGMA_ALBEDO);
+        }
+        if let Some(normal) = &self.normal {
+            let (np, nq) = (normal[p], normal[q]);
+            let (cp, cq) = (length(&np), length(&nq));
+            if cq == 0. {
+                return 0.;
+            }
+            let dot = (np[0] * nq[0] + np[1] * nq[1] + np[2] * nq[2]) / (cp * cq);
+            weight *= dot.max(0.).powi(SIGMA_NORMAL) * gaussian((cp - cq).powi(2), SIGMA_COVERAGE);
+        }
+        if let Some(depth) = &self.depth {
+            let (dp, dq) = (depth[p], depth[q]);
+            weight *= (-(dp - dq).abs() / (SIGMA_DEPTH * dp.max(dq).max(1e-6))).exp();
+        }
+        weight
+    }
+}
+
+fn distance2(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+fn length(v: &[f64; 3]) -> f64 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+fn gaussian(distance2: f64, sigma: f64) -> f64 {
+    (-distance2 / (sigma * sigma)).exp()
+}
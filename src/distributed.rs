@@ -0,0 +1,439 @@
+use std::collections::VecDeque;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::aov;
+use crate::checkpoint::{read_aovs, read_f64, read_u32, read_u64, scene_hash};
+use crate::color::Color;
+use crate::colorspace::ColorSpace;
+use crate::film::{Film, TileSamples};
+use crate::render::{render_tile, Scene, Settings};
+use crate::tile::{spiral_tiles, Tile};
+
+//rendu distribué: le coordinateur découpe l'image en unités de travail (une tuile pour une passe)
+//et les distribue par TCP aux workers, qui sont ce même binaire lancé avec --worker.
+//les workers reconstruisent la scène à partir de la graine, le hash de scène garantit qu'ils rendent la même.
+//chaque pixel étant échantillonné à partir de (graine, passe, pixel), le résultat ne dépend pas du worker
+
+//coordinateur -> worker: graine, hash de scène, largeur, hauteur, rebonds max, aovs, espace de travail, mode spectral, rendu stylisé
+const SETUP: u8 = 1;
+//coordinateur -> worker: tuile (x, y, largeur, hauteur), passe, échantillons par pixel
+const WORK: u8 = 2;
+//worker -> coordinateur: somme des échantillons de chaque pixel de la tuile, puis valeurs des aovs
+const RESULT: u8 = 3;
+//worker -> coordinateur: le worker est vivant, envoyé pendant les rendus longs
+const HEARTBEAT: u8 = 4;
+//coordinateur -> worker: plus de travail
+const DONE: u8 = 5;
+//worker -> coordinateur: la scène reconstruite ne correspond pas au hash reçu
+const REJECT: u8 = 6;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+//sans nouvelles d'un worker pendant ce délai, il est considéré comme mort et son travail est redistribué
+const WORKER_TIMEOUT: Duration = Duration::from_secs(10);
+
+//étapes du rendu distribué, transmises à l'appelant de coordinate pour qu'il les affiche
+#[derive(Debug)]
+pub enum Event<'a> {
+    //adresse effective du coordinateur, utile quand le port demandé est 0
+    Listening(SocketAddr),
+    Connected(&'a str),
+    Finished(&'a str),
+    Lost(&'a str, &'a Error),
+    //unités de travail restantes, sur le total
+    Remaining(usize, usize),
+}
+
+#[derive(Copy, Clone)]
+struct Unit {
+    tile: Tile,
+    pass: u32,
+    samples: u32,
+}
+
+struct Queue {
+    pending: VecDeque<Unit>,
+    remaining: usize,
+}
+
+struct Shared {
+    setup: Vec<u8>,
+    //nombre de valeurs d'aovs par pixel
+    stride: usize,
+    queue: Mutex<Queue>,
+    changed: Condvar,
+    film: Mutex<Film>,
+}
+
+//distribue le rendu de la scène aux workers qui se connectent à `address`, et retourne le film complet.
+//`report` reçoit les événements, depuis les threads des connexions
+pub fn coordinate<F>(
+    address: &str,
+    scene: &Scene,
+    settings: &Settings,
+    pass_samples: u32,
+    report: F,
+) -> std::io::Result<Film>
+where
+    F: Fn(&Event) + Send + Sync + 'static,
+{
+    let tiles = spiral_tiles(settings.width, settings.height, settings.tile_size);
+    let mut pending = VecDeque::new();
+    let mut done = 0;
+    let mut pass = 0;
+    while done < settings.samples_per_pixel {
+        let samples = pass_samples.min(settings.samples_per_pixel - done);
+        pending.extend(tiles.iter().map(|tile| Unit {
+            tile: *tile,
+            pass,
+            samples,
+        }));
+        done += samples;
+        pass += 1;
+    }
+
+    let mut setup = vec![SETUP];
+    setup.extend_from_slice(&settings.seed.to_le_bytes());
+    setup.extend_from_slice(&scene_hash(scene, settings).to_le_bytes());
+    setup.extend_from_slice(&settings.width.to_le_bytes());
+    setup.extend_from_slice(&settings.height.to_le_bytes());
+    setup.extend_from_slice(&(settings.max_reflection as u32).to_le_bytes());
+    setup.extend_from_slice(&(settings.aovs.len() as u32).to_le_bytes());
+    setup.extend(settings.aovs.iter().map(|aov| aov.code()));
+    setup.push(settings.working_space.code());
+    setup.push(settings.spectral as u8);
+    setup.push(settings.toon as u8);
+
+    let shared = Arc::new(Shared {
+        setup,
+        stride: aov::stride(&settings.aovs),
+        queue: Mutex::new(Queue {
+            remaining: pending.len(),
+            pending,
+        }),
+        changed: Condvar::new(),
+        film: Mutex::new(
+            Film::with_aovs(settings.width, settings.height, &settings.aovs)
+                .in_space(settings.working_space),
+        ),
+    });
+
+    let listener = TcpListener::bind(address)?;
+    let report = Arc::new(report);
+    report(&Event::Listening(listener.local_addr()?));
+    let accepting = Arc::clone(&shared);
+    let reporting = Arc::clone(&report);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let shared = Arc::clone(&accepting);
+            let report = Arc::clone(&reporting);
+            std::thread::spawn(move || {
+                let peer = stream
+                    .peer_addr()
+                    .map_or_else(|_| "?".to_string(), |a| a.to_string());
+                report(&Event::Connected(&peer));
+                match serve_worker(stream, &shared) {
+                    Ok(()) => report(&Event::Finished(&peer)),
+                    Err(error) => report(&Event::Lost(&peer, &error)),
+                }
+            });
+        }
+    });
+
+    let mut queue = shared.queue.lock().unwrap();
+    let total = queue.remaining;
+    let mut reported = total;
+    while queue.remaining > 0 {
+        queue = shared.changed.wait(queue).unwrap();
+        if queue.remaining != reported {
+            reported = queue.remaining;
+            report(&Event::Remaining(reported, total));
+        }
+    }
+    drop(queue);
+    let film = shared.film.lock().unwrap().clone();
+    //réveille les connexions en attente pour qu'elles libèrent leurs workers
+    shared.changed.notify_all();
+    Ok(film)
+}
+
+fn serve_worker(stream: TcpStream, shared: &Shared) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(WORKER_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    writer.write_all(&shared.setup)?;
+    writer.flush()?;
+
+    loop {
+        let unit = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(unit) = queue.pending.pop_front() {
+                    break unit;
+                }
+                if queue.remaining == 0 {
+                    writer.write_all(&[DONE])?;
+                    return writer.flush();
+                }
+                queue = shared.changed.wait(queue).unwrap();
+            }
+        };
+        match process(unit, shared.stride, &mut reader, &mut writer) {
+            Ok(result) => {
+                shared
+                    .film
+                    .lock()
+                    .unwrap()
+                    .add_tile(&unit.tile, &result, unit.samples, unit.pass);
+                shared.queue.lock().unwrap().remaining -= 1;
+                shared.changed.notify_all();
+            }
+            Err(error) => {
+                //le travail du worker perdu est remis en tête de file pour un autre worker
+                shared.queue.lock().unwrap().pending.push_front(unit);
+                shared.changed.notify_all();
+                return Err(error);
+            }
+        }
+    }
+}
+
+fn process<R: Read, W: Write>(
+    unit: Unit,
+    stride: usize,
+    reader: &mut R,
+    writer: &mut W,
+) -> std::io::Result<TileSamples> {
+    let Tile {
+        x,
+        y,
+        width,
+        height,
+    } = unit.tile;
+    writer.write_all(&[WORK])?;
+    for value in [x, y, width, height, unit.pass, unit.samples] {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.flush()?;
+
+    loop {
+        match read_u8(reader)? {
+            HEARTBEAT => continue,
+            RESULT => {
+                let count = read_u32(reader)? as usize;
+                if count != unit.tile.pixel_count() {
+                    return Err(Error::new(ErrorKind::InvalidData, "wrong tile size"));
+                }
+                let mut colors = Vec::with_capacity(count);
+                for _ in 0..count {
+                    colors.push(Color::new(
+                        read_f64(reader)?,
+                        read_f64(reader)?,
+                        read_f64(reader)?,
+                    ));
+                }
+                let aovs = (0..count * stride)
+                    .map(|_| read_f64(reader))
+                    .collect::<std::io::Result<_>>()?;
+                return Ok(TileSamples { colors, aovs });
+            }
+            REJECT => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "worker rejected the scene",
+                ))
+            }
+            other => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unexpected message {}", other),
+                ))
+            }
+        }
+    }
+}
+
+//se connecte au coordinateur avec une connexion par thread, chaque connexion rendant une tuile à la fois
+pub fn work<F>(address: &str, threads: usize, scene: F) -> std::io::Result<()>
+where
+    F: Fn(u64) -> Scene + Sync,
+{
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| scope.spawn(|| work_connection(address, &scene)))
+            .collect();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().unwrap())
+    })
+}
+
+fn work_connection<F>(address: &str, scene: &F) -> std::io::Result<()>
+where
+    F: Fn(u64) -> Scene,
+{
+    let stream = TcpStream::connect(address)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    //partagé avec le thread de heartbeat, un message est toujours écrit en entier sous le verrou
+    let writer = Mutex::new(BufWriter::new(stream));
+
+    if read_u8(&mut reader)? != SETUP {
+        return Err(Error::new(ErrorKind::InvalidData, "expected setup"));
+    }
+    let seed = read_u64(&mut reader)?;
+    let expected_hash = read_u64(&mut reader)?;
+    let width = read_u32(&mut reader)?;
+    let height = read_u32(&mut reader)?;
+    let max_reflection = read_u32(&mut reader)? as u16;
+    let aovs = read_aovs(&mut reader)?;
+    let working_space = ColorSpace::from_code(read_u8(&mut reader)?)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "unknown colour space"))?;
+    let spectral = read_u8(&mut reader)? != 0;
+    let toon = read_u8(&mut reader)? != 0;
+
+    let scene = scene(seed);
+    let settings = Settings {
+        width,
+        height,
+        samples_per_pixel: 0,
+        max_reflection,
+        tile_size: 0,
+        threads: 1,
+        seed,
+        aovs,
+        working_space,
+        spectral,
+        toon,
+    };
+    if scene_hash(&scene, &settings) != expected_hash {
+        let mut writer = writer.lock().unwrap();
+        writer.write_all(&[REJECT])?;
+        writer.flush()?;
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "scene differs from the coordinator's",
+        ));
+    }
+
+    let (stop, stopped) = channel::<()>();
+    std::thread::scope(|scope| {
+        let writer = &writer;
+        scope.spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(HEARTBEAT_INTERVAL) {
+                let mut writer = writer.lock().unwrap();
+                if writer
+                    .write_all(&[HEARTBEAT])
+                    .and_then(|_| writer.flush())
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+        let result = render_units(&mut reader, writer, &scene, &settings);
+        drop(stop);
+        result
+    })
+}
+
+fn render_units<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &Mutex<W>,
+    scene: &Scene,
+    settings: &Settings,
+) -> std::io::Result<()> {
+    loop {
+        let message = match read_u8(reader) {
+            Ok(message) => message,
+            //le coordinateur a terminé et fermé la connexion
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(error) => return Err(error),
+        };
+        match message {
+            DONE => return Ok(()),
+            WORK => {
+                let tile = Tile {
+                    x: read_u32(reader)?,
+                    y: read_u32(reader)?,
+                    width: read_u32(reader)?,
+                    height: read_u32(reader)?,
+                };
+                let pass = read_u32(reader)?;
+                let samples = read_u32(reader)?;
+                //une tuile qui déborde de l'image ferait paniquer le rendu
+                let inside = |start: u32, size: u32, limit: u32| {
+                    start.checked_add(size).is_some_and(|end| end <= limit)
+                };
+                if !inside(tile.x, tile.width, settings.width)
+                    || !inside(tile.y, tile.height, settings.height)
+                {
+                    return Err(Error::new(ErrorKind::InvalidData, "tile outside the image"));
+                }
+                let result = render_tile(scene, settings, pass, samples, &tile);
+
+                let mut writer = writer.lock().unwrap();
+                writer.write_all(&[RESULT])?;
+                writer.write_all(&(result.colors.len() as u32).to_le_bytes())?;
+                for color in result.colors {
+                    writer.write_all(&color.red.to_le_bytes())?;
+                    writer.write_all(&color.green.to_le_bytes())?;
+                    writer.write_all(&color.blue.to_le_bytes())?;
+                }
+                for value in result.aovs {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                writer.flush()?;
+            }
+            other => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unexpected message {}", other),
+                ))
+            }
+        }
+    }
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> std::io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Angle;
+    use crate::cam::Camera;
+    use crate::point::Point3;
+    use crate::vec::Vec3;
+    use std::io::Cursor;
+
+    #[test]
+    fn workers_reject_tiles_outside_the_image() {
+        let camera = Camera::new(
+            Angle::Deg(40.),
+            2.,
+            0.,
+            Point3(0., 0., 1.),
+            Point3(0., 0., 0.),
+            Vec3(0., 1., 0.),
+        );
+        let scene = Scene::new(camera, Vec::new());
+        let settings = Settings::new(8, 4, 1);
+        for (x, y, width, height) in [(4, 0, 5, 4), (0, 2, 8, 3), (u32::MAX, 0, 2, 1)] {
+            let mut message = vec![WORK];
+            for value in [x, y, width, height, 0, 1] {
+                message.extend_from_slice(&value.to_le_bytes());
+            }
+            let writer = Mutex::new(Vec::new());
+            let error =
+                render_units(&mut Cursor::new(message), &writer, &scene, &settings).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+            assert!(writer.into_inner().unwrap().is_empty());
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//encodeur OpenEXR minimal: une seule partie, lignes non compressées, canaux en flottants 32 bits
+//les calques sont nommés "calque.canal" (ex: normal.X), l'image principale utilise R, G et B
+pub struct Exr {
+    width: u32,
+    height: u32,
+    channels: Vec<(String, Vec<f32>)>,
+    chromaticities: Option<[f64; 8]>,
+}
+
+impl Exr {
+    pub fn new(width: u32, height: u32) -> Exr {
+        Exr {
+            width,
+            height,
+            channels: Vec::new(),
+            chromaticities: None,
+        }
+    }
+
+    //primaires et blanc (xy) de l'espace des canaux R, G, B; sans cet attribut les lecteurs supposent du Rec.709
+    pub fn chromaticities(&mut self, xy: [f64; 8]) {
+        self.chromaticities = Some(xy);
+    }
+
+    //values: une valeur par pixel, ligne par ligne depuis le haut de l'image
+    pub fn channel(&mut self, name: &str, values: Vec<f32>) {
+        self.channels.push((name.to_string(), values));
+    }
+
+    pub fn encode(mut self) -> Vec<u8> {
+        //le format impose des canaux triés par nom, dans l'en-tête comme dans les données
+        self.channels.sort_by(|a, b| a.0.cmp(&b.0));
+        let long_names = self.channels.iter().any(|(name, _)| name.len() > 31);
+
+        let mut exr = vec![0x76, 0x2f, 0x31, 0x01];
+        //version 2, bit 10: noms de plus de 31 caractères
+        let flags: u32 = if long_names { 0x400 } else { 0 };
+        exr.extend_from_slice(&(2 | flags).to_le_bytes());
+
+        let mut chlist = Vec::new();
+        for (name, _) in &self.channels {
+            chlist.extend_from_slice(name.as_bytes());
+            chlist.push(0);
+            //type FLOAT, pLinear, 3 octets réservés, échantillonnage 1x1
+            chlist.extend_from_slice(&2i32.to_le_bytes());
+            chlist.extend_from_slice(&[0, 0, 0, 0]);
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+        }
+        chlist.push(0);
+        let mut window = Vec::with_capacity(16);
+        for value in [0, 0, self.width as i32 - 1, self.height as i32 - 1] {
+            window.extend_from_slice(&value.to_le_bytes());
+        }
+        attribute(&mut exr, "channels", "chlist", &chlist);
+        if let Some(xy) = self.chromaticities {
+            let bytes: Vec<u8> = xy.iter().flat_map(|v| (*v as f32).to_le_bytes()).collect();
+            attribute(&mut exr, "chromaticities", "chromaticities", &bytes);
+        }
+        attribute(&mut exr, "compression", "compression", &[0]);
+        attribute(&mut exr, "dataWindow", "box2i", &window);
+        attribute(&mut exr, "displayWindow", "box2i", &window);
+        attribute(&mut exr, "lineOrder", "lineOrder", &[0]);
+        attribute(&mut exr, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+        attribute(&mut exr, "screenWindowCenter", "v2f", &[0; 8]);
+        attribute(&mut exr, "screenWindowWidth", "float", &1f32.to_le_bytes());
+        exr.push(0);
+
+        //table des positions de chaque ligne, puis les lignes: numéro, taille, et chaque canal à la suite
+        let line_size = self.width as usize * self.channels.len() * 4;
+        let first_line = exr.len() + self.height as usize * 8;
+        for y in 0..self.height as usize {
+            let offset = (first_line + y * (line_size + 8)) as u64;
+            exr.extend_from_slice(&offset.to_le_bytes());
+        }
+        let width = self.width as usize;
+        for y in 0..self.height as usize {
+            exr.extend_from_slice(&(y as i32).to_le_bytes());
+            exr.extend_from_slice(&(line_size as i32).to_le_bytes());
+            for (_, values) in &self.channels {
+                for value in &values[y * width..(y + 1) * width] {
+                    exr.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        exr
+    }
+}
+
+fn attribute(exr: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    exr.extend_from_slice(name.as_bytes());
+    exr.push(0);
+    exr.extend_from_slice(kind.as_bytes());
+    exr.push(0);
+    exr.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    exr.extend_from_slice(value);
+}
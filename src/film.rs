@@ -0,0 +1,160 @@
+use crate::aov::{self, Aov};
+use crate::color::Color;
+use crate::colorspace::ColorSpace;
+use crate::tile::Tile;
+
+//le film accumule la somme des échantillons de chaque pixel et leur nombre,
+//la moyenne n'est calculée qu'à la lecture
+#[derive(Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+    samples: Vec<u32>,
+    aovs: Vec<Aov>,
+    //valeurs des aovs, pixel par pixel: toutes les composantes de chaque aov dans l'ordre de `aovs`
+    layers: Vec<f64>,
+    //espace des couleurs accumulées, celui du rendu
+    space: ColorSpace,
+}
+
+//résultat du rendu d'une tuile, pixel par pixel, ligne par ligne:
+//somme des échantillons, et valeurs des aovs rangées comme dans le film
+pub struct TileSamples {
+    pub colors: Vec<Color>,
+    pub aovs: Vec<f64>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Film {
+        Film::with_aovs(width, height, &[])
+    }
+
+    pub fn with_aovs(width: u32, height: u32, aovs: &[Aov]) -> Film {
+        let size = (width * height) as usize;
+        Film {
+            width,
+            height,
+            pixels: vec![Color::EMPTY; size],
+            samples: vec![0; size],
+            aovs: aovs.to_vec(),
+            layers: vec![0.; size * aov::stride(aovs)],
+            space: ColorSpace::Srgb,
+        }
+    }
+
+    pub fn in_space(self, space: ColorSpace) -> Film {
+        Film { space, ..self }
+    }
+
+    //reconstruit un film à partir de sommes et de nombres d'échantillons (cf checkpoint)
+    pub fn from_raw(
+        width: u32,
+        height: u32,
+        pixels: Vec<Color>,
+        samples: Vec<u32>,
+        aovs: Vec<Aov>,
+        layers: Vec<f64>,
+    ) -> Film {
+        Film {
+            width,
+            height,
+            pixels,
+            samples,
+            aovs,
+            layers,
+            space: ColorSpace::Srgb,
+        }
+    }
+
+    pub fn raw(&self) -> (&[Color], &[u32]) {
+        (&self.pixels, &self.samples)
+    }
+
+    pub fn raw_layers(&self) -> &[f64] {
+        &self.layers
+    }
+
+    pub fn aovs(&self) -> &[Aov] {
+        &self.aovs
+    }
+
+    pub fn space(&self) -> ColorSpace {
+        self.space
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    //ajoute les échantillons de la passe `pass` rendus pour une tuile
+    //les aovs non moyennés (identifiants) ne sont retenus que pour la première passe,
+    //pour que le résultat ne dépende pas de l'ordre d'arrivée des tuiles
+    pub fn add_tile(&mut self, tile: &Tile, result: &TileSamples, samples: u32, pass: u32) {
+        let stride = aov::stride(&self.aovs);
+        for (n, ((x, y), color)) in tile.pixels().zip(&result.colors).enumerate() {
+            let index = self.index(x, y);
+            self.pixels[index] = self.pixels[index] + *color;
+            self.samples[index] += samples;
+
+            let values = &result.aovs[n * stride..(n + 1) * stride];
+            let layers = &mut self.layers[index * stride..(index + 1) * stride];
+            let mut offset = 0;
+            for aov in &self.aovs {
+                let count = aov.channels().len();
+                for k in offset..offset + count {
+                    if aov.averaged() {
+                        layers[k] += values[k];
+                    } else if pass == 0 {
+                        layers[k] = values[k];
+                    }
+                }
+                offset += count;
+            }
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let index = self.index(x, y);
+        match self.samples[index] {
+            0 => Color::EMPTY,
+            samples => self.pixels[index] / samples as f64,
+        }
+    }
+
+    //valeur de l'aov pour le pixel (moyenne des échantillons), None si le film ne le contient pas
+    //les composantes inutilisées valent 0
+    pub fn aov(&self, aov: Aov, x: u32, y: u32) -> Option<[f64; 3]> {
+        let mut offset = 0;
+        for layer in &self.aovs {
+            if *layer == aov {
+                let index = self.index(x, y);
+                let start = index * aov::stride(&self.aovs) + offset;
+                let samples = match self.samples[index] {
+                    0 => return Some([0.; 3]),
+                    samples if aov.averaged() => samples as f64,
+                    _ => 1.,
+                };
+                let mut values = [0.; 3];
+                for (k, value) in values.iter_mut().enumerate().take(aov.channels().len()) {
+                    *value = self.layers[start + k] / samples;
+                }
+                return Some(values);
+            }
+            offset += layer.channels().len();
+        }
+        None
+    }
+
+    pub fn line(&self, y: u32) -> Vec<Color> {
+        (0..self.width).map(|x| self.pixel(x, y)).collect()
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+}
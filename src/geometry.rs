@@ -1,8 +1,11 @@
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
+use crate::curve::Curve;
 use crate::material::GenericMaterial;
 use crate::point::Point3;
 use crate::ray::Ray;
+use crate::stats;
 use crate::vec::Vec3;
 
 pub enum Face {
@@ -17,6 +20,12 @@ pub struct Intersection {
     pub face: Face,
     pub factor: f64,
     pub material: GenericMaterial,
+    //coordonnées de texture du point touché
+    pub uv: (f64, f64),
+    //indice de l'objet touché dans la liste des objets de la scène
+    pub object: u32,
+    //direction de u croissant sur la surface (sens du brossage d'un métal anisotrope), nulle si indéfinie
+    pub tangent: Vec3,
 }
 
 impl Intersection {
@@ -39,6 +48,9 @@ impl Intersection {
                 face: Face::Front,
                 factor,
                 material,
+                uv: (0., 0.),
+                object: 0,
+                tangent: Vec3(0., 0., 0.),
             }
         } else {
             Intersection {
@@ -47,21 +59,73 @@ impl Intersection {
                 face: Face::Back,
                 factor,
                 material,
+                uv: (0., 0.),
+                object: 0,
+                tangent: Vec3(0., 0., 0.),
             }
         }
     }
 }
 
+#[derive(Hash)]
 pub enum Geometry {
     Sphere(Sphere),
+    Curve(Curve),
 }
 
 impl Geometry {
     pub fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
         match self {
             Geometry::Sphere(sphere) => sphere.hit(ray, t_min, t_max),
+            Geometry::Curve(curve) => curve.hit(ray, t_min, t_max),
         }
     }
+
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            Geometry::Sphere(sphere) => {
+                //rayon négatif: sphère inversée, même encombrement
+                let r = sphere.radius.abs();
+                Aabb {
+                    min: sphere.centre - Vec3(r, r, r),
+                    max: sphere.centre + Vec3(r, r, r),
+                }
+            }
+            Geometry::Curve(curve) => curve.bounds(),
+        }
+    }
+}
+
+//boite englobante alignée sur les axes
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point3(
+                self.min.0.min(other.min.0),
+                self.min.1.min(other.min.1),
+                self.min.2.min(other.min.2),
+            ),
+            max: Point3(
+                self.max.0.max(other.max.0),
+                self.max.1.max(other.max.1),
+                self.max.2.max(other.max.2),
+            ),
+        }
+    }
+
+    pub fn centre(&self) -> Point3 {
+        Point3(
+            (self.min.0 + self.max.0) / 2.,
+            (self.min.1 + self.max.1) / 2.,
+            (self.min.2 + self.max.2) / 2.,
+        )
+    }
 }
 
 pub fn sphere(x: f64, y: f64, z: f64, r: f64, material: GenericMaterial) -> Geometry {
@@ -76,12 +140,14 @@ impl Intersect for [Arc<Geometry>] {
     fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
         let mut closest = t_max;
         let mut ret = None;
-        for obj in self.iter() {
-            if let Some(hit) = obj.intersect(ray, t_min, closest) {
+        for (object, obj) in self.iter().enumerate() {
+            if let Some(mut hit) = obj.intersect(ray, t_min, closest) {
                 closest = hit.factor;
+                hit.object = object as u32;
                 ret = Some(hit);
             }
         }
+        stats::record_traversal(0, self.len() as u64);
         ret
     }
 }
@@ -97,6 +163,14 @@ pub struct Sphere {
     pub(crate) material: GenericMaterial,
 }
 
+impl Hash for Sphere {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.centre.hash(state);
+        self.radius.to_bits().hash(state);
+        self.material.hash(state);
+    }
+}
+
 impl Sphere {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
         let x = Vec3::points(self.centre, ray.origin);
@@ -113,27 +187,182 @@ impl Sphere {
             //normale: va du centre  de la sphere vers le hitpoint
             let root = (-h - d.sqrt()) / a;
             if root >= t_min && root <= t_max {
-                return Some(Intersection::new(
-                    ray,
-                    root,
-                    ray.at(root),
-                    //division par radius plutot que .unit() -> utilisation d'un bug qui reverse la face du matériau en cas de radius negatif
-                    Vec3::points(self.centre, ray.at(root)) / self.radius,
-                    self.material.clone(),
-                ));
+                return Some(self.intersection(ray, root));
             }
             let root = (-h + d.sqrt()) / a;
             if root >= t_min && root <= t_max {
-                return Some(Intersection::new(
-                    ray,
-                    root,
-                    ray.at(root),
-                    //division par radius plutot que .unit() -> utilisation d'un bug qui reverse la face du matériau en cas de radius negatif
-                    Vec3::points(self.centre, ray.at(root)) / self.radius,
-                    self.material.clone(),
-                ));
+                return Some(self.intersection(ray, root));
             }
         }
         None
     }
+
+    fn intersection(&self, ray: &Ray, root: f64) -> Intersection {
+        let hit_point = ray.at(root);
+        //division par radius plutot que .unit() -> utilisation d'un bug qui reverse la face du matériau en cas de radius negatif
+        let outward_normale = Vec3::points(self.centre, hit_point) / self.radius;
+        let mut intersection =
+            Intersection::new(ray, root, hit_point, outward_normale, self.material.clone());
+        let p = Vec3::points(self.centre, hit_point).unit();
+        intersection.uv = sphere_uv(p);
+        //u tourne autour de l'axe y: tangente horizontale, indéfinie aux pôles
+        let tangent = Vec3(0., 1., 0.).cross_product(p);
+        if tangent.sqr_len() > 1e-12 {
+            intersection.tangent = tangent.unit();
+        }
+        intersection
+    }
+}
+
+//coordonnées sphériques d'un point de la sphère unité: u autour de l'axe y en partant de -x, v du pôle sud au pôle nord
+fn sphere_uv(p: Vec3) -> (f64, f64) {
+    let theta = (-p.y()).clamp(-1., 1.).acos();
+    let phi = (-p.z()).atan2(p.x()) + std::f64::consts::PI;
+    (
+        phi / (2. * std::f64::consts::PI),
+        theta / std::f64::consts::PI,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::material::diffuse;
+
+    fn ray(origin: Point3, direction: Vec3) -> Ray {
+        Ray { origin, direction }
+    }
+
+    fn unit_sphere(radius: f64) -> Geometry {
+        sphere(0., 0., 0., radius, diffuse(0.5, 0.5, 0.5))
+    }
+
+    fn assert_vec(a: Vec3, b: Vec3) {
+        assert!((a - b).len() < 1e-9);
+    }
+
+    #[test]
+    fn hit_from_outside_takes_nearest_root() {
+        let r = ray(Point3(0., 0., -5.), Vec3(0., 0., 1.));
+        let hit = unit_sphere(1.).intersect(&r, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.factor, 4.);
+        assert!(matches!(hit.face, Face::Front));
+        assert_vec(hit.normale, Vec3(0., 0., -1.));
+    }
+
+    #[test]
+    fn hit_falls_back_to_far_root() {
+        //la première racine est hors de l'intervalle: on obtient la sortie de la sphère, vue de l'intérieur
+        let r = ray(Point3(0., 0., -5.), Vec3(0., 0., 1.));
+        let hit = unit_sphere(1.).intersect(&r, 5., f64::INFINITY).unwrap();
+        assert_eq!(hit.factor, 6.);
+        assert!(matches!(hit.face, Face::Back));
+        assert_vec(hit.normale, Vec3(0., 0., -1.));
+
+        let inside = ray(Point3(0., 0., 0.), Vec3(0., 0., 2.));
+        let hit = unit_sphere(1.)
+            .intersect(&inside, 0.001, f64::INFINITY)
+            .unwrap();
+        assert_eq!(hit.factor, 0.5);
+        assert!(matches!(hit.face, Face::Back));
+    }
+
+    #[test]
+    fn hit_respects_interval() {
+        let r = ray(Point3(0., 0., -5.), Vec3(0., 0., 1.));
+        assert!(unit_sphere(1.).intersect(&r, 0.001, 3.9).is_none());
+        assert!(unit_sphere(1.).intersect(&r, 6.1, f64::INFINITY).is_none());
+        let away = ray(Point3(0., 0., -5.), Vec3(0., 0., -1.));
+        assert!(unit_sphere(1.)
+            .intersect(&away, 0.001, f64::INFINITY)
+            .is_none());
+    }
+
+    #[test]
+    fn miss() {
+        let r = ray(Point3(0., 2., -5.), Vec3(0., 0., 1.));
+        assert!(unit_sphere(1.)
+            .intersect(&r, 0.001, f64::INFINITY)
+            .is_none());
+    }
+
+    #[test]
+    fn negative_radius_flips_faces() {
+        //rayon négatif: même surface, normales retournées (utilisé pour les sphères creuses)
+        let r = ray(Point3(0., 0., -5.), Vec3(0., 0., 1.));
+        let hit = unit_sphere(-1.)
+            .intersect(&r, 0.001, f64::INFINITY)
+            .unwrap();
+        assert_eq!(hit.factor, 4.);
+        assert!(matches!(hit.face, Face::Back));
+        assert_vec(hit.normale, Vec3(0., 0., -1.));
+        assert_vec_point(unit_sphere(-1.).bounds().max, Point3(1., 1., 1.));
+    }
+
+    #[test]
+    fn intersection_orients_normal_against_ray() {
+        let material = diffuse(0.5, 0.5, 0.5);
+        let r = ray(Point3(0., 0., 0.), Vec3(0., -1., 0.));
+        let front = Intersection::new(
+            &r,
+            1.,
+            Point3(0., -1., 0.),
+            Vec3(0., 1., 0.),
+            material.clone(),
+        );
+        assert!(matches!(front.face, Face::Front));
+        assert_vec(front.normale, Vec3(0., 1., 0.));
+        let back = Intersection::new(&r, 1., Point3(0., -1., 0.), Vec3(0., -1., 0.), material);
+        assert!(matches!(back.face, Face::Back));
+        assert_vec(back.normale, Vec3(0., 1., 0.));
+    }
+
+    #[test]
+    fn sphere_uv_poles_and_equator() {
+        let close = |(u, v): (f64, f64), (eu, ev): (f64, f64)| {
+            assert!(
+                (u - eu).abs() < 1e-9 && (v - ev).abs() < 1e-9,
+                "({}, {})",
+                u,
+                v
+            )
+        };
+        close(sphere_uv(Vec3(1., 0., 0.)), (0.5, 0.5));
+        close(sphere_uv(Vec3(0., 0., 1.)), (0.25, 0.5));
+        close(sphere_uv(Vec3(0., 0., -1.)), (0.75, 0.5));
+        assert!((sphere_uv(Vec3(0., 1., 0.)).1 - 1.).abs() < 1e-9);
+        assert!(sphere_uv(Vec3(0., -1., 0.)).1.abs() < 1e-9);
+
+        let r = ray(Point3(0., 0., -5.), Vec3(0., 0., 1.));
+        let hit = unit_sphere(1.).intersect(&r, 0.001, f64::INFINITY).unwrap();
+        close(hit.uv, (0.75, 0.5));
+    }
+
+    fn assert_vec_point(a: Point3, b: Point3) {
+        assert_vec(Vec3::points(a, b), Vec3(0., 0., 0.));
+    }
+
+    proptest! {
+        #[test]
+        fn hit_point_lies_on_sphere(
+            (x, y, z) in (-10.0..10., -10.0..10., -10.0..10.),
+            (dx, dy, dz) in (-1.0..1., -1.0..1., -1.0..1.),
+            radius in 0.1..5.,
+        ) {
+            let direction = Vec3(dx, dy, dz);
+            prop_assume!(direction.len() > 1e-3);
+            let r = ray(Point3(x, y, z), direction);
+            if let Some(hit) = unit_sphere(radius).intersect(&r, 0.001, f64::INFINITY) {
+                let distance = Vec3::points(Point3(0., 0., 0.), hit.hit_point).len();
+                prop_assert!((distance - radius).abs() < 1e-6 * radius.max(1.));
+                prop_assert!(hit.factor >= 0.001);
+                assert_vec_point(hit.hit_point, r.at(hit.factor));
+                //la normale est unitaire et opposée au rayon
+                prop_assert!((hit.normale.len() - 1.).abs() < 1e-6);
+                prop_assert!(hit.normale.scalar_product(direction) <= 0.);
+            }
+        }
+    }
 }
@@ -0,0 +1,445 @@
+use std::f64::consts::{LN_2, PI};
+use std::hash::{Hash, Hasher};
+
+use crate::color::Color;
+use crate::geometry::Intersection;
+use crate::material::{fresnel_complex, Complex};
+use crate::microfacet::frame;
+use crate::ray::Ray;
+use crate::sampler::random;
+use crate::vec::Vec3;
+
+//BSDF de cheveu (Chiang et al., "A practical and controllable hair and fur model", 2016, comme pbrt):
+//la fibre est un cylindre diélectrique absorbant. la lumière est réfléchie en surface (R), traverse la fibre (TT),
+//ou s'y réfléchit une fois avant de ressortir (TRT); les réflexions suivantes sont regroupées dans un dernier terme.
+//chaque lobe est un produit d'un terme longitudinal (angle avec la fibre) et d'un terme azimutal (autour de la fibre).
+//le point touché est repéré par h dans [-1, 1], sa position en travers de la fibre (coordonnée v de la courbe)
+const P_MAX: usize = 3;
+//indice de la kératine
+const ETA: f64 = 1.55;
+//inclinaison des écailles de la cuticule, en degrés
+const ALPHA: f64 = 2.;
+//coefficients d'absorption des deux mélanines, par unité de diamètre de la fibre
+const EUMELANIN: [f64; 3] = [0.419, 0.697, 1.37];
+const PHEOMELANIN: [f64; 3] = [0.187, 0.4, 1.05];
+
+#[derive(Copy, Clone, Debug)]
+pub struct Hair {
+    //coefficient d'absorption dans la fibre, par unité de diamètre
+    pub absorption: Color,
+    //rugosités longitudinale et azimutale, dans [0, 1]
+    pub beta_m: f64,
+    pub beta_n: f64,
+}
+
+impl Hash for Hair {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.absorption.hash(state);
+        [self.beta_m, self.beta_n].map(f64::to_bits).hash(state);
+    }
+}
+
+impl Hair {
+    //cheveu naturel: concentrations d'eumélanine (brun, noir, de 0 à ~8) et de phéomélanine (roux)
+    pub fn melanin(eumelanin: f64, pheomelanin: f64, beta_m: f64, beta_n: f64) -> Hair {
+        let absorption = |c: usize| eumelanin * EUMELANIN[c] + pheomelanin * PHEOMELANIN[c];
+        Hair {
+            absorption: Color::new(absorption(0), absorption(1), absorption(2)),
+            beta_m,
+            beta_n,
+        }
+    }
+
+    //cheveu teint: absorption qui donne à peu près la couleur `color` à une mèche épaisse
+    pub fn dyed(color: Color, beta_m: f64, beta_n: f64) -> Hair {
+        let b = beta_n;
+        let denominator = 5.969 - 0.215 * b + 2.532 * b.powi(2) - 10.73 * b.powi(3)
+            + 5.574 * b.powi(4)
+            + 0.245 * b.powi(5);
+        Hair {
+            absorption: color.map_each(|c| (c.clamp(1e-4, 1.).ln() / denominator).powi(2)),
+            beta_m,
+            beta_n,
+        }
+    }
+
+    //variances longitudinales de chaque lobe, et échelle de la logistique azimutale
+    fn roughness(&self) -> ([f64; P_MAX + 1], f64) {
+        let b = self.beta_m;
+        let v0 = (0.726 * b + 0.812 * b.powi(2) + 3.7 * b.powi(20)).powi(2);
+        let b = self.beta_n;
+        let s = (PI / 8.).sqrt() * (0.265 * b + 1.194 * b.powi(2) + 5.372 * b.powi(22));
+        ([v0, v0 / 4., 4. * v0, 4. * v0], s)
+    }
+
+    //direction tirée et poids f cos / pdf, comme Microfacet::sample
+    pub(crate) fn sample(&self, hit: &Intersection, incident_ray: &Ray) -> Option<(Vec3, Color)> {
+        //repère de la fibre: x le long de la fibre, z vers l'observateur, y en travers (sens des h croissants)
+        let wo = -incident_ray.direction.unit();
+        let x = if hit.tangent.sqr_len() > 1e-12 {
+            hit.tangent.unit()
+        } else {
+            frame(hit.normale.unit(), Vec3(0., 0., 0.)).0
+        };
+        let mut z = wo - wo.scalar_product(x) * x;
+        if z.sqr_len() < 1e-12 {
+            z = hit.normale - hit.normale.scalar_product(x) * x;
+        }
+        let z = z.unit();
+        let y = z.cross_product(x);
+        let local = Vec3(
+            wo.scalar_product(x),
+            wo.scalar_product(y),
+            wo.scalar_product(z),
+        );
+        let h = (2. * hit.uv.1 - 1.).clamp(-1., 1.);
+        let lobes = Lobes::new(self, local, h);
+        let wi = lobes.sample()?;
+        let pdf = lobes.pdf(wi);
+        if pdf <= 0. {
+            return None;
+        }
+        let weight = lobes.eval(wi) * (1. / pdf);
+        Some((wi.0 * x + wi.1 * y + wi.2 * z, weight))
+    }
+}
+
+//lobes évalués pour une direction d'observation donnée
+struct Lobes {
+    sin_theta_o: f64,
+    cos_theta_o: f64,
+    phi_o: f64,
+    gamma_o: f64,
+    gamma_t: f64,
+    //atténuation de chaque lobe (Fresnel et absorption), et probabilité de le tirer
+    attenuation: [Color; P_MAX + 1],
+    probability: [f64; P_MAX + 1],
+    variance: [f64; P_MAX + 1],
+    scale: f64,
+    //inclinaison des écailles: sin et cos de 2^k α
+    tilt: [(f64, f64); 3],
+}
+
+impl Lobes {
+    fn new(hair: &Hair, wo: Vec3, h: f64) -> Lobes {
+        let sin_theta_o = wo.0.clamp(-1., 1.);
+        let cos_theta_o = safe_sqrt(1. - sin_theta_o * sin_theta_o);
+        let phi_o = wo.2.atan2(wo.1);
+        //réfraction dans la fibre: indice modifié de Bravais pour la projection sur la section
+        let sin_theta_t = sin_theta_o / ETA;
+        let cos_theta_t = safe_sqrt(1. - sin_theta_t * sin_theta_t);
+        let eta_p = safe_sqrt(ETA * ETA - sin_theta_o * sin_theta_o) / cos_theta_o.max(1e-9);
+        let sin_gamma_t = h / eta_p;
+        let cos_gamma_t = safe_sqrt(1. - sin_gamma_t * sin_gamma_t);
+        //transmittance d'une traversée de la fibre
+        let transmittance = hair
+            .absorption
+            .map_each(|a| (-a * 2. * cos_gamma_t / cos_theta_t).exp());
+
+        let cos_gamma_o = safe_sqrt(1. - h * h);
+        let f = fresnel_complex((cos_theta_o * cos_gamma_o).clamp(0., 1.), Complex(ETA, 0.));
+        let mut attenuation = [Color::EMPTY; P_MAX + 1];
+        attenuation[0] = Color::new(f, f, f);
+        attenuation[1] = transmittance * (1. - f).powi(2);
+        for p in 2..P_MAX {
+            attenuation[p] = attenuation[p - 1] * transmittance * f;
+        }
+        //somme des réflexions suivantes, série géométrique
+        attenuation[P_MAX] = Color::new(
+            tail(attenuation[P_MAX - 1].red, transmittance.red, f),
+            tail(attenuation[P_MAX - 1].green, transmittance.green, f),
+            tail(attenuation[P_MAX - 1].blue, transmittance.blue, f),
+        );
+        let luminance = |c: Color| 0.2126 * c.red + 0.7152 * c.green + 0.0722 * c.blue;
+        let total: f64 = attenuation.iter().map(|&a| luminance(a)).sum();
+        let probability = attenuation.map(|a| if total > 0. { luminance(a) / total } else { 0. });
+
+        let (variance, scale) = hair.roughness();
+        let mut tilt = [(0., 0.); 3];
+        let sin = ALPHA.to_radians().sin();
+        tilt[0] = (sin, safe_sqrt(1. - sin * sin));
+        for k in 1..3 {
+            let (sin, cos) = tilt[k - 1];
+            tilt[k] = (2. * cos * sin, cos * cos - sin * sin);
+        }
+        Lobes {
+            sin_theta_o,
+            cos_theta_o,
+            phi_o,
+            gamma_o: h.clamp(-1., 1.).asin(),
+            gamma_t: sin_gamma_t.clamp(-1., 1.).asin(),
+            attenuation,
+            probability,
+            variance,
+            scale,
+            tilt,
+        }
+    }
+
+    //angle de sortie incliné par les écailles, pour le lobe p
+    fn tilted(&self, p: usize) -> (f64, f64) {
+        let (sin_o, cos_o) = (self.sin_theta_o, self.cos_theta_o);
+        let (sin, cos) = match p {
+            //R: incliné de -2α, TT de α, TRT de 4α
+            0 => (-self.tilt[1].0, self.tilt[1].1),
+            1 => self.tilt[0],
+            2 => self.tilt[2],
+            _ => return (sin_o, cos_o),
+        };
+        (sin_o * cos + cos_o * sin, (cos_o * cos - sin_o * sin).abs())
+    }
+
+    fn sample(&self) -> Option<Vec3> {
+        let mut u: f64 = random();
+        let mut p = 0;
+        while p < P_MAX && u >= self.probability[p] {
+            u -= self.probability[p];
+            p += 1;
+        }
+        if self.probability[p] <= 0. {
+            return None;
+        }
+        let (sin_theta_op, cos_theta_op) = self.tilted(p);
+        //terme longitudinal: distribution de von Mises-Fisher autour de la direction miroir
+        let v = self.variance[p];
+        let u1 = random::<f64>().max(1e-5);
+        let cos_theta = 1. + v * (u1 + (1. - u1) * (-2. / v).exp()).ln();
+        let sin_theta = safe_sqrt(1. - cos_theta * cos_theta);
+        let cos_phi = (2. * PI * random::<f64>()).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1. - sin_theta_i * sin_theta_i);
+        //terme azimutal: logistique autour de la déviation du lobe
+        let dphi = if p < P_MAX {
+            phi(p, self.gamma_o, self.gamma_t)
+                + sample_trimmed_logistic(random(), self.scale, -PI, PI)
+        } else {
+            2. * PI * random::<f64>()
+        };
+        let phi_i = self.phi_o + dphi;
+        Some(Vec3(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        ))
+    }
+
+    //f(wo, wi) cos θi
+    fn eval(&self, wi: Vec3) -> Color {
+        self.sum(wi, |p| self.attenuation[p])
+    }
+
+    fn pdf(&self, wi: Vec3) -> f64 {
+        let probability = self.sum(wi, |p| {
+            let q = self.probability[p];
+            Color::new(q, q, q)
+        });
+        probability.red
+    }
+
+    //somme sur les lobes de M_p N_p pondérés
+    fn sum<F: Fn(usize) -> Color>(&self, wi: Vec3, weight: F) -> Color {
+        let sin_theta_i = wi.0.clamp(-1., 1.);
+        let cos_theta_i = safe_sqrt(1. - sin_theta_i * sin_theta_i);
+        let dphi = wi.2.atan2(wi.1) - self.phi_o;
+        let mut sum = Color::EMPTY;
+        for p in 0..P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p);
+            let m = longitudinal(
+                cos_theta_i,
+                cos_theta_op,
+                sin_theta_i,
+                sin_theta_op,
+                self.variance[p],
+            );
+            let n = azimuthal(dphi, p, self.scale, self.gamma_o, self.gamma_t);
+            sum = sum + weight(p) * (m * n);
+        }
+        let m = longitudinal(
+            cos_theta_i,
+            self.cos_theta_o,
+            sin_theta_i,
+            self.sin_theta_o,
+            self.variance[P_MAX],
+        );
+        sum + weight(P_MAX) * (m / (2. * PI))
+    }
+}
+
+fn tail(previous: f64, transmittance: f64, f: f64) -> f64 {
+    let denominator = 1. - transmittance * f;
+    if denominator > 0. {
+        previous * f * transmittance / denominator
+    } else {
+        0.
+    }
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.).sqrt()
+}
+
+//terme longitudinal M_p (d'Eon et al. 2011), calculé en logarithme pour les faibles rugosités
+fn longitudinal(cos_i: f64, cos_o: f64, sin_i: f64, sin_o: f64, v: f64) -> f64 {
+    let a = cos_i * cos_o / v;
+    let b = sin_i * sin_o / v;
+    if v <= 0.1 {
+        (log_bessel_i0(a) - b - 1. / v + LN_2 + (1. / (2. * v)).ln()).exp()
+    } else {
+        (-b).exp() * bessel_i0(a) / ((1. / v).sinh() * 2. * v)
+    }
+}
+
+//fonction de Bessel modifiée de première espèce I0, par sa série
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 0.;
+    let mut term = 1.;
+    let x2 = x * x / 4.;
+    for i in 1..=10 {
+        sum += term;
+        term *= x2 / (i * i) as f64;
+    }
+    sum
+}
+
+fn log_bessel_i0(x: f64) -> f64 {
+    if x > 12. {
+        x + 0.5 * (-(2. * PI).ln() + (1. / x).ln() + 1. / (8. * x))
+    } else {
+        bessel_i0(x).ln()
+    }
+}
+
+//déviation azimutale du lobe p
+fn phi(p: usize, gamma_o: f64, gamma_t: f64) -> f64 {
+    2. * p as f64 * gamma_t - 2. * gamma_o + p as f64 * PI
+}
+
+//terme azimutal N_p: logistique tronquée sur [-π, π] autour de la déviation du lobe
+fn azimuthal(angle: f64, p: usize, s: f64, gamma_o: f64, gamma_t: f64) -> f64 {
+    let dphi = (angle - phi(p, gamma_o, gamma_t) + PI).rem_euclid(2. * PI) - PI;
+    trimmed_logistic(dphi, s, -PI, PI)
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1. + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1. / (1. + (-x / s).exp())
+}
+
+fn trimmed_logistic(x: f64, s: f64, a: f64, b: f64) -> f64 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64, a: f64, b: f64) -> f64 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1. / (u * k + logistic_cdf(a, s)) - 1.).ln();
+    x.clamp(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_direction() -> Vec3 {
+        let z = 2. * random::<f64>() - 1.;
+        let phi = 2. * PI * random::<f64>();
+        let r = safe_sqrt(1. - z * z);
+        Vec3(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    fn white(beta_m: f64, beta_n: f64) -> Hair {
+        Hair {
+            absorption: Color::EMPTY,
+            beta_m,
+            beta_n,
+        }
+    }
+
+    #[test]
+    fn distributions_are_normalised() {
+        //M_p sur θi et N_p sur φ s'intègrent à 1
+        for v in [0.01, 0.1, 0.5] {
+            let steps = 2000;
+            let (sin_o, cos_o) = (0.3f64, (1. - 0.09f64).sqrt());
+            let integral: f64 = (0..steps)
+                .map(|i| {
+                    let theta = -PI / 2. + (i as f64 + 0.5) / steps as f64 * PI;
+                    longitudinal(theta.cos(), cos_o, theta.sin(), sin_o, v) * theta.cos()
+                })
+                .sum::<f64>()
+                * PI
+                / steps as f64;
+            assert!((integral - 1.).abs() < 0.01, "{} {}", v, integral);
+        }
+        let integral: f64 = (0..2000)
+            .map(|i| azimuthal(-PI + (i as f64 + 0.5) / 1000. * PI, 1, 0.3, 0.2, 0.1))
+            .sum::<f64>()
+            * PI
+            / 1000.;
+        assert!((integral - 1.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn white_hair_keeps_the_energy() {
+        //sans absorption, toute la lumière est diffusée: test du four blanc
+        for (beta_m, beta_n) in [(0.2, 0.3), (0.5, 0.5), (0.8, 0.9)] {
+            let hair = white(beta_m, beta_n);
+            let count = 100_000;
+            let mut sum = 0.;
+            for _ in 0..count {
+                let lobes = Lobes::new(&hair, random_direction(), 2. * random::<f64>() - 1.);
+                if let Some(wi) = lobes.sample() {
+                    sum += lobes.eval(wi).green / lobes.pdf(wi);
+                }
+            }
+            let mean = sum / count as f64;
+            assert!((mean - 1.).abs() < 0.05, "{} {} {}", beta_m, beta_n, mean);
+        }
+    }
+
+    #[test]
+    fn sampling_matches_uniform_integration() {
+        //même intégrale de f cos avec les directions tirées et avec des directions uniformes
+        let hair = Hair::melanin(1.3, 0.2, 0.4, 0.5);
+        let wo = Vec3(0.3, 0.2, 0.93).unit();
+        let lobes = Lobes::new(&hair, wo, 0.35);
+        let count = 200_000;
+        let (mut sampled, mut uniform) = (0., 0.);
+        for _ in 0..count {
+            if let Some(wi) = lobes.sample() {
+                sampled += lobes.eval(wi).red / lobes.pdf(wi);
+            }
+            uniform += lobes.eval(random_direction()).red * 4. * PI;
+        }
+        let (sampled, uniform) = (sampled / count as f64, uniform / count as f64);
+        assert!(
+            (sampled - uniform).abs() < 0.03 * uniform,
+            "{} {}",
+            sampled,
+            uniform
+        );
+    }
+
+    #[test]
+    fn melanin_darkens_and_warms_the_hair() {
+        let wo = Vec3(0.1, 0., 1.).unit();
+        let reflected = |hair: Hair| {
+            let lobes = Lobes::new(&hair, wo, 0.2);
+            (0..20_000)
+                .filter_map(|_| {
+                    lobes
+                        .sample()
+                        .map(|wi| lobes.eval(wi) * (1. / lobes.pdf(wi)))
+                })
+                .fold(Color::EMPTY, |sum, c| sum + c * (1. / 20_000.))
+        };
+        let blond = reflected(Hair::melanin(0.3, 0., 0.3, 0.3));
+        let brown = reflected(Hair::melanin(1.3, 0., 0.3, 0.3));
+        let red = reflected(Hair::melanin(0.3, 2., 0.3, 0.3));
+        assert!(brown.green < blond.green);
+        assert!(blond.red > blond.blue && red.red > 2. * red.blue);
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::OnceLock;
+
+use ray::CancelToken;
+
+static CANCEL: OnceLock<CancelToken> = OnceLock::new();
+
+//Ctrl-C annule le rendu: les workers terminent leurs tuiles et l'image partielle est écrite.
+//un second Ctrl-C quitte immédiatement
+pub fn cancel_on_interrupt(cancel: &CancelToken) {
+    if CANCEL.set(cancel.clone()).is_ok() {
+        install();
+    }
+}
+
+#[cfg(unix)]
+fn install() {
+    //le gestionnaire ne fait que des opérations atomiques, sans allocation ni verrou
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        if let Some(cancel) = CANCEL.get() {
+            if cancel.is_cancelled() {
+                unsafe { libc::_exit(130) };
+            }
+            cancel.cancel();
+        }
+    }
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+//ailleurs, Ctrl-C garde son comportement par défaut
+#[cfg(not(unix))]
+fn install() {}
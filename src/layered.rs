@@ -0,0 +1,263 @@
+use std::hash::{Hash, Hasher};
+use std::ops::Mul;
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::geometry::{Face, Intersection};
+use crate::material::{fresnel_complex, reflect, refract, Complex, GenericMaterial};
+use crate::microfacet::sample_facet;
+use crate::ray::Ray;
+use crate::sampler::random;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+//nombre maximal de réflexions entre le vernis et la base avant d'abandonner le chemin
+const MAX_INTERNAL_BOUNCES: u32 = 16;
+
+//vernis transparent sur un matériau de base (peinture de carrosserie, bois verni, plastique laqué):
+//la lumière est réfléchie par l'interface du vernis selon Fresnel, ou la traverse, est absorbée sur son
+//épaisseur, atteint la base, puis remonte en se réfléchissant éventuellement sous l'interface.
+//les réflexions entre couches sont suivies explicitement (marche stochastique dans l'empilement)
+#[derive(Clone)]
+pub struct Coat {
+    pub indice: f64,
+    //rugosité perceptuelle de l'interface, dans [0, 1]
+    pub roughness: f64,
+    pub thickness: f64,
+    //coefficient d'absorption du vernis, par unité de distance
+    pub absorption: Color,
+    pub base: GenericMaterial,
+}
+
+//mélange de deux matériaux: en chaque point, le second est choisi avec la probabilité donnée par le masque
+//(rouille sur un métal, motif de carrelage, poussière)
+#[derive(Clone)]
+pub struct Mix {
+    pub materials: [GenericMaterial; 2],
+    pub mask: Mask,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mask {
+    Constant(f64),
+    //valeurs plaquées sur les coordonnées uv
+    Texture(Arc<Texture>),
+}
+
+impl Mask {
+    pub fn at(&self, uv: (f64, f64)) -> f64 {
+        match self {
+            Mask::Constant(value) => value.clamp(0., 1.),
+            Mask::Texture(texture) => texture.sample(uv).clamp(0., 1.),
+        }
+    }
+}
+
+impl Mix {
+    //matériau retenu au point touché
+    pub(crate) fn choose(&self, hit: &Intersection) -> &GenericMaterial {
+        if random::<f64>() < self.mask.at(hit.uv) {
+            &self.materials[1]
+        } else {
+            &self.materials[0]
+        }
+    }
+}
+
+impl Hash for Coat {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        [self.indice, self.roughness, self.thickness]
+            .map(f64::to_bits)
+            .hash(state);
+        self.absorption.hash(state);
+        self.base.hash(state);
+    }
+}
+
+impl Hash for Mix {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.materials.hash(state);
+        match &self.mask {
+            Mask::Constant(value) => value.to_bits().hash(state),
+            Mask::Texture(texture) => texture.hash(state),
+        }
+    }
+}
+
+impl Coat {
+    //rebond sur l'empilement, pour un poids de type W (Color ou Spectrum).
+    //`base` fait rebondir un rayon sur la base, `transmittance` donne l'atténuation d'un trajet de longueur
+    //donnée dans le vernis. la face intérieure d'un objet verni n'a pas de vernis: seule la base compte
+    pub(crate) fn scatter<W: Copy + Mul<Output = W>>(
+        &self,
+        hit: &Intersection,
+        incident_ray: &Ray,
+        one: W,
+        mut base: impl FnMut(&Ray) -> Option<(Ray, W)>,
+        transmittance: impl Fn(f64) -> W,
+    ) -> Option<(Ray, W)> {
+        if let Face::Back = hit.face {
+            return base(incident_ray);
+        }
+        let n = hit.normale.unit();
+        let uv = incident_ray.direction.unit();
+        //interface du vernis, éventuellement rugueuse
+        let facet = sample_facet(n, -uv, self.roughness);
+        let cosinus = (-uv).scalar_product(facet);
+        if random::<f64>() < fresnel_complex(cosinus, Complex(self.indice, 0.)) {
+            return self.leave(hit, reflect(uv, facet), one);
+        }
+        let mut direction = refract(uv, facet, 1. / self.indice);
+        let mut weight = one;
+        for _ in 0..MAX_INTERNAL_BOUNCES {
+            //descente jusqu'à la base
+            let cos_down = -direction.unit().scalar_product(n);
+            if cos_down <= 0. {
+                return None;
+            }
+            weight = weight * transmittance(self.thickness / cos_down);
+            let (ray, attenuation) = base(&Ray {
+                origin: hit.hit_point - direction,
+                direction,
+            })?;
+            weight = weight * attenuation;
+            let up = ray.direction.unit();
+            let cos_up = up.scalar_product(n);
+            //la base transmet le rayon dans l'objet: il ne repasse pas par le vernis
+            if cos_up <= 0. {
+                return Some((ray, weight));
+            }
+            //remontée, puis interface vue de l'intérieur (réflexion totale comprise)
+            weight = weight * transmittance(self.thickness / cos_up);
+            let facet = sample_facet(n, -up, self.roughness);
+            let cosinus = (-up).scalar_product(facet);
+            if random::<f64>() < fresnel_complex(cosinus.abs(), Complex(1. / self.indice, 0.)) {
+                direction = reflect(up, facet);
+            } else {
+                return self.leave(hit, refract(up, -facet, self.indice), weight);
+            }
+        }
+        None
+    }
+
+    //rayon qui quitte l'empilement vers l'extérieur
+    fn leave<W>(&self, hit: &Intersection, direction: Vec3, weight: W) -> Option<(Ray, W)> {
+        if direction.scalar_product(hit.normale) <= 0. {
+            return None;
+        }
+        Some((
+            Ray {
+                origin: hit.hit_point,
+                direction,
+            },
+            weight,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{coated, dielectric, diffuse, metal, mix};
+    use crate::point::Point3;
+
+    //rayon descendant sur une surface horizontale, vue de dessus ou de dessous
+    fn hit_at(material: GenericMaterial, outward: Vec3) -> (Intersection, Ray) {
+        let ray = Ray {
+            origin: Point3(0., 0., 1.),
+            direction: Vec3(0., 0., -1.),
+        };
+        let hit = Intersection::new(&ray, 1., Point3(0., 0., 0.), outward, material);
+        (hit, ray)
+    }
+
+    fn hit(material: GenericMaterial) -> (Intersection, Ray) {
+        hit_at(material, Vec3(0., 0., 1.))
+    }
+
+    //moyenne de l'atténuation (verte) et part des rayons réfléchis dans la direction miroir
+    fn scatter(material: GenericMaterial) -> (f64, f64) {
+        let (hit, ray) = hit(material);
+        let count = 50_000;
+        let mut energy = 0.;
+        let mut mirror = 0;
+        for _ in 0..count {
+            if let Some(reflexion) = hit.material.scatter(&hit, &ray) {
+                energy += reflexion.attenuation.green;
+                let direction = reflexion.reflected_ray.direction.unit();
+                if direction.2 > 1. - 1e-9 {
+                    mirror += 1;
+                }
+            }
+        }
+        (energy / count as f64, mirror as f64 / count as f64)
+    }
+
+    #[test]
+    fn clear_coat_reflects_fresnel_and_keeps_the_energy() {
+        //vernis incolore sur un blanc parfait: presque rien n'est perdu (chemins trop longs sous le vernis),
+        //4% de reflet en incidence normale
+        let (energy, mirror) = scatter(coated(
+            diffuse(1., 1., 1.),
+            1.5,
+            0.,
+            0.1,
+            Color::new(0., 0., 0.),
+        ));
+        assert!((energy - 1.).abs() < 0.005);
+        assert!((mirror - 0.04).abs() < 0.005);
+        //un vernis absorbant assombrit la base mais pas son reflet
+        let (energy, mirror) = scatter(coated(
+            diffuse(1., 1., 1.),
+            1.5,
+            0.,
+            1.,
+            Color::new(0., 1., 0.),
+        ));
+        assert!(energy < 0.3 && energy > 0.04);
+        assert!((mirror - 0.04).abs() < 0.005);
+    }
+
+    #[test]
+    fn coat_is_transparent_from_inside() {
+        //un verre verni se comporte comme le verre quand le rayon en sort
+        let material = coated(dielectric(1.5), 1.5, 0.2, 0.1, Color::new(1., 1., 1.));
+        let (hit, ray) = hit_at(material, Vec3(0., 0., -1.));
+        let reflexion = hit.material.scatter(&hit, &ray).unwrap();
+        assert!(reflexion.reflected_ray.direction.unit().2.abs() > 1. - 1e-9);
+        assert!((reflexion.attenuation.green - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn mask_chooses_between_materials() {
+        let mix = Mix {
+            materials: [diffuse(1., 0., 0.), metal(0., 0., 1., 0.)],
+            mask: Mask::Constant(0.25),
+        };
+        let (hit, _) = hit(diffuse(0., 0., 0.));
+        let count = 20_000;
+        let second = (0..count)
+            .filter(|_| mix.choose(&hit).reflection_factor.is_some())
+            .count();
+        assert!((second as f64 / count as f64 - 0.25).abs() < 0.015);
+        let striped = Mask::Texture(Arc::new(Texture::new(2, 1, vec![0., 1.])));
+        assert_eq!(striped.at((0.25, 0.5)), 0.);
+        assert_eq!(striped.at((0.75, 0.5)), 1.);
+    }
+
+    #[test]
+    fn mix_of_a_coated_material_follows_the_mask() {
+        //masque à 1: le second matériau, bleu, est toujours choisi, le vernis du premier est ignoré
+        let varnished = coated(diffuse(1., 0., 0.), 1.5, 0., 0.1, Color::new(0., 0., 0.));
+        let material = mix(varnished, diffuse(0., 0., 1.), Mask::Constant(1.));
+        assert!(material.coat.is_none());
+        let (hit, ray) = hit(material);
+        for _ in 0..1000 {
+            let attenuation = hit.material.scatter(&hit, &ray).unwrap().attenuation;
+            assert_eq!(
+                (attenuation.red, attenuation.green, attenuation.blue),
+                (0., 0., 1.)
+            );
+        }
+    }
+}
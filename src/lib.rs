@@ -0,0 +1,74 @@
+pub mod angle;
+pub mod aov;
+pub mod bvh;
+pub mod cam;
+pub mod checkpoint;
+pub mod color;
+pub mod colorspace;
+pub mod curve;
+pub mod denoise;
+pub mod distributed;
+pub mod exr;
+pub mod film;
+pub mod geometry;
+pub mod hair;
+pub mod layered;
+pub mod material;
+pub mod merl;
+pub mod microfacet;
+pub mod output;
+pub mod png;
+pub mod point;
+pub mod ppm;
+pub mod progress;
+pub mod ray;
+pub mod render;
+pub mod sampler;
+pub mod server;
+pub mod spectrum;
+pub mod stats;
+pub mod strands;
+pub mod subsurface;
+pub mod texture;
+pub mod thinfilm;
+pub mod tile;
+pub mod tonemap;
+pub mod toon;
+pub mod vec;
+pub mod world;
+
+//api stable: les types nécessaires pour décrire une scène, la rendre et écrire l'image.
+//les modules restent publics pour les outils (tests, benchs, rendu distribué) mais leur contenu peut changer
+pub use crate::angle::Angle;
+pub use crate::aov::Aov;
+pub use crate::cam::Camera;
+pub use crate::color::Color;
+pub use crate::colorspace::{decode_srgb8, ColorSpace};
+pub use crate::curve::{curve, CurveShape};
+pub use crate::denoise::denoise;
+pub use crate::film::Film;
+pub use crate::geometry::{sphere, Geometry};
+pub use crate::hair::Hair;
+pub use crate::layered::Mask;
+pub use crate::material::{
+    absorbing_dielectric, anisotropic, coated, colored_dielectric, conductor, dielectric, diffuse,
+    dispersive_dielectric, glass, hair, measured, metal, mix, multi_lobe, soap_bubble, subsurface,
+    thin_film, toon, Conductor, Dispersion, GenericMaterial, Glass,
+};
+pub use crate::merl::Merl;
+pub use crate::microfacet::{Lobe, Tangent};
+pub use crate::output::{
+    encode_exr, encode_png, encode_png_with, save_exr, save_png, save_png_with, save_ppm,
+    save_ppm_with,
+};
+pub use crate::point::Point3;
+pub use crate::progress::{CancelToken, Observer, RenderProgress};
+pub use crate::render::{Renderer, Scene, Settings};
+pub use crate::stats::Stats;
+pub use crate::strands::Strand;
+pub use crate::subsurface::Boundary;
+pub use crate::texture::Texture;
+pub use crate::thinfilm::{Thickness, ThicknessMap, ThinFilm};
+pub use crate::tonemap::{DisplayTransform, ToneMap};
+pub use crate::toon::{outline, Outline, Toon};
+pub use crate::vec::Vec3;
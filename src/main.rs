@@ -1,85 +1,104 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::rc::Rc;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::mpsc::RecvError;
-use std::sync::Arc;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use ray::checkpoint::{scene_hash, Checkpoint};
+use ray::distributed::{self, Event};
+use ray::progress::Tracker;
+use ray::render::render_pass;
+use ray::sampler::reseed;
+use ray::server::{self, Progress};
+use ray::tile::spiral_tiles;
+use ray::world::world_v4;
+use ray::{
+    denoise, outline, save_exr, save_ppm_with, Angle, Camera, CancelToken, Film, Outline, Point3,
+    Scene, Settings, Stats, Vec3,
+};
+
+use crate::options::{Options, USAGE};
+use crate::progress_bar::ProgressBar;
+
+mod interrupt;
+mod options;
+mod progress_bar;
+
+const ASPECT_RATIO: f64 = 16.0 / 9.0;
 
-use rand::Rng;
-
-use ppm::Ppm;
-
-use crate::angle::Angle;
-use crate::angle::Angle::Deg;
-use crate::cam::Camera;
-use crate::color::Color;
-use crate::geometry::{sphere, Geometry, Intersect, Sphere};
-use crate::material::{colored_dielectric, dielectric, diffuse, metal, GenericMaterial};
-use crate::point::Point3;
-use crate::ray::Ray;
-use crate::vec::Vec3;
-
-mod angle;
-mod cam;
-mod color;
-mod geometry;
-mod material;
-mod point;
-mod ppm;
-mod ray;
-mod vec;
+fn main() -> std::io::Result<()> {
+    const SAMPLES_PER_PIXEL: u32 = 2000;
+    const MAX_REFLECTION: u16 = 50;
+    const TILE_SIZE: u32 = 32;
 
-struct StdOutWriter;
-impl Write for StdOutWriter {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        print!("{}", String::from_utf8_lossy(buf));
-        Ok(buf.len())
-    }
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let threads = options.threads.unwrap_or_else(Settings::available_threads);
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        Ok(())
+    //un worker reçoit la graine et les réglages du coordinateur
+    if let Some(address) = &options.worker {
+        return distributed::work(address, threads, scene);
     }
-}
 
-fn ray_color(ray: &Ray, objects: &[Arc<Geometry>], rec_depth: u16) -> Color {
-    const WHITE: Color = Color::new(1., 1., 1.);
-    const BLACK: Color = Color::new(0., 0., 0.);
-    const BLUE: Color = Color::new(0.5, 0.7, 1.0);
-
-    //si le rayon a trop rebondi, il n'y a peu de lumière qui peut venir de cette direction -> noir
-    if rec_depth == 0 {
-        return BLACK;
+    let resumed = options
+        .resume
+        .as_deref()
+        .map(Checkpoint::load)
+        .transpose()?;
+
+    let settings = Settings {
+        width: options.width,
+        height: (options.width as f64 / ASPECT_RATIO) as u32,
+        samples_per_pixel: options.samples.unwrap_or(SAMPLES_PER_PIXEL),
+        max_reflection: MAX_REFLECTION,
+        tile_size: options.tile_size.unwrap_or(TILE_SIZE),
+        threads,
+        //une reprise doit réutiliser la graine du rendu interrompu
+        seed: resumed
+            .as_ref()
+            .map(|checkpoint| checkpoint.seed)
+            .or(options.seed)
+            .unwrap_or_else(rand::random),
+        //une reprise produit les aovs du checkpoint
+        aovs: resumed.as_ref().map_or_else(
+            || options.aovs.clone(),
+            |checkpoint| checkpoint.film.aovs().to_vec(),
+        ),
+        working_space: options.working_space,
+        spectral: options.spectral,
+        toon: options.toon,
+    };
+    println!("seed {}", settings.seed);
+
+    let started = Instant::now();
+    let scene = scene(settings.seed);
+    let mut timings = Stats::default();
+    timings.add_time("scene", started.elapsed());
+
+    if let Some(address) = &options.coordinator {
+        let pass_samples = options
+            .progressive
+            .unwrap_or(settings.samples_per_pixel)
+            .clamp(1, settings.samples_per_pixel);
+        let film = distributed::coordinate(
+            address,
+            &scene,
+            &settings,
+            pass_samples,
+            report_coordination,
+        )?;
+        return save_images(&film, &options);
     }
 
-    // 0.001 pour être sûr d'être > 0. car à cause de l'erreur d'echantillon, lors d'une reflection, le point de deépart peut se
-    // trouver legerement avant 0 (-0.000000000000000000001), et donc rebondir sur la surface intérieure de l'objet -> obscurcissement
-    // -> http://www.opengl-tutorial.org/intermediate-tutorials/tutorial-16-shadow-mapping/#shadow-acne
-    if let Some(hit) = objects.intersect(ray, 0.001, f64::INFINITY) {
-        // le hit avec le materiau définit si il doit y avoir un rayon reflechi/refracté, et avec quelle attenuation
-        // l'attenuation est la couleur de l'objet 0 <= (r,g,b) <= 1
-        // un rayon secondaire est lancé depuis le hit point dans la direction du rayon réfléchi/refracté, etc...
-        // récursivité: chaque rayon réfl/refr peut frapper un autre objet et rebondir en fonction du matériau
-        if let Some(reflexion) = hit.material.scatter(&hit, ray) {
-            // le nombre de rebonds va impacter la luminosité et la couleur
-            reflexion.attenuation * ray_color(&reflexion.reflected_ray, objects, rec_depth - 1)
-        } else {
-            //absorption totale si HIT mais pas de rayon réfléchi/réfracté
-            BLACK
-        }
-    } else {
-        //gradient de couleur (blanc..bleu) pour le fond si pas de HIT
-        let t = 0.5 * (ray.direction.unit().y() + 1.);
-        WHITE * (1.0 - t) + BLUE * t
-    }
+    render_local(&options, &scene, &settings, resumed, timings)
 }
 
-fn main() -> std::io::Result<()> {
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-    const IMAGE_WIDTH: u32 = 1920;
-    const IMAGE_HEIGHT: u32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as u32;
-    const SAMPLES_PER_PIXEL: u32 = 2000;
-    const MAX_REFLECTION: u16 = 50;
-
+//la scène aléatoire est générée à partir de la graine, pour pouvoir être reconstruite à l'identique
+//lors d'une reprise ou par un worker distant
+fn scene(seed: u64) -> Scene {
     let camera = Camera::new(
         Angle::Deg(20.),
         ASPECT_RATIO,
@@ -88,198 +107,210 @@ fn main() -> std::io::Result<()> {
         Point3(0., 0., 0.),
         Vec3(0., 1., 0.),
     );
+    reseed(seed);
+    Scene::new(camera, world_v4())
+}
 
-    let file = File::create("back.ppm")?;
-
-    let mut ppm = Ppm::new(
-        BufWriter::with_capacity((IMAGE_WIDTH * 13) as usize, file),
-        IMAGE_WIDTH,
-        IMAGE_HEIGHT,
-        255,
-    )?;
-
-    let objects = world_v4();
-
-    struct WorkFinished(u32, Vec<Color>);
-    let (worker_tx, main_rx) = std::sync::mpsc::channel();
-    let lines_count = Arc::new(AtomicU32::new(0));
-    let workers: Vec<_> = (0..6)
-        .map(|_| {
-            let lines_count = Arc::clone(&lines_count);
-            let camera = camera.clone();
-            let objects = objects.clone();
-            let worker_tx = worker_tx.clone();
-            std::thread::spawn(move || {
-                let mut current_line = lines_count.fetch_add(1, Ordering::SeqCst);
-                while current_line < IMAGE_HEIGHT {
-                    let j = IMAGE_HEIGHT - current_line;
-                    let mut colors = Vec::with_capacity(IMAGE_WIDTH as usize);
-                    for i in 0..IMAGE_WIDTH {
-                        let mut color = Color::EMPTY;
-
-                        for _ in 0..SAMPLES_PER_PIXEL {
-                            let u = (i as f64 + rand::random::<f64>()) / (IMAGE_WIDTH as f64 - 1.);
-                            let v = (j as f64 + rand::random::<f64>()) / (IMAGE_HEIGHT as f64 - 1.);
-                            let ray = camera.ray(u, v);
-                            color = color + ray_color(&ray, &objects, MAX_REFLECTION);
-                        }
-                        color = color / SAMPLES_PER_PIXEL as f64;
-                        //gamma correction color^(1/gamma), gamma=2
-                        color = color.map_each(|v| v.sqrt());
-                        colors.push(color);
-                    }
-                    worker_tx.send(WorkFinished(current_line, colors)).unwrap();
+fn render_local(
+    options: &Options,
+    scene: &Scene,
+    settings: &Settings,
+    resumed: Option<Checkpoint>,
+    mut timings: Stats,
+) -> std::io::Result<()> {
+    //taille des passes quand on sauvegarde des checkpoints sans avoir demandé le mode progressif
+    const CHECKPOINT_PASS_SAMPLES: u32 = 16;
+
+    let scene_hash = scene_hash(scene, settings);
+
+    let (film, first_pass, mut done) = match resumed {
+        Some(checkpoint) => {
+            if checkpoint.scene_hash != scene_hash {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "checkpoint was made for a different scene",
+                ));
+            }
+            println!(
+                "resuming after pass {}, {} spp",
+                checkpoint.passes, checkpoint.samples_per_pixel
+            );
+            //l'espace de travail fait partie du hash de scène: c'est celui du checkpoint
+            (
+                checkpoint.film.in_space(settings.working_space),
+                checkpoint.passes,
+                checkpoint.samples_per_pixel,
+            )
+        }
+        None => (
+            Film::with_aovs(settings.width, settings.height, &settings.aovs)
+                .in_space(settings.working_space),
+            0,
+            0,
+        ),
+    };
+    let checkpoint_path = options.checkpoint.as_ref().or(options.resume.as_ref());
+
+    //sans mode progressif ni checkpoint, tout est rendu en une seule passe
+    let pass_samples = options
+        .progressive
+        .or_else(|| checkpoint_path.map(|_| CHECKPOINT_PASS_SAMPLES))
+        .unwrap_or(settings.samples_per_pixel)
+        .clamp(1, settings.samples_per_pixel);
+    let passes = first_pass
+        + settings
+            .samples_per_pixel
+            .saturating_sub(done)
+            .div_ceil(pass_samples);
+
+    let film = Arc::new(Mutex::new(film));
+    let progress = Arc::new(Mutex::new(Progress {
+        pass: first_pass,
+        passes,
+        samples_per_pixel: done,
+        target_samples_per_pixel: settings.samples_per_pixel,
+        started: Instant::now(),
+    }));
+    if let Some(port) = options.serve {
+        let port = server::serve(
+            port,
+            Arc::clone(&film),
+            Arc::clone(&progress),
+            options.display,
+        )?;
+        println!("preview on http://127.0.0.1:{}/", port);
+    }
 
-                    current_line = lines_count.fetch_add(1, Ordering::SeqCst);
-                }
-            })
-        })
-        .collect();
+    let cancel = CancelToken::new();
+    interrupt::cancel_on_interrupt(&cancel);
+    let bar = ProgressBar::new();
+    let tracker = Tracker::new(
+        &bar,
+        &cancel,
+        spiral_tiles(settings.width, settings.height, settings.tile_size).len()
+            * (passes - first_pass) as usize,
+        settings.width as u64
+            * settings.height as u64
+            * settings.samples_per_pixel.saturating_sub(done) as u64,
+    );
 
-    let mut lines: Vec<(u32, Vec<Color>)> = Vec::new();
-    let mut x = 0;
-    for _ in 0..IMAGE_HEIGHT {
-        let WorkFinished(j2, colors) = main_rx.recv().unwrap();
-        // assert_eq!(j, j2);
-        if x == j2 {
-            println!("{} lines remaining", IMAGE_HEIGHT - x);
-            ppm.next_pixels(&colors)?;
-            x += 1;
-        } else {
-            lines.push((j2, colors));
-            lines.sort_by_key(|a: &(u32, Vec<_>)| IMAGE_HEIGHT - a.0);
-            while let Some((j3, colors)) = lines.pop() {
-                if j3 == x {
-                    println!("{} lines remaining", IMAGE_HEIGHT - x);
-                    ppm.next_pixels(&colors)?;
-                    x += 1;
-                } else {
-                    lines.push((j3, colors));
-                    break;
+    let mut last_write = Instant::now();
+    let mut last_checkpoint = Instant::now();
+    let mut checkpointed = first_pass;
+    for pass in first_pass + 1..=passes {
+        let samples = pass_samples.min(settings.samples_per_pixel - done);
+        let started = Instant::now();
+        render_pass(scene, settings, pass - 1, samples, &film, &tracker);
+        timings.add_time("render", started.elapsed());
+        if cancel.is_cancelled() {
+            //la passe interrompue est incomplète: l'image est écrite, mais pas le checkpoint
+            bar.finish();
+            save_images(&film.lock().unwrap(), options)?;
+            timings.merge(&tracker.stats());
+            report_stats(options, &timings)?;
+            eprintln!(
+                "interrupted during pass {}/{}, partial image written to {}",
+                pass,
+                passes,
+                options.output.display()
+            );
+            if let Some(path) = checkpoint_path.filter(|_| checkpointed > 0) {
+                eprintln!(
+                    "{} keeps the state after pass {}, use --resume to continue",
+                    path.display(),
+                    checkpointed
+                );
+            }
+            std::process::exit(130);
+        }
+        done += samples;
+        {
+            let mut progress = progress.lock().unwrap();
+            progress.pass = pass;
+            progress.samples_per_pixel = done;
+        }
+        bar.println(&format!("pass {}/{} done, {} spp", pass, passes, done));
+
+        //on réécrit l'image avec la moyenne courante, au plus une fois par write_interval
+        if pass == passes || last_write.elapsed() >= options.write_interval {
+            let started = Instant::now();
+            save_images(&film.lock().unwrap(), options)?;
+            timings.add_time("output", started.elapsed());
+            last_write = Instant::now();
+        }
+        if let Some(path) = checkpoint_path {
+            if pass == passes || last_checkpoint.elapsed() >= options.checkpoint_interval {
+                let started = Instant::now();
+                Checkpoint {
+                    scene_hash,
+                    seed: settings.seed,
+                    passes: pass,
+                    samples_per_pixel: done,
+                    film: film.lock().unwrap().clone(),
                 }
+                .save(path)?;
+                timings.add_time("checkpoint", started.elapsed());
+                last_checkpoint = Instant::now();
+                checkpointed = pass;
             }
         }
     }
-    lines.sort_by_key(|(l, _)| *l);
-    for (j, colors) in lines {
-        println!("{} lines remaining", IMAGE_HEIGHT - j);
-        ppm.next_pixels(&colors)?;
+    bar.finish();
+    //reprise d'un rendu déjà terminé: on réécrit seulement l'image
+    if first_pass == passes {
+        save_images(&film.lock().unwrap(), options)?;
     }
-    for worker in workers {
-        worker.join();
-    }
-    Ok(())
+    timings.merge(&tracker.stats());
+    report_stats(options, &timings)
 }
 
-fn world_v5() -> Vec<Arc<Geometry>> {
-    let mut objects: Vec<Arc<Geometry>> = Vec::new();
-
-    let ground_material = diffuse(0.5, 0.5, 0.5);
-    objects.push(Arc::new(sphere(0., -1000., 0., 1000., ground_material)));
-
-    let material2 = diffuse(0.4, 0.2, 0.1);
-    objects.push(Arc::new(sphere(-4., 1., 0., 1.0, material2)));
+fn report_coordination(event: &Event) {
+    match event {
+        Event::Listening(address) => println!("coordinator listening on {}", address),
+        Event::Connected(peer) => println!("worker {} connected", peer),
+        Event::Finished(peer) => println!("worker {} finished", peer),
+        Event::Lost(peer, error) => println!("worker {} lost: {}", peer, error),
+        Event::Remaining(remaining, total) => {
+            println!("{}/{} work units remaining", remaining, total)
+        }
+    }
+}
 
-    let bubble = GenericMaterial {
-        color: Color::new(1., 0.9, 0.9),
-        reflection_factor: Some(0.02),
-        diffusion_factor: 0.,
-        refraction_indice: 0.99,
+fn save_images(film: &Film, options: &Options) -> std::io::Result<()> {
+    //le débruitage ne touche que les images écrites, le film et le checkpoint gardent les échantillons bruts
+    let denoised;
+    let film = if options.denoise {
+        denoised = denoise(film);
+        &denoised
+    } else {
+        film
     };
-    objects.push(Arc::new(sphere(0., 1., 0., 1.0, bubble)));
-
-    let material3 = metal(0.7, 0.6, 0.5, 0.0);
-    objects.push(Arc::new(sphere(4., 1., 0., 1.0, material3)));
-
-    let material2 = diffuse(0.2, 0.6, 0.1);
-    objects.push(Arc::new(sphere(-4., 1., -4., 1.0, material2)));
-
-    objects
+    //les contours sont tracés après le débruitage, qui les flouterait
+    let outlined;
+    let film = if options.toon {
+        outlined = outline(film, &Outline::default());
+        &outlined
+    } else {
+        film
+    };
+    save_ppm_with(film, &options.output, &options.display)?;
+    //sans --exr, les aovs sont écrits à côté de l'image
+    let exr = options
+        .exr
+        .clone()
+        .or_else(|| (!film.aovs().is_empty()).then(|| options.output.with_extension("exr")));
+    match exr {
+        Some(path) => save_exr(film, &path),
+        None => Ok(()),
+    }
 }
 
-fn world_v4() -> Vec<Arc<Geometry>> {
-    let mut objects: Vec<Arc<Geometry>> = Vec::new();
-
-    let ground_material = diffuse(0.5, 0.5, 0.5);
-    objects.push(Arc::new(sphere(0., -1000., 0., 1000., ground_material)));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = rand::random::<f64>();
-            let center = Point3(
-                a as f64 + 0.9 * rand::random::<f64>(),
-                0.2,
-                b as f64 + 0.9 * rand::random::<f64>(),
-            );
-
-            if (Vec3::points(Point3(4., 0.2, 0.), center).len() > 0.9) {
-                let sphere_material;
-
-                if (choose_mat < 0.4) {
-                    // diffuse
-                    let Color { red, green, blue } = Color::random() * Color::random();
-                    sphere_material = diffuse(red, green, blue);
-                    objects.push(Arc::new(sphere(
-                        center.0,
-                        center.1,
-                        center.2,
-                        0.2,
-                        sphere_material,
-                    )));
-                } else if (choose_mat < 0.65) {
-                    // metal
-                    let Color { red, green, blue } = Color::random_range(0.5..1.);
-                    let fuzz = rand::thread_rng().gen_range(0.0..0.5);
-                    sphere_material = metal(red, green, blue, fuzz);
-                    objects.push(Arc::new(sphere(
-                        center.0,
-                        center.1,
-                        center.2,
-                        0.2,
-                        sphere_material,
-                    )));
-                } else if choose_mat < 0.85 {
-                    // glass
-                    let Color { red, green, blue } =
-                        Color::random().map_each(|v| v.sqrt().sqrt().sqrt().sqrt());
-                    sphere_material = colored_dielectric(red, green, blue, 1.5);
-                    objects.push(Arc::new(sphere(
-                        center.0,
-                        center.1,
-                        center.2,
-                        0.2,
-                        sphere_material,
-                    )));
-                } else {
-                    // bubble
-                    let Color { red, green, blue } =
-                        Color::random().map_each(|v| v.sqrt().sqrt().sqrt().sqrt());
-                    let bubble = GenericMaterial {
-                        color: Color::new(red, green, blue),
-                        reflection_factor: Some(0.02),
-                        diffusion_factor: 0.,
-                        refraction_indice: 0.99,
-                    };
-                    objects.push(Arc::new(sphere(
-                        center.0,
-                        center.1 + 0.3 + 1.8 * rand::random::<f64>(),
-                        center.2 - (0.15 * rand::random::<f64>()),
-                        0.2,
-                        bubble,
-                    )));
-                }
-            }
-        }
+fn report_stats(options: &Options, stats: &Stats) -> std::io::Result<()> {
+    if options.stats {
+        eprint!("{}", stats);
     }
-
-    let material1 = dielectric(1.5);
-    objects.push(Arc::new(sphere(0., 1., 0., 1.0, material1)));
-
-    let material2 = diffuse(0.4, 0.2, 0.1);
-    objects.push(Arc::new(sphere(-4., 1., 0., 1.0, material2)));
-
-    let material3 = metal(0.7, 0.6, 0.5, 0.0);
-    objects.push(Arc::new(sphere(4., 1., 0., 1.0, material3)));
-
-    objects
+    if let Some(path) = &options.stats_json {
+        std::fs::write(path, stats.to_json() + "\n")?;
+    }
+    Ok(())
 }
@@ -1,12 +1,21 @@
 #![allow(dead_code)]
 
+use std::hash::{Hash, Hasher};
 use std::ops::{Mul, Neg};
-
-use rand::random;
+use std::sync::Arc;
 
 use crate::color::Color;
 use crate::geometry::{Face, Intersection};
+use crate::hair::Hair;
+use crate::layered::{Coat, Mask, Mix};
+use crate::merl::Merl;
+use crate::microfacet::{Lobe, Microfacet, Tangent};
 use crate::ray::Ray;
+use crate::sampler::random;
+use crate::spectrum::{self, Spectrum, Wavelengths};
+use crate::subsurface::{self as walk, Boundary, Subsurface, Walk};
+use crate::thinfilm::{Thickness, ThinFilm};
+use crate::toon::Toon;
 use crate::vec::Vec3;
 
 pub struct Reflexion {
@@ -14,17 +23,31 @@ pub struct Reflexion {
     pub attenuation: Color,
 }
 
-fn reflect(incident: Vec3, normale: Vec3) -> Vec3 {
+//rebond en mode spectral: l'atténuation est donnée pour chaque longueur d'onde du chemin
+pub struct SpectralReflexion {
+    pub reflected_ray: Ray,
+    pub attenuation: Spectrum,
+}
+
+//nature du rebond choisi par le matériau
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Event {
+    Diffusion,
+    Reflection,
+    Refraction,
+}
+
+pub(crate) fn reflect(incident: Vec3, normale: Vec3) -> Vec3 {
     incident - 2. * incident.scalar_product(normale) * normale
 }
 
-// fn refract(incident: Vec3, normale: Vec3, density_ratio: f64) -> Vec3 {
-//     let uv = incident.unit();
-//     let cos_theta = (-uv).scalar_product(normale).min(1.);
-//     let r_perp = density_ratio * (uv + cos_theta * normale);
-//     let r_par = (1. - r_perp.sqr_len()).abs().sqrt().neg().mul(normale);
-//     r_perp + r_par
-// }
+//uv: vecteur incident unitaire, density_ratio: n/n' (indice du milieu d'origine / indice du milieu traversé)
+pub(crate) fn refract(uv: Vec3, normale: Vec3, density_ratio: f64) -> Vec3 {
+    let cos_theta = (-uv).scalar_product(normale).min(1.);
+    let r_perp = density_ratio * (uv + cos_theta * normale);
+    let r_par = (1. - r_perp.sqr_len()).abs().sqrt().neg().mul(normale);
+    r_perp + r_par
+}
 
 fn reflectance(cosinus: f64, ratio: f64) -> f64 {
     //schlick approximation
@@ -33,12 +56,295 @@ fn reflectance(cosinus: f64, ratio: f64) -> f64 {
     r0 + (1. - r0) * ((1. - cosinus).powi(5))
 }
 
+//métaux définis par leur indice de réfraction complexe n + ik, mesuré à quelques longueurs d'onde
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Conductor {
+    Gold,
+    Silver,
+    Copper,
+    Aluminium,
+}
+
+impl Conductor {
+    pub const ALL: [Conductor; 4] = [
+        Conductor::Gold,
+        Conductor::Silver,
+        Conductor::Copper,
+        Conductor::Aluminium,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Conductor::Gold => "gold",
+            Conductor::Silver => "silver",
+            Conductor::Copper => "copper",
+            Conductor::Aluminium => "aluminium",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Conductor> {
+        Conductor::ALL.iter().copied().find(|c| c.name() == name)
+    }
+
+    //(n, k) de 400 à 700 nm par pas de 50 nm (Johnson & Christy 1972, Rakić 1995 pour l'aluminium)
+    fn indices(self) -> &'static [(f64, f64); 7] {
+        match self {
+            Conductor::Gold => &[
+                (1.658, 1.956),
+                (1.504, 1.878),
+                (0.970, 1.870),
+                (0.430, 2.455),
+                (0.250, 2.980),
+                (0.170, 3.530),
+                (0.160, 3.980),
+            ],
+            Conductor::Silver => &[
+                (0.050, 2.100),
+                (0.040, 2.650),
+                (0.050, 3.090),
+                (0.059, 3.560),
+                (0.060, 4.010),
+                (0.050, 4.480),
+                (0.040, 4.830),
+            ],
+            Conductor::Copper => &[
+                (1.180, 2.210),
+                (1.170, 2.400),
+                (1.130, 2.560),
+                (0.940, 2.590),
+                (0.270, 3.410),
+                (0.210, 3.670),
+                (0.210, 4.050),
+            ],
+            Conductor::Aluminium => &[
+                (0.490, 4.860),
+                (0.620, 5.470),
+                (0.770, 6.080),
+                (0.960, 6.690),
+                (1.200, 7.260),
+                (1.490, 7.820),
+                (1.830, 8.310),
+            ],
+        }
+    }
+
+    //indice complexe interpolé linéairement, constant hors de la plage mesurée
+    pub fn indice(self, lambda: f64) -> (f64, f64) {
+        let indices = self.indices();
+        let position = ((lambda - 400.) / 50.).clamp(0., (indices.len() - 1) as f64);
+        let i = (position as usize).min(indices.len() - 2);
+        let t = position - i as f64;
+        let ((n0, k0), (n1, k1)) = (indices[i], indices[i + 1]);
+        (n0 + (n1 - n0) * t, k0 + (k1 - k0) * t)
+    }
+
+    //réflectance de Fresnel exacte (lumière non polarisée) depuis l'air
+    pub fn fresnel(self, lambda: f64, cosinus: f64) -> f64 {
+        let (n, k) = self.indice(lambda);
+        fresnel_complex(cosinus.clamp(0., 1.), Complex(n, k))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Complex(pub f64, pub f64);
+
+impl Complex {
+    pub fn add(self, o: Complex) -> Complex {
+        Complex(self.0 + o.0, self.1 + o.1)
+    }
+    pub fn sub(self, o: Complex) -> Complex {
+        Complex(self.0 - o.0, self.1 - o.1)
+    }
+    pub fn mul(self, o: Complex) -> Complex {
+        Complex(self.0 * o.0 - self.1 * o.1, self.0 * o.1 + self.1 * o.0)
+    }
+    pub fn div(self, o: Complex) -> Complex {
+        let d = o.0 * o.0 + o.1 * o.1;
+        Complex(
+            (self.0 * o.0 + self.1 * o.1) / d,
+            (self.1 * o.0 - self.0 * o.1) / d,
+        )
+    }
+    pub fn norm(self) -> f64 {
+        self.0 * self.0 + self.1 * self.1
+    }
+    pub fn sqrt(self) -> Complex {
+        let r = self.norm().sqrt();
+        let re = ((r + self.0) / 2.).max(0.).sqrt();
+        let im = ((r - self.0) / 2.).max(0.).sqrt();
+        Complex(re, if self.1 < 0. { -im } else { im })
+    }
+}
+
+//équations de Fresnel pour un indice complexe (cf pbrt-v4, FrComplex)
+pub(crate) fn fresnel_complex(cos_i: f64, eta: Complex) -> f64 {
+    let real = |v: f64| Complex(v, 0.);
+    let sin2_i = 1. - cos_i * cos_i;
+    let sin2_t = real(sin2_i).div(eta.mul(eta));
+    let cos_t = real(1.).sub(sin2_t).sqrt();
+    let ci = real(cos_i);
+    let parallel = eta.mul(ci).sub(cos_t).div(eta.mul(ci).add(cos_t));
+    let perpendicular = ci.sub(eta.mul(cos_t)).div(ci.add(eta.mul(cos_t)));
+    (parallel.norm() + perpendicular.norm()) / 2.
+}
+
+//indice de réfraction dépendant de la longueur d'onde, λ en µm dans les formules
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Dispersion {
+    //n = a + b / λ²
+    Cauchy { a: f64, b: f64 },
+    //n² = 1 + Σ b_i λ² / (λ² - c_i), c_i en µm²
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+//nombre de longueurs d'onde auxquelles la réflectance d'un film mince est évaluée en rendu rgb
+const FILM_STEPS: usize = 34;
+
+//raie d du sodium (nm), longueur d'onde de référence des indices de catalogue
+pub const LAMBDA_D: f64 = 587.56;
+
+impl Dispersion {
+    //indice de réfraction à `lambda` nm
+    pub fn indice(self, lambda: f64) -> f64 {
+        let l2 = (lambda / 1000.).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = b.iter().zip(c).map(|(b, c)| b * l2 / (l2 - c)).sum();
+                (1. + sum).sqrt()
+            }
+        }
+    }
+
+    //nombre d'Abbe: (n_d - 1) / (n_F - n_C), d'autant plus petit que le verre disperse
+    pub fn abbe(self) -> f64 {
+        (self.indice(LAMBDA_D) - 1.) / (self.indice(486.13) - self.indice(656.27))
+    }
+}
+
+//verres usuels, coefficients de Sellmeier des catalogues (Schott, Malitson 1965 pour la silice)
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Glass {
+    Bk7,
+    FusedSilica,
+    //flint dense, très dispersif
+    Sf11,
+    Diamond,
+}
+
+impl Glass {
+    pub const ALL: [Glass; 4] = [Glass::Bk7, Glass::FusedSilica, Glass::Sf11, Glass::Diamond];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Glass::Bk7 => "bk7",
+            Glass::FusedSilica => "fused-silica",
+            Glass::Sf11 => "sf11",
+            Glass::Diamond => "diamond",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Glass> {
+        Glass::ALL.iter().copied().find(|g| g.name() == name)
+    }
+
+    pub fn dispersion(self) -> Dispersion {
+        let (b, c) = match self {
+            Glass::Bk7 => (
+                [1.03961212, 0.231792344, 1.01046945],
+                [0.00600069867, 0.0200179144, 103.560653],
+            ),
+            Glass::FusedSilica => (
+                [0.6961663, 0.4079426, 0.8974794],
+                [0.00467914826, 0.0135120631, 97.9340025],
+            ),
+            Glass::Sf11 => (
+                [1.73759695, 0.313747346, 1.89878101],
+                [0.013188707, 0.0623068142, 155.23629],
+            ),
+            Glass::Diamond => ([0.3306, 4.3356, 0.], [0.030625, 0.011236, 0.]),
+        };
+        Dispersion::Sellmeier { b, c }
+    }
+}
+
 #[derive(Clone)]
 pub struct GenericMaterial {
     pub color: Color,
     pub diffusion_factor: f64,
     pub reflection_factor: Option<f64>,
     pub refraction_indice: f64,
+    //métal mesuré: en mode spectral, sa réflectance remplace `color` (qui en est la moyenne en incidence normale)
+    pub conductor: Option<Conductor>,
+    //diélectrique dispersif: en mode spectral, l'indice suit la longueur d'onde du chemin,
+    //en rgb c'est `refraction_indice` (l'indice à la raie d) qui est utilisé
+    pub dispersion: Option<Dispersion>,
+    //coefficient d'absorption du milieu intérieur, par unité de distance (loi de Beer-Lambert):
+    //un rayon qui a parcouru d à l'intérieur est atténué de exp(-absorption * d)
+    pub absorption: Option<Color>,
+    //film mince en surface: remplace la réflectance du matériau, qui doit être un diélectrique ou un métal mesuré
+    pub thin_film: Option<ThinFilm>,
+    //milieu diffusant à l'intérieur de l'objet (peau, cire, marbre, lait), parcouru par marche aléatoire
+    pub subsurface: Option<Subsurface>,
+    //réflexion par microfacettes GGX d'un métal, à la place du flou isotrope de diffusion_factor
+    pub microfacet: Option<Microfacet>,
+    //vernis transparent sur le matériau de base, qui rebondit sous le vernis
+    pub coat: Option<Box<Coat>>,
+    //mélange de deux matériaux selon un masque
+    pub mix: Option<Box<Mix>>,
+    //BRDF mesurée, qui remplace tout le reste de la réflexion
+    pub measured: Option<Arc<Merl>>,
+    //fibre (cheveu, poil), sur une courbe dont v repère la position en travers
+    pub hair: Option<Hair>,
+    //style du rendu stylisé (cf toon), sans effet sur le rendu photoréaliste
+    pub toon: Option<Toon>,
+}
+
+impl Hash for GenericMaterial {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.color.hash(state);
+        self.diffusion_factor.to_bits().hash(state);
+        self.reflection_factor.map(f64::to_bits).hash(state);
+        self.refraction_indice.to_bits().hash(state);
+        //absent des matériaux d'avant les métaux mesurés, pour garder leur hash
+        if let Some(conductor) = self.conductor {
+            conductor.name().hash(state);
+        }
+        match self.dispersion {
+            Some(Dispersion::Cauchy { a, b }) => [a, b].map(f64::to_bits).hash(state),
+            Some(Dispersion::Sellmeier { b, c }) => {
+                (b.map(f64::to_bits), c.map(f64::to_bits)).hash(state)
+            }
+            None => {}
+        }
+        if let Some(absorption) = self.absorption {
+            absorption.hash(state);
+        }
+        if let Some(thin_film) = &self.thin_film {
+            thin_film.hash(state);
+        }
+        if let Some(subsurface) = &self.subsurface {
+            subsurface.hash(state);
+        }
+        if let Some(microfacet) = &self.microfacet {
+            microfacet.hash(state);
+        }
+        if let Some(coat) = &self.coat {
+            coat.hash(state);
+        }
+        if let Some(mix) = &self.mix {
+            mix.hash(state);
+        }
+        if let Some(measured) = &self.measured {
+            measured.hash(state);
+        }
+        if let Some(hair) = &self.hair {
+            hair.hash(state);
+        }
+        if let Some(toon) = &self.toon {
+            toon.hash(state);
+        }
+    }
 }
 
 pub fn diffuse(r: f64, g: f64, b: f64) -> GenericMaterial {
@@ -47,6 +353,17 @@ pub fn diffuse(r: f64, g: f64, b: f64) -> GenericMaterial {
         reflection_factor: None,
         diffusion_factor: 1.,
         refraction_indice: 1.,
+        conductor: None,
+        dispersion: None,
+        absorption: None,
+        thin_film: None,
+        subsurface: None,
+        microfacet: None,
+        coat: None,
+        mix: None,
+        measured: None,
+        hair: None,
+        toon: None,
     }
 }
 
@@ -56,6 +373,17 @@ pub fn metal(r: f64, g: f64, b: f64, fuzziness: f64) -> GenericMaterial {
         reflection_factor: Some(1.),
         diffusion_factor: fuzziness,
         refraction_indice: 1.,
+        conductor: None,
+        dispersion: None,
+        absorption: None,
+        thin_film: None,
+        subsurface: None,
+        microfacet: None,
+        coat: None,
+        mix: None,
+        measured: None,
+        hair: None,
+        toon: None,
     }
 }
 
@@ -65,59 +393,654 @@ pub fn dielectric(refraction_indice: f64) -> GenericMaterial {
         reflection_factor: Some(-1.),
         diffusion_factor: 0.,
         refraction_indice,
+        conductor: None,
+        dispersion: None,
+        absorption: None,
+        thin_film: None,
+        subsurface: None,
+        microfacet: None,
+        coat: None,
+        mix: None,
+        measured: None,
+        hair: None,
+        toon: None,
+    }
+}
+//métal mesuré, de couleur sa réflectance en incidence normale sous D65
+pub fn conductor(conductor: Conductor, fuzziness: f64) -> GenericMaterial {
+    GenericMaterial {
+        color: spectrum::reflectance_to_srgb(|lambda| conductor.fresnel(lambda, 1.)),
+        reflection_factor: Some(1.),
+        diffusion_factor: fuzziness,
+        refraction_indice: 1.,
+        conductor: Some(conductor),
+        dispersion: None,
+        absorption: None,
+        thin_film: None,
+        subsurface: None,
+        microfacet: None,
+        coat: None,
+        mix: None,
+        measured: None,
+        hair: None,
+        toon: None,
+    }
+}
+
+//diélectrique dont l'indice varie avec la longueur d'onde: les arcs-en-ciel n'apparaissent qu'en mode spectral
+pub fn dispersive_dielectric(dispersion: Dispersion) -> GenericMaterial {
+    GenericMaterial {
+        refraction_indice: dispersion.indice(LAMBDA_D),
+        dispersion: Some(dispersion),
+        ..dielectric(1.)
+    }
+}
+
+pub fn glass(glass: Glass) -> GenericMaterial {
+    dispersive_dielectric(glass.dispersion())
+}
+
+//diélectrique teinté dans la masse: (r, g, b) est la fraction de lumière transmise après `distance` dans le milieu
+//contrairement à colored_dielectric, qui teinte à chaque traversée de surface, la teinte dépend de l'épaisseur traversée
+pub fn absorbing_dielectric(
+    r: f64,
+    g: f64,
+    b: f64,
+    distance: f64,
+    refraction_indice: f64,
+) -> GenericMaterial {
+    GenericMaterial {
+        absorption: Some(absorption(Color::new(r, g, b), distance)),
+        ..dielectric(refraction_indice)
+    }
+}
+
+//coefficient d'absorption qui transmet `transmittance` après `distance`, qui doit être strictement positive
+pub fn absorption(transmittance: Color, distance: f64) -> Color {
+    assert!(
+        distance > 0.,
+        "absorption distance must be positive, got {}",
+        distance
+    );
+    transmittance.map_each(|t| -t.clamp(1e-9, 1.).ln() / distance)
+}
+
+//recouvre le matériau d'un film mince d'indice `indice`; sur un diélectrique, le substrat est le milieu intérieur
+//(une bulle de savon est un film d'eau sur de l'air), sur un métal mesuré, c'est le métal.
+//les autres matériaux n'ont pas de substrat défini: ils sont refusés
+pub fn thin_film(material: GenericMaterial, indice: f64, thickness: Thickness) -> GenericMaterial {
+    assert!(
+        material.conductor.is_some() || material.reflection_factor == Some(-1.),
+        "thin film needs a dielectric or a measured conductor as base"
+    );
+    GenericMaterial {
+        thin_film: Some(ThinFilm { indice, thickness }),
+        ..material
+    }
+}
+
+//bulle de savon: film d'eau savonneuse sur de l'air, le rayon transmis n'est pas dévié
+pub fn soap_bubble(thickness: Thickness) -> GenericMaterial {
+    thin_film(dielectric(1.), 1.33, thickness)
+}
+
+//matériau translucide diffusant la lumière sous sa surface; `albedo` est sa couleur apparente,
+//`mean_free_path` la distance moyenne parcourue entre deux diffusions, par canal (cf Subsurface::new).
+//l'objet doit être fermé: la marche aléatoire se poursuit jusqu'à en toucher la face intérieure
+pub fn subsurface(albedo: Color, mean_free_path: Color, boundary: Boundary) -> GenericMaterial {
+    let refraction_indice = match boundary {
+        Boundary::Refractive(indice) => indice,
+        Boundary::Diffuse => 1.,
+    };
+    GenericMaterial {
+        subsurface: Some(Subsurface::new(albedo, mean_free_path, boundary)),
+        ..dielectric(refraction_indice)
+    }
+}
+
+//métal (metal() ou conductor()) à rugosité anisotrope, roughness_u le long de la tangente: métal brossé
+pub fn anisotropic(
+    material: GenericMaterial,
+    roughness_u: f64,
+    roughness_v: f64,
+    tangent: Tangent,
+) -> GenericMaterial {
+    multi_lobe(
+        material,
+        vec![Lobe::new(1., roughness_u, roughness_v)],
+        tangent,
+    )
+}
+
+//métal (metal() ou conductor()) dont la réflexion mélange plusieurs lobes GGX, dans les proportions de leurs poids
+pub fn multi_lobe(
+    material: GenericMaterial,
+    lobes: Vec<Lobe>,
+    tangent: Tangent,
+) -> GenericMaterial {
+    assert!(
+        material.reflection_factor == Some(1.),
+        "microfacet lobes need a metal or a conductor as base"
+    );
+    GenericMaterial {
+        diffusion_factor: 0.,
+        microfacet: Some(Microfacet { lobes, tangent }),
+        ..material
+    }
+}
+
+//recouvre `base` d'un vernis d'indice `indice`, de rugosité `roughness` et d'épaisseur `thickness`;
+//`absorption` est le coefficient d'absorption du vernis par unité de distance (cf absorption()), nul s'il est incolore
+pub fn coated(
+    base: GenericMaterial,
+    indice: f64,
+    roughness: f64,
+    thickness: f64,
+    absorption: Color,
+) -> GenericMaterial {
+    GenericMaterial {
+        coat: Some(Box::new(Coat {
+            indice,
+            roughness,
+            thickness,
+            absorption,
+            base: base.clone(),
+        })),
+        ..base
+    }
+}
+
+//mélange `a` et `b`: `b` est choisi avec la probabilité donnée par le masque au point touché.
+//le milieu intérieur (absorption, diffusion sous la surface) n'est pas mélangé: il n'y en a pas.
+//les couches de `a` restent dans le mélange, pas sur le matériau qui le porte: elles passeraient avant lui
+pub fn mix(a: GenericMaterial, b: GenericMaterial, mask: Mask) -> GenericMaterial {
+    GenericMaterial {
+        absorption: None,
+        thin_film: None,
+        subsurface: None,
+        microfacet: None,
+        coat: None,
+        measured: None,
+        hair: None,
+        mix: Some(Box::new(Mix {
+            materials: [a.clone(), b],
+            mask,
+        })),
+        ..a
+    }
+}
+
+//matériau opaque dont la réflexion est une BRDF mesurée; sa couleur (albedo en incidence normale) ne sert qu'aux aov
+pub fn measured(brdf: Arc<Merl>) -> GenericMaterial {
+    let albedo = brdf.albedo(1.);
+    GenericMaterial {
+        diffusion_factor: 0.,
+        measured: Some(brdf),
+        ..diffuse(albedo.red, albedo.green, albedo.blue)
+    }
+}
+
+//cheveux et poils, à poser sur des courbes; la couleur, celle d'une fibre vue de face, ne sert qu'aux aov
+pub fn hair(hair: Hair) -> GenericMaterial {
+    let color = hair.absorption.map_each(|a| (-2. * a).exp());
+    GenericMaterial {
+        diffusion_factor: 0.,
+        hair: Some(hair),
+        ..diffuse(color.red, color.green, color.blue)
+    }
+}
+
+//matériau diffus qui, en rendu stylisé, est dessiné en `bands` aplats avec le style par défaut
+pub fn toon(r: f64, g: f64, b: f64, bands: u32) -> GenericMaterial {
+    GenericMaterial {
+        toon: Some(Toon {
+            bands,
+            ..Toon::default()
+        }),
+        ..diffuse(r, g, b)
     }
 }
+
 pub fn colored_dielectric(r: f64, g: f64, b: f64, refraction_indice: f64) -> GenericMaterial {
     GenericMaterial {
         color: Color::new(r, g, b),
         reflection_factor: Some(-1.),
         diffusion_factor: 0.,
         refraction_indice,
+        conductor: None,
+        dispersion: None,
+        absorption: None,
+        thin_film: None,
+        subsurface: None,
+        microfacet: None,
+        coat: None,
+        mix: None,
+        measured: None,
+        hair: None,
+        toon: None,
     }
 }
 
 impl GenericMaterial {
-    pub(crate) fn scatter(&self, hit: &Intersection, incident_ray: &Ray) -> Option<Reflexion> {
+    pub fn scatter(&self, hit: &Intersection, incident_ray: &Ray) -> Option<Reflexion> {
+        if let Some(coat) = &self.coat {
+            let white = Color::new(1., 1., 1.);
+            return coat
+                .scatter(
+                    hit,
+                    incident_ray,
+                    white,
+                    |ray| {
+                        coat.base
+                            .scatter(hit, ray)
+                            .map(|r| (r.reflected_ray, r.attenuation))
+                    },
+                    |distance| coat.absorption.map_each(|a| (-a * distance).exp()),
+                )
+                .map(|(reflected_ray, attenuation)| Reflexion {
+                    reflected_ray,
+                    attenuation,
+                });
+        }
+        if let Some(mix) = &self.mix {
+            return mix.choose(hit).scatter(hit, incident_ray);
+        }
+        if let Some(sampled) = self.sample_bsdf(hit, incident_ray) {
+            let (direction, attenuation) = sampled?;
+            return self.emit(hit, direction).map(|reflected_ray| Reflexion {
+                reflected_ray,
+                attenuation,
+            });
+        }
+        if let Some(film) = &self.thin_film {
+            //réflectance intégrée sur le spectre visible
+            let cosinus = (-incident_ray.direction.unit()).scalar_product(hit.normale);
+            let reflectance = spectrum::reflectance_to_srgb_sampled(FILM_STEPS, |lambda| {
+                self.film_reflectance(film, hit, cosinus, lambda)
+            });
+            let probability = (reflectance.red + reflectance.green + reflectance.blue) / 3.;
+            return self.bounce_thin_film(hit, incident_ray, probability).map(
+                |(reflected_ray, event, probability)| Reflexion {
+                    attenuation: match event {
+                        Event::Reflection => reflectance * (1. / probability),
+                        //la réflectance rgb intégrée peut sortir de [0, 1]
+                        _ => reflectance.map_each(|r| (1. - r).clamp(0., 1.)) * (1. / probability),
+                    } * self.film_tint(),
+                    reflected_ray,
+                },
+            );
+        }
+        if let Some(microfacet) = &self.microfacet {
+            //Fresnel de Schlick, la couleur du métal est sa réflectance en incidence normale
+            let f0 = self.color;
+            let sample = microfacet.sample(hit, incident_ray)?;
+            let fresnel = f0.map_each(|f| f + (1. - f) * (1. - sample.cos_half).powi(5));
+            let compensation = f0.map_each(|f| 1. + f * sample.missing);
+            return self
+                .emit(hit, sample.direction)
+                .map(|reflected_ray| Reflexion {
+                    attenuation: fresnel * compensation * sample.shadowing,
+                    reflected_ray,
+                });
+        }
+        if self.diffuse_boundary() {
+            return self.cross_diffuse(hit).map(|reflected_ray| Reflexion {
+                attenuation: self.color,
+                reflected_ray,
+            });
+        }
+        self.bounce(hit, incident_ray, self.refraction_indice)
+            .map(|(reflected_ray, _)| Reflexion {
+                attenuation: self.color,
+                reflected_ray,
+            })
+    }
+
+    //même rebond que scatter(), l'atténuation est évaluée aux longueurs d'onde du chemin
+    //un diélectrique dispersif réfracte (et réfléchit) avec l'indice du héros: les autres longueurs d'onde
+    //auraient suivi d'autres chemins, le chemin ne transporte plus que le héros
+    pub fn scatter_spectral(
+        &self,
+        hit: &Intersection,
+        incident_ray: &Ray,
+        wavelengths: &mut Wavelengths,
+    ) -> Option<SpectralReflexion> {
+        if let Some(coat) = &self.coat {
+            let unit = spectrum::reflectance(coat.absorption.map_each(|a| (-a).exp()), wavelengths);
+            return coat
+                .scatter(
+                    hit,
+                    incident_ray,
+                    Spectrum::ONE,
+                    |ray| {
+                        coat.base
+                            .scatter_spectral(hit, ray, wavelengths)
+                            .map(|r| (r.reflected_ray, r.attenuation))
+                    },
+                    |distance| unit.map(|t| t.powf(distance)),
+                )
+                .map(|(reflected_ray, attenuation)| SpectralReflexion {
+                    reflected_ray,
+                    attenuation,
+                });
+        }
+        if let Some(mix) = &self.mix {
+            return mix
+                .choose(hit)
+                .scatter_spectral(hit, incident_ray, wavelengths);
+        }
+        if let Some(sampled) = self.sample_bsdf(hit, incident_ray) {
+            //le poids est en rgb et peut dépasser 1: il est ramené dans [0, 1] pour la conversion
+            let (direction, weight) = sampled?;
+            let scale = weight.red.max(weight.green).max(weight.blue).max(1e-12);
+            let attenuation = spectrum::reflectance(weight * (1. / scale), wavelengths) * scale;
+            return self
+                .emit(hit, direction)
+                .map(|reflected_ray| SpectralReflexion {
+                    reflected_ray,
+                    attenuation,
+                });
+        }
+        if let Some(film) = &self.thin_film {
+            let cosinus = (-incident_ray.direction.unit()).scalar_product(hit.normale);
+            let reflectance =
+                wavelengths.map(|lambda| self.film_reflectance(film, hit, cosinus, lambda));
+            //probabilité de réflexion: réflectance moyenne des longueurs d'onde encore transportées
+            let probability = if wavelengths.terminated {
+                reflectance.0[0]
+            } else {
+                reflectance.0.iter().sum::<f64>() / spectrum::WAVELENGTHS as f64
+            };
+            let tint = spectrum::reflectance(self.film_tint(), wavelengths);
+            return self.bounce_thin_film(hit, incident_ray, probability).map(
+                |(reflected_ray, event, probability)| SpectralReflexion {
+                    attenuation: match event {
+                        Event::Reflection => reflectance * (1. / probability),
+                        _ => reflectance.map(|r| (1. - r) / probability),
+                    } * tint,
+                    reflected_ray,
+                },
+            );
+        }
+        if let Some(microfacet) = &self.microfacet {
+            let sample = microfacet.sample(hit, incident_ray)?;
+            let (fresnel, f0) = match self.conductor {
+                Some(conductor) => (
+                    wavelengths.map(|lambda| conductor.fresnel(lambda, sample.cos_half)),
+                    wavelengths.map(|lambda| conductor.fresnel(lambda, 1.)),
+                ),
+                None => {
+                    let f0 = spectrum::reflectance(self.color, wavelengths);
+                    let schlick = (1. - sample.cos_half).powi(5);
+                    (f0.map(|f| f + (1. - f) * schlick), f0)
+                }
+            };
+            let compensation = f0.map(|f| 1. + f * sample.missing);
+            return self
+                .emit(hit, sample.direction)
+                .map(|reflected_ray| SpectralReflexion {
+                    attenuation: fresnel * compensation * sample.shadowing,
+                    reflected_ray,
+                });
+        }
+        if self.diffuse_boundary() {
+            return self
+                .cross_diffuse(hit)
+                .map(|reflected_ray| SpectralReflexion {
+                    attenuation: spectrum::reflectance(self.color, wavelengths),
+                    reflected_ray,
+                });
+        }
+        let refraction_indice = match self.dispersion {
+            Some(dispersion) => {
+                wavelengths.terminate_secondary();
+                dispersion.indice(wavelengths.hero())
+            }
+            None => self.refraction_indice,
+        };
+        let (reflected_ray, event) = self.bounce(hit, incident_ray, refraction_indice)?;
+        let attenuation = match (self.conductor, event) {
+            (Some(conductor), Event::Reflection) => {
+                let cosinus = (-incident_ray.direction.unit()).scalar_product(hit.normale);
+                wavelengths.map(|lambda| conductor.fresnel(lambda, cosinus))
+            }
+            _ => spectrum::reflectance(self.color, wavelengths),
+        };
+        Some(SpectralReflexion {
+            reflected_ray,
+            attenuation,
+        })
+    }
+
+    //segment parcouru par le rayon jusqu'à `hit`: à l'intérieur d'un milieu diffusant, le rayon peut être
+    //dévié avant la surface (direction isotrope); sinon il l'atteint, atténué par l'absorption éventuelle
+    pub fn walk(&self, hit: &Intersection, incident_ray: &Ray) -> Walk<Color> {
+        match (&self.subsurface, &hit.face) {
+            (Some(medium), Face::Back) => {
+                let channels = |c: Color| [c.red, c.green, c.blue];
+                let distance = hit.factor * incident_ray.direction.len();
+                let (t, [red, green, blue]) = walk::sample(
+                    channels(medium.extinction),
+                    channels(medium.albedo),
+                    3,
+                    distance,
+                );
+                self.walk_event(incident_ray, t, Color::new(red, green, blue))
+            }
+            _ => Walk::Reached(self.transmittance(hit, incident_ray)),
+        }
+    }
+
+    //même marche aux longueurs d'onde du chemin
+    pub fn walk_spectral(
+        &self,
+        hit: &Intersection,
+        incident_ray: &Ray,
+        wavelengths: &Wavelengths,
+    ) -> Walk<Spectrum> {
+        match (&self.subsurface, &hit.face) {
+            (Some(medium), Face::Back) => {
+                //l'extinction, non bornée, est convertie comme une transmittance: exp(-σt / max σt) est dans [1/e, 1]
+                let Color { red, green, blue } = medium.extinction;
+                let scale = red.max(green).max(blue).max(1e-9);
+                let unit = medium.extinction.map_each(|e| (-e / scale).exp());
+                let extinction =
+                    spectrum::reflectance(unit, wavelengths).map(|t| -t.max(1e-9).ln() * scale);
+                let albedo = spectrum::reflectance(medium.albedo, wavelengths);
+                let channels = if wavelengths.terminated {
+                    1
+                } else {
+                    spectrum::WAVELENGTHS
+                };
+                let distance = hit.factor * incident_ray.direction.len();
+                let (t, weight) = walk::sample(extinction.0, albedo.0, channels, distance);
+                self.walk_event(incident_ray, t, Spectrum(weight))
+            }
+            _ => Walk::Reached(self.transmittance_spectral(hit, incident_ray, wavelengths)),
+        }
+    }
+
+    fn walk_event<W>(&self, incident_ray: &Ray, t: Option<f64>, weight: W) -> Walk<W> {
+        match t {
+            Some(t) => Walk::Scattered(
+                Ray {
+                    origin: incident_ray.at(t / incident_ray.direction.len()),
+                    direction: Vec3::random_unit_sphere(),
+                },
+                weight,
+            ),
+            None => Walk::Reached(weight),
+        }
+    }
+
+    //matériaux qui tirent eux-mêmes la direction réfléchie et son poids (mesure, cheveu):
+    //None si le matériau n'en est pas un, Some(None) si le rayon est absorbé
+    fn sample_bsdf(&self, hit: &Intersection, incident_ray: &Ray) -> Option<Option<(Vec3, Color)>> {
+        match (&self.measured, &self.hair) {
+            (Some(measured), _) => Some(measured.sample(hit, incident_ray)),
+            (None, Some(hair)) => Some(hair.sample(hit, incident_ray)),
+            (None, None) => None,
+        }
+    }
+
+    fn diffuse_boundary(&self) -> bool {
+        self.subsurface
+            .is_some_and(|medium| medium.boundary == Boundary::Diffuse)
+    }
+
+    //traversée d'une interface rugueuse: direction diffuse de l'autre côté de la surface
+    fn cross_diffuse(&self, hit: &Intersection) -> Option<Ray> {
+        self.emit(hit, Vec3::random_unit_sphere() - hit.normale)
+    }
+
+    //atténuation de la lumière sur le segment qui arrive en `hit`: non nulle seulement si le rayon
+    //était à l'intérieur de l'objet, c'est à dire s'il en touche la face intérieure
+    pub fn transmittance(&self, hit: &Intersection, incident_ray: &Ray) -> Color {
+        match (self.absorption, &hit.face) {
+            (Some(absorption), Face::Back) => {
+                let distance = hit.factor * incident_ray.direction.len();
+                absorption.map_each(|a| (-a * distance).exp())
+            }
+            _ => Color::new(1., 1., 1.),
+        }
+    }
+
+    //même atténuation aux longueurs d'onde du chemin: la transmittance sur une unité de distance est convertie
+    //en spectre, puis élevée à la puissance de la distance parcourue
+    pub fn transmittance_spectral(
+        &self,
+        hit: &Intersection,
+        incident_ray: &Ray,
+        wavelengths: &Wavelengths,
+    ) -> Spectrum {
+        match (self.absorption, &hit.face) {
+            (Some(absorption), Face::Back) => {
+                let distance = hit.factor * incident_ray.direction.len();
+                let unit = absorption.map_each(|a| (-a).exp());
+                spectrum::reflectance(unit, wavelengths).map(|t| t.powf(distance))
+            }
+            _ => Spectrum::ONE,
+        }
+    }
+
+    //réflectance du film à `lambda`, entre le milieu d'où vient le rayon et le substrat
+    fn film_reflectance(
+        &self,
+        film: &ThinFilm,
+        hit: &Intersection,
+        cosinus: f64,
+        lambda: f64,
+    ) -> f64 {
+        let thickness = film.thickness.at(hit.uv);
+        let (n0, n2) = match (self.conductor, &hit.face) {
+            (Some(conductor), _) => {
+                let (n, k) = conductor.indice(lambda);
+                (1., Complex(n, k))
+            }
+            (None, Face::Front) => (1., Complex(self.refraction_indice, 0.)),
+            //le rayon sort de l'objet: il traverse le substrat, puis le film, puis l'air
+            (None, Face::Back) => (self.refraction_indice, Complex(1., 0.)),
+        };
+        film.reflectance(lambda, cosinus, thickness, n0, n2)
+    }
+
+    //la couleur d'un métal mesuré vient déjà de sa réflectance: seul un diélectrique est teinté
+    fn film_tint(&self) -> Color {
+        match self.conductor {
+            Some(_) => Color::new(1., 1., 1.),
+            None => self.color,
+        }
+    }
+
+    //réflexion avec la probabilité `probability`, sinon transmission à travers le film et le substrat
+    //(un film parallèle ne dévie pas le rayon: seuls les milieux de part et d'autre comptent)
+    //retourne aussi la probabilité de l'évènement choisi, pour pondérer l'atténuation
+    fn bounce_thin_film(
+        &self,
+        hit: &Intersection,
+        incident_ray: &Ray,
+        probability: f64,
+    ) -> Option<(Ray, Event, f64)> {
+        let uv = incident_ray.direction.unit();
+        let cos_theta = (-uv).scalar_product(hit.normale).min(1.);
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let density_ratio = if let Face::Front = hit.face {
+            1. / self.refraction_indice
+        } else {
+            self.refraction_indice
+        };
+        let transparent = self.conductor.is_none() && density_ratio * sin_theta <= 1.;
+        let probability = if transparent {
+            probability.clamp(0., 1.)
+        } else {
+            1.
+        };
+        let (direction, event) = if random::<f64>() < probability {
+            (reflect(uv, hit.normale), Event::Reflection)
+        } else {
+            (refract(uv, hit.normale, density_ratio), Event::Refraction)
+        };
+        self.emit(hit, direction).map(|ray| {
+            let probability = match event {
+                Event::Reflection => probability,
+                _ => 1. - probability,
+            };
+            (ray, event, probability)
+        })
+    }
+
+    //choisit le rayon ré-émis, pour un indice de réfraction donné
+    fn bounce(
+        &self,
+        hit: &Intersection,
+        incident_ray: &Ray,
+        refraction_indice: f64,
+    ) -> Option<(Ray, Event)> {
         //on détermine si reflexion ou refraction
         let mut direction = None;
         //si pas de facteur de reflection, alors -> diffusion totale: reflexion totale dans un rayon diffus autour de la normale
         if self.reflection_factor.is_none() {
-            direction = Some(hit.normale);
+            direction = Some((hit.normale, Event::Diffusion));
         }
         let uv = incident_ray.direction.unit();
         //si pas diffusion totale, on détermine aléatoirement si le rayon peut être refracté par rapport au facteur de reflexion
         //donc avec un facteur de reflexion <0 on se retrouve avec une refraction totale (ou quasi selon d'autres facteurs physiques)
         if direction.is_none()
-            && self.reflection_factor.map_or(false, |reflection_factor| {
-                reflection_factor < rand::random()
-            })
+            && self
+                .reflection_factor
+                .is_some_and(|reflection_factor| reflection_factor < random())
         {
             let cos_theta = (-uv).scalar_product(hit.normale).min(1.);
             let sin_theta = (1. - cos_theta * cos_theta).sqrt();
             let density_ratio = if let Face::Front = hit.face {
-                1. / self.refraction_indice
+                1. / refraction_indice
             } else {
-                self.refraction_indice
+                refraction_indice
             };
 
             //  si rayon a l'interieur et n > n' ex densité 1.5 et 1. pour l'air
             //  sin theta' = 1.5/1 * sin theta. sachant sin theta' est max 1:
             //  1 > 1.5 * sin theta. donc si inverse ( 1.5/1 *sin theta > 1 ==> faux, pas de solution, pas de refraction )
             // on calcul un rayon refracté si non reflection interne totale et non reflectance
-            if density_ratio * sin_theta <= 1.
-                && reflectance(cos_theta, density_ratio) <= rand::random()
+            if density_ratio * sin_theta <= 1. && reflectance(cos_theta, density_ratio) <= random()
             {
-                let r_perp = density_ratio * (uv + cos_theta * hit.normale);
-                let r_par = (1. - r_perp.sqr_len()).abs().sqrt().neg().mul(hit.normale);
-                direction = Some(r_perp + r_par);
+                direction = Some((refract(uv, hit.normale, density_ratio), Event::Refraction));
             }
         }
         //si pas de refraction ni de diffusion, c'est un rayon réfléchi
         if direction.is_none() {
-            direction = Some(reflect(uv, hit.normale));
+            direction = Some((reflect(uv, hit.normale), Event::Reflection));
         }
 
-        direction
+        direction.and_then(|(direction, event)| {
+            self.emit(hit, direction)
+                .map(|reflected_ray| (reflected_ray, event))
+        })
+    }
+
+    //rayon ré-émis depuis le point touché dans la direction choisie
+    fn emit(&self, hit: &Intersection, direction: Vec3) -> Option<Ray> {
+        Some(direction)
             //si vecteur orthogonaux, pas de rayon ré-émis
             .filter(|direction| direction.scalar_product(hit.normale).abs() > 0.00000000001)
             .map(|direction| {
@@ -129,13 +1052,343 @@ impl GenericMaterial {
                     // self.diffusion_factor * Vec3::random_unit_sphere() => vecteur dans une sphere de rayon self.diffusion_factor, qui part de son centre
                     direction + self.diffusion_factor * Vec3::random_unit_sphere()
                 };
-                Reflexion {
-                    attenuation: self.color,
-                    reflected_ray: Ray {
-                        origin: hit.hit_point,
-                        direction,
-                    },
+                Ray {
+                    origin: hit.hit_point,
+                    direction,
                 }
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::microfacet::Tangent;
+    use crate::point::Point3;
+    use crate::thinfilm::Thickness;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    //vecteur unitaire dans l'hémisphère opposé à la normale (0, 1, 0): un rayon qui arrive sur la surface
+    fn incident() -> impl Strategy<Value = Vec3> {
+        (-1.0..1., -1.0..-0.01, -1.0..1.)
+            .prop_filter("non nul", |(x, y, z): &(f64, f64, f64)| {
+                x * x + y * y + z * z > 1e-4
+            })
+            .prop_map(|(x, y, z)| Vec3(x, y, z).unit())
+    }
+
+    #[test]
+    fn reflect_mirrors_around_normal() {
+        let r = reflect(Vec3(1., -1., 0.), Vec3(0., 1., 0.));
+        assert!(close(r.0, 1.) && close(r.1, 1.) && close(r.2, 0.));
+    }
+
+    #[test]
+    fn reflectance_bounds() {
+        //incidence normale: r0 = ((1 - n) / (1 + n))^2, soit 4% pour le verre
+        assert!(close(reflectance(1., 1.5), 0.04));
+        assert!(close(reflectance(1., 1. / 1.5), 0.04));
+        //incidence rasante: réflexion totale
+        assert!(close(reflectance(0., 1.5), 1.));
+        //même milieu: pas de réflexion en incidence normale
+        assert!(close(reflectance(1., 1.), 0.));
+    }
+
+    #[test]
+    fn refract_through_same_medium_keeps_direction() {
+        let uv = Vec3(1., -2., 0.5).unit();
+        let r = refract(uv, Vec3(0., 1., 0.), 1.);
+        assert!((r - uv).len() < 1e-9);
+    }
+
+    #[test]
+    fn diffuse_scatter_attenuates_by_colour() {
+        let material = diffuse(0.2, 0.4, 0.6);
+        let ray = Ray {
+            origin: Point3(0., 1., 0.),
+            direction: Vec3(0., -1., 0.),
+        };
+        let hit = Intersection::new(
+            &ray,
+            1.,
+            Point3(0., 0., 0.),
+            Vec3(0., 1., 0.),
+            material.clone(),
+        );
+        let Reflexion {
+            attenuation,
+            reflected_ray,
+        } = material.scatter(&hit, &ray).unwrap();
+        assert_eq!(
+            (attenuation.red, attenuation.green, attenuation.blue),
+            (0.2, 0.4, 0.6)
+        );
+        //normale + vecteur de la sphère unité: toujours du côté de la normale
+        assert!(reflected_ray.direction.y() >= 0.);
+    }
+
+    #[test]
+    fn conductors_follow_their_measured_indices() {
+        //l'or réfléchit le rouge bien plus que le bleu, l'argent est presque neutre
+        let gold = conductor(Conductor::Gold, 0.).color;
+        assert!(gold.red > 0.8 && gold.blue < 0.45);
+        let silver = conductor(Conductor::Silver, 0.).color;
+        assert!(silver.blue > 0.9 && (silver.red - silver.blue).abs() < 0.05);
+        for metal in Conductor::ALL {
+            assert_eq!(Conductor::parse(metal.name()), Some(metal));
+            for lambda in [400., 550., 700.] {
+                let normal = metal.fresnel(lambda, 1.);
+                assert!((0. ..1.).contains(&normal));
+                //incidence rasante: réflexion totale
+                assert!(close(metal.fresnel(lambda, 0.), 1.));
+            }
+        }
+        //indice réel: même résultat que Fresnel pour un diélectrique, Schlick en est proche
+        let f = fresnel_complex(1., Complex(1.5, 0.));
+        assert!(close(f, 0.04));
+    }
+
+    #[test]
+    fn glasses_match_their_catalogue() {
+        //indice à la raie d et nombre d'Abbe publiés
+        for (glass, n_d, abbe) in [
+            (Glass::Bk7, 1.5168, 64.17),
+            (Glass::FusedSilica, 1.4585, 67.8),
+            (Glass::Sf11, 1.7847, 25.68),
+            (Glass::Diamond, 2.4175, 55.3),
+        ] {
+            let dispersion = glass.dispersion();
+            assert!(
+                (dispersion.indice(LAMBDA_D) - n_d).abs() < 1e-3,
+                "{:?}",
+                glass
+            );
+            assert!(
+                (dispersion.abbe() - abbe).abs() < 1.,
+                "{:?} {}",
+                glass,
+                dispersion.abbe()
+            );
+            assert_eq!(Glass::parse(glass.name()), Some(glass));
+        }
+        let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.004 };
+        assert!(cauchy.indice(400.) > cauchy.indice(700.));
+    }
+
+    #[test]
+    fn dispersion_bends_each_wavelength_differently() {
+        let material = glass(Glass::Sf11);
+        let ray = Ray {
+            origin: Point3(-1., 1., 0.),
+            direction: Vec3(1., -1., 0.),
+        };
+        let hit = Intersection::new(
+            &ray,
+            1.,
+            Point3(0., 0., 0.),
+            Vec3(0., 1., 0.),
+            material.clone(),
+        );
+        //premier rayon réfracté obtenu pour chaque longueur d'onde
+        let refracted = |lambda| loop {
+            let mut wavelengths = Wavelengths {
+                lambda: [lambda; spectrum::WAVELENGTHS],
+                terminated: false,
+            };
+            let reflexion = material
+                .scatter_spectral(&hit, &ray, &mut wavelengths)
+                .unwrap();
+            assert!(wavelengths.terminated);
+            if reflexion.reflected_ray.direction.y() < 0. {
+                return reflexion.reflected_ray.direction.unit();
+            }
+        };
+        //le bleu, d'indice plus fort, est plus dévié vers la normale
+        let (blue, red) = (refracted(420.), refracted(680.));
+        assert!(blue.x() < red.x());
+        //la réfraction suit Snell-Descartes: sin θ = sin 45° / n
+        let n = Glass::Sf11.dispersion().indice(420.);
+        assert!((blue.x() - (0.5f64).sqrt() / n).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "absorption distance must be positive")]
+    fn absorption_needs_a_positive_distance() {
+        absorbing_dielectric(0.5, 1., 1., 0., 1.5);
+    }
+
+    #[test]
+    fn absorption_depends_on_distance_travelled_inside() {
+        //moitié du rouge transmis après 2 unités
+        let material = absorbing_dielectric(0.5, 1., 1., 2., 1.5);
+        let transmittance = |origin: Point3, outward: Vec3| {
+            let ray = Ray {
+                origin,
+                direction: Vec3(0., -2., 0.),
+            };
+            //le rayon (non unitaire) parcourt une distance de 4
+            let hit = Intersection::new(&ray, 2., Point3(0., 0., 0.), outward, material.clone());
+            (
+                material.transmittance(&hit, &ray),
+                material.transmittance_spectral(&hit, &ray, &Wavelengths::sample()),
+            )
+        };
+        //le rayon sort de l'objet: il a traversé 4 unités de verre
+        let (inside, spectral) = transmittance(Point3(0., 4., 0.), Vec3(0., -1., 0.));
+        assert!(close(inside.red, 0.25) && close(inside.green, 1.) && close(inside.blue, 1.));
+        assert!(spectral.0.iter().all(|t| (0. ..=1.).contains(t)));
+        //le rayon entre dans l'objet: rien n'a été absorbé
+        let (outside, spectral) = transmittance(Point3(0., 4., 0.), Vec3(0., 1., 0.));
+        assert_eq!((outside.red, outside.green, outside.blue), (1., 1., 1.));
+        assert_eq!(spectral, Spectrum::ONE);
+        //les surfaces elles-mêmes ne teintent pas
+        let Color { red, green, blue } = material.color;
+        assert_eq!((red, green, blue), (1., 1., 1.));
+    }
+
+    #[test]
+    fn thin_films_reflect_or_transmit_without_bending() {
+        let ray = Ray {
+            origin: Point3(-1., 1., 0.),
+            direction: Vec3(1., -1., 0.),
+        };
+        let hit = |material: &GenericMaterial| {
+            Intersection::new(
+                &ray,
+                1.,
+                Point3(0., 0., 0.),
+                Vec3(0., 1., 0.),
+                material.clone(),
+            )
+        };
+        //bulle: la plupart des rayons traversent sans être déviés, la moyenne des poids conserve l'énergie
+        let bubble = soap_bubble(Thickness::Constant(400.));
+        let (mut reflected, mut energy) = (0, Color::EMPTY);
+        for _ in 0..2000 {
+            let Reflexion {
+                attenuation,
+                reflected_ray,
+            } = bubble.scatter(&hit(&bubble), &ray).unwrap();
+            let direction = reflected_ray.direction.unit();
+            if direction.y() > 0. {
+                reflected += 1;
+            } else {
+                assert!((direction - ray.direction.unit()).len() < 1e-9);
+            }
+            energy = energy + attenuation * (1. / 2000.);
+        }
+        assert!(reflected > 0 && reflected < 400, "{}", reflected);
+        for v in [energy.red, energy.green, energy.blue] {
+            assert!((v - 1.).abs() < 0.02, "{:?}", energy);
+        }
+        //métal anodisé: toujours réfléchi, coloré par l'épaisseur de la couche d'oxyde
+        let colour = |thickness| {
+            let anodised = thin_film(
+                conductor(Conductor::Aluminium, 0.),
+                1.65,
+                Thickness::Constant(thickness),
+            );
+            let Reflexion {
+                attenuation,
+                reflected_ray,
+            } = anodised.scatter(&hit(&anodised), &ray).unwrap();
+            assert!(reflected_ray.direction.y() > 0.);
+            attenuation
+        };
+        let (thin, thick) = (colour(120.), colour(200.));
+        assert!((thin.red - thick.red).abs() > 0.05 || (thin.blue - thick.blue).abs() > 0.05);
+    }
+
+    #[test]
+    #[should_panic(expected = "thin film needs a dielectric or a measured conductor")]
+    fn thin_film_rejects_other_bases() {
+        thin_film(metal(0.8, 0.8, 0.8, 0.), 1.33, Thickness::Constant(300.));
+    }
+
+    #[test]
+    #[should_panic(expected = "microfacet lobes need a metal or a conductor")]
+    fn microfacets_reject_other_bases() {
+        anisotropic(
+            diffuse(0.8, 0.8, 0.8),
+            0.2,
+            0.2,
+            Tangent::Direction(Vec3(0., 0., 1.)),
+        );
+    }
+
+    #[test]
+    fn rough_metals_keep_their_energy() {
+        //test du four blanc: un métal parfaitement réfléchissant renvoie toute la lumière, quelle que soit sa rugosité
+        let ray = Ray {
+            origin: Point3(-1., 1., 0.),
+            direction: Vec3(1., -1., 0.),
+        };
+        for (roughness_u, roughness_v) in [(0.1, 0.1), (0.9, 0.2), (1., 1.)] {
+            let material = anisotropic(
+                metal(1., 1., 1., 0.),
+                roughness_u,
+                roughness_v,
+                Tangent::Direction(Vec3(0., 0., 1.)),
+            );
+            let hit = Intersection::new(
+                &ray,
+                1.,
+                Point3(0., 0., 0.),
+                Vec3(0., 1., 0.),
+                material.clone(),
+            );
+            let count = 20_000;
+            let mut energy = 0.;
+            for _ in 0..count {
+                if let Some(Reflexion {
+                    attenuation,
+                    reflected_ray,
+                }) = material.scatter(&hit, &ray)
+                {
+                    assert!(reflected_ray.direction.y() > 0.);
+                    energy += attenuation.green / count as f64;
+                }
+            }
+            assert!((energy - 1.).abs() < 0.03, "{} {}", roughness_u, energy);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn reflectance_decreases_with_cosinus(a in 0.0..1., b in 0.0..1., ratio in 0.2..5.) {
+            let (low, high) = if a < b { (a, b) } else { (b, a) };
+            prop_assert!(reflectance(low, ratio) >= reflectance(high, ratio));
+            prop_assert!((0. ..=1.).contains(&reflectance(low, ratio)));
+        }
+
+        #[test]
+        fn reflection_preserves_length_and_angle(uv in incident()) {
+            let normale = Vec3(0., 1., 0.);
+            let r = reflect(uv, normale);
+            prop_assert!(close(r.len(), uv.len()));
+            prop_assert!(close(r.scalar_product(normale), -uv.scalar_product(normale)));
+        }
+
+        #[test]
+        fn refraction_follows_snell(uv in incident(), ratio in 0.3..3.) {
+            let normale = Vec3(0., 1., 0.);
+            let cos_i = (-uv).scalar_product(normale);
+            let sin_i = (1. - cos_i * cos_i).sqrt();
+            //pas de réflexion totale interne
+            prop_assume!(ratio * sin_i <= 1.);
+            let r = refract(uv, normale, ratio);
+            let cos_t = (-r).scalar_product(normale);
+            let sin_t = (1. - cos_t * cos_t).max(0.).sqrt();
+            prop_assert!((r.len() - 1.).abs() < 1e-9);
+            prop_assert!(cos_t >= 0.);
+            prop_assert!((sin_t - ratio * sin_i).abs() < 1e-6);
+            //le rayon réfracté reste dans le plan d'incidence
+            prop_assert!(uv.cross_product(normale).scalar_product(r).abs() < 1e-9);
+        }
+    }
+}
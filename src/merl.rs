@@ -0,0 +1,416 @@
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, Error, ErrorKind, Read};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::checkpoint::{read_f64, read_u32, SceneHasher};
+use crate::color::Color;
+use crate::geometry::Intersection;
+use crate::microfacet::frame;
+use crate::ray::Ray;
+use crate::sampler::random;
+use crate::vec::Vec3;
+
+//BRDF isotrope mesurée de la base MERL (Matusik et al., "A data-driven reflectance model", 2003):
+//réflectances tabulées selon les angles du demi-vecteur et de la différence (Rusinkiewicz 1998),
+//θh échantillonné en racine carrée pour resserrer les échantillons autour du reflet
+const THETA_HALF: usize = 90;
+const THETA_DIFF: usize = 90;
+const PHI_DIFF: usize = 180;
+const SIZE: usize = THETA_HALF * THETA_DIFF * PHI_DIFF;
+//facteurs d'échelle des canaux, donnés avec la base
+const SCALE: [f64; 3] = [1. / 1500., 1.15 / 1500., 1.66 / 1500.];
+
+//tabulation de l'échantillonnage d'importance: pour chaque angle d'incidence, la réflectance
+//(luminance × cos θo) sur une grille de directions sortantes (cos θo, φo par rapport au plan d'incidence)
+const SAMPLED_INCIDENCES: usize = 32;
+const SAMPLED_COSINES: usize = 32;
+const SAMPLED_AZIMUTHS: usize = 64;
+//part des directions tirées selon le cosinus, qui couvre ce que la grille aurait manqué
+const COSINE_FRACTION: f64 = 0.1;
+
+pub struct Merl {
+    //réflectances rgb, déjà mises à l'échelle
+    values: Vec<[f32; 3]>,
+    digest: u64,
+    table: OnceLock<Vec<Distribution>>,
+}
+
+//distribution constante par morceaux des directions sortantes, pour une incidence
+struct Distribution {
+    //fonction de répartition des lignes (cos θo), puis des colonnes (φo) de chaque ligne
+    rows: Vec<f64>,
+    columns: Vec<Vec<f64>>,
+    weights: Vec<f64>,
+    total: f64,
+}
+
+impl Merl {
+    pub fn load(path: &Path) -> std::io::Result<Merl> {
+        Merl::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    //format .binary: trois entiers (résolutions de θh, θd, φd), puis les trois canaux à la suite en f64
+    pub fn read<R: Read>(reader: &mut R) -> std::io::Result<Merl> {
+        let dimensions = [read_u32(reader)?, read_u32(reader)?, read_u32(reader)?];
+        if dimensions != [THETA_HALF, THETA_DIFF, PHI_DIFF].map(|d| d as u32) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unexpected MERL resolution {:?}", dimensions),
+            ));
+        }
+        let mut values = vec![[0f32; 3]; SIZE];
+        for (channel, scale) in SCALE.iter().enumerate() {
+            for value in values.iter_mut() {
+                //les mesures manquantes sont négatives
+                value[channel] = (read_f64(reader)? * scale).max(0.) as f32;
+            }
+        }
+        Ok(Merl::new(values))
+    }
+
+    //tabule une BRDF analytique f(θh, θd, φd) dans la même paramétrisation (validation, tests)
+    pub fn from_fn<F: Fn(f64, f64, f64) -> Color>(f: F) -> Merl {
+        let mut values = Vec::with_capacity(SIZE);
+        for h in 0..THETA_HALF {
+            let theta_half = (h as f64 / THETA_HALF as f64).powi(2) * FRAC_PI_2;
+            for d in 0..THETA_DIFF {
+                let theta_diff = d as f64 / THETA_DIFF as f64 * FRAC_PI_2;
+                for p in 0..PHI_DIFF {
+                    let color = f(theta_half, theta_diff, p as f64 / PHI_DIFF as f64 * PI);
+                    values.push([color.red, color.green, color.blue].map(|v| v.max(0.) as f32));
+                }
+            }
+        }
+        Merl::new(values)
+    }
+
+    //l'empreinte des données entre dans le hash de scène: elle doit être stable d'une version de rust à l'autre
+    fn new(values: Vec<[f32; 3]>) -> Merl {
+        let mut hasher = SceneHasher::new();
+        values
+            .iter()
+            .for_each(|value| value.map(f32::to_bits).hash(&mut hasher));
+        Merl {
+            values,
+            digest: hasher.finish(),
+            table: OnceLock::new(),
+        }
+    }
+
+    //BRDF entre deux directions du repère local (normale selon z), nulle sous l'horizon
+    pub fn eval(&self, wi: Vec3, wo: Vec3) -> Color {
+        if wi.2 <= 0. || wo.2 <= 0. {
+            return Color::EMPTY;
+        }
+        let (theta_half, theta_diff, phi_diff) = half_diff(wi, wo);
+        //indices continus, les échantillons sont aux indices entiers comme dans le code de référence
+        let h = ((theta_half / FRAC_PI_2).max(0.).sqrt() * THETA_HALF as f64)
+            .clamp(0., (THETA_HALF - 1) as f64);
+        let d = (theta_diff / FRAC_PI_2 * THETA_DIFF as f64).clamp(0., (THETA_DIFF - 1) as f64);
+        //réciprocité: φd et φd + π sont équivalents
+        let p = phi_diff.rem_euclid(PI) / PI * PHI_DIFF as f64;
+        let corners = |x: f64, n: usize, wrap: bool| {
+            let i0 = (x.floor() as usize).min(n - 1);
+            let i1 = if wrap {
+                (i0 + 1) % n
+            } else {
+                (i0 + 1).min(n - 1)
+            };
+            (i0, i1, x - x.floor())
+        };
+        let (h0, h1, th) = corners(h, THETA_HALF, false);
+        let (d0, d1, td) = corners(d, THETA_DIFF, false);
+        let (p0, p1, tp) = corners(p, PHI_DIFF, true);
+        let mut value = [0.; 3];
+        for (h, wh) in [(h0, 1. - th), (h1, th)] {
+            for (d, wd) in [(d0, 1. - td), (d1, td)] {
+                for (p, wp) in [(p0, 1. - tp), (p1, tp)] {
+                    let sample = self.values[(h * THETA_DIFF + d) * PHI_DIFF + p];
+                    let weight = wh * wd * wp;
+                    (0..3).for_each(|c| value[c] += sample[c] as f64 * weight);
+                }
+            }
+        }
+        Color::new(value[0], value[1], value[2])
+    }
+
+    //albedo directionnel (fraction réfléchie) pour une incidence de cosinus `cos_i`, intégré sur la grille
+    pub fn albedo(&self, cos_i: f64) -> Color {
+        let wi = incidence(cos_i);
+        let cell = 1. / SAMPLED_COSINES as f64 * 2. * PI / SAMPLED_AZIMUTHS as f64;
+        let mut sum = Color::EMPTY;
+        for j in 0..SAMPLED_COSINES {
+            for l in 0..SAMPLED_AZIMUTHS {
+                let wo = outgoing(j, l);
+                sum = sum + self.eval(wi, wo) * (wo.2 * cell);
+            }
+        }
+        sum
+    }
+
+    //direction réfléchie tirée selon la tabulation, et poids f cos θo / pdf
+    pub(crate) fn sample(&self, hit: &Intersection, incident_ray: &Ray) -> Option<(Vec3, Color)> {
+        //repère local dont le plan xz contient la direction d'incidence
+        let to_viewer = -incident_ray.direction.unit();
+        let (t, b, n) = frame(hit.normale.unit(), to_viewer);
+        let local = |v: Vec3| {
+            Vec3(
+                v.scalar_product(t),
+                v.scalar_product(b),
+                v.scalar_product(n),
+            )
+        };
+        let wi = local(to_viewer);
+        if wi.2 <= 0. {
+            return None;
+        }
+        let table = self.table.get_or_init(|| self.tabulate());
+        let index = ((wi.2.min(1.).acos() / FRAC_PI_2 * SAMPLED_INCIDENCES as f64) as usize)
+            .min(SAMPLED_INCIDENCES - 1);
+        let distribution = &table[index];
+        let wo = if distribution.total <= 0. || random::<f64>() < COSINE_FRACTION {
+            cosine_direction()
+        } else {
+            distribution.sample()
+        };
+        if wo.2 <= 0. {
+            return None;
+        }
+        let pdf = if distribution.total <= 0. {
+            wo.2 / PI
+        } else {
+            COSINE_FRACTION * wo.2 / PI + (1. - COSINE_FRACTION) * distribution.pdf(wo)
+        };
+        let weight = self.eval(wi, wo) * (wo.2 / pdf);
+        Some((wo.0 * t + wo.1 * b + wo.2 * n, weight))
+    }
+
+    fn tabulate(&self) -> Vec<Distribution> {
+        (0..SAMPLED_INCIDENCES)
+            .map(|k| {
+                let theta = (k as f64 + 0.5) / SAMPLED_INCIDENCES as f64 * FRAC_PI_2;
+                let wi = incidence(theta.cos());
+                let weights = (0..SAMPLED_COSINES * SAMPLED_AZIMUTHS)
+                    .map(|cell| {
+                        let wo = outgoing(cell / SAMPLED_AZIMUTHS, cell % SAMPLED_AZIMUTHS);
+                        let f = self.eval(wi, wo);
+                        (0.2126 * f.red + 0.7152 * f.green + 0.0722 * f.blue) * wo.2
+                    })
+                    .collect();
+                Distribution::new(weights)
+            })
+            .collect()
+    }
+}
+
+impl Hash for Merl {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.digest.hash(state);
+    }
+}
+
+impl Distribution {
+    fn new(weights: Vec<f64>) -> Distribution {
+        let cumulate = |values: &mut dyn Iterator<Item = f64>| {
+            let mut sum = 0.;
+            values
+                .map(|v| {
+                    sum += v;
+                    sum
+                })
+                .collect::<Vec<_>>()
+        };
+        let columns = weights
+            .chunks(SAMPLED_AZIMUTHS)
+            .map(|row| cumulate(&mut row.iter().copied()))
+            .collect::<Vec<_>>();
+        let rows = cumulate(&mut columns.iter().map(|row| row[SAMPLED_AZIMUTHS - 1]));
+        let total = rows[SAMPLED_COSINES - 1];
+        Distribution {
+            rows,
+            columns,
+            weights,
+            total,
+        }
+    }
+
+    fn sample(&self) -> Vec3 {
+        let find = |cdf: &[f64], x: f64| cdf.partition_point(|&c| c <= x).min(cdf.len() - 1);
+        let j = find(&self.rows, random::<f64>() * self.total);
+        let row = &self.columns[j];
+        let l = find(row, random::<f64>() * row[SAMPLED_AZIMUTHS - 1]);
+        direction(
+            (j as f64 + random::<f64>()) / SAMPLED_COSINES as f64,
+            (l as f64 + random::<f64>()) / SAMPLED_AZIMUTHS as f64 * 2. * PI,
+        )
+    }
+
+    //densité par unité d'angle solide (dω = d(cos θ) dφ)
+    fn pdf(&self, wo: Vec3) -> f64 {
+        let j = ((wo.2 * SAMPLED_COSINES as f64) as usize).min(SAMPLED_COSINES - 1);
+        let phi = wo.1.atan2(wo.0).rem_euclid(2. * PI);
+        let l = ((phi / (2. * PI) * SAMPLED_AZIMUTHS as f64) as usize).min(SAMPLED_AZIMUTHS - 1);
+        let cell = 1. / SAMPLED_COSINES as f64 * 2. * PI / SAMPLED_AZIMUTHS as f64;
+        self.weights[j * SAMPLED_AZIMUTHS + l] / self.total / cell
+    }
+}
+
+fn incidence(cos_i: f64) -> Vec3 {
+    let cos_i = cos_i.clamp(0., 1.);
+    Vec3((1. - cos_i * cos_i).sqrt(), 0., cos_i)
+}
+
+//centre d'une cellule de la grille des directions sortantes
+fn outgoing(j: usize, l: usize) -> Vec3 {
+    direction(
+        (j as f64 + 0.5) / SAMPLED_COSINES as f64,
+        (l as f64 + 0.5) / SAMPLED_AZIMUTHS as f64 * 2. * PI,
+    )
+}
+
+fn direction(cosinus: f64, phi: f64) -> Vec3 {
+    let sinus = (1. - cosinus * cosinus).max(0.).sqrt();
+    Vec3(sinus * phi.cos(), sinus * phi.sin(), cosinus)
+}
+
+fn cosine_direction() -> Vec3 {
+    let r: f64 = random();
+    direction(r.sqrt(), random::<f64>() * 2. * PI)
+}
+
+//angles (θh, θd, φd) du demi-vecteur et de la direction d'incidence dans le repère du demi-vecteur
+fn half_diff(wi: Vec3, wo: Vec3) -> (f64, f64, f64) {
+    let half = (wi + wo).unit();
+    let theta_half = half.2.clamp(-1., 1.).acos();
+    let phi_half = half.1.atan2(half.0);
+    let diff = rotate(wi, Vec3(0., 0., 1.), -phi_half);
+    let diff = rotate(diff, Vec3(0., 1., 0.), -theta_half);
+    (
+        theta_half,
+        diff.2.clamp(-1., 1.).acos(),
+        diff.1.atan2(diff.0),
+    )
+}
+
+//rotation de Rodrigues autour d'un axe unitaire
+fn rotate(v: Vec3, axis: Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    v * cos + axis * (axis.scalar_product(v) * (1. - cos)) + axis.cross_product(v) * sin
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn lambertian(albedo: f64) -> Merl {
+        Merl::from_fn(|_, _, _| Color::new(albedo / PI, albedo / PI, albedo / PI))
+    }
+
+    #[test]
+    fn half_and_difference_angles() {
+        //réflexion miroir: demi-vecteur sur la normale, θd est l'angle d'incidence
+        let wi = direction(0.5, 0.3);
+        let wo = Vec3(-wi.0, -wi.1, wi.2);
+        let (theta_half, theta_diff, _) = half_diff(wi, wo);
+        assert!(theta_half.abs() < 1e-9);
+        assert!((theta_diff - 0.5f64.acos()).abs() < 1e-9);
+        //rétro-réflexion: θd nul, θh est l'angle d'incidence
+        let (theta_half, theta_diff, _) = half_diff(wi, wi);
+        assert!((theta_half - 0.5f64.acos()).abs() < 1e-9);
+        assert!(theta_diff.abs() < 1e-6);
+    }
+
+    #[test]
+    fn reads_the_merl_layout() {
+        //rouge = indice de θh, vert = indice de θd, bleu nul
+        let mut bytes = Vec::new();
+        for d in [90u32, 90, 180] {
+            bytes.extend(d.to_le_bytes());
+        }
+        for channel in 0..3 {
+            for i in 0..SIZE {
+                let value = match channel {
+                    0 => (i / (THETA_DIFF * PHI_DIFF)) as f64 / SCALE[0],
+                    1 => (i / PHI_DIFF % THETA_DIFF) as f64 / SCALE[1],
+                    _ => -1.,
+                };
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        let merl = Merl::read(&mut Cursor::new(&bytes)).unwrap();
+        //direction rétro-réfléchie à θh = (40 / 90)² π/2
+        let wi = incidence(((40f64 / 90.).powi(2) * FRAC_PI_2).cos());
+        let color = merl.eval(wi, wi);
+        assert!((color.red - 40.).abs() < 1e-3, "{:?}", color);
+        assert!(color.green.abs() < 1e-3 && color.blue == 0.);
+        //réflexion miroir à 30°: θh nul, θd = 30 (indice 30 × π/2 / 90 = 30°)
+        let wi = direction(30f64.to_radians().cos(), 0.);
+        let color = merl.eval(wi, Vec3(-wi.0, -wi.1, wi.2));
+        assert!(color.red.abs() < 1e-3 && (color.green - 30.).abs() < 1e-3);
+        //sous l'horizon
+        assert_eq!(merl.eval(wi, Vec3(0., 0., -1.)).green, 0.);
+
+        bytes[0] = 91;
+        assert!(Merl::read(&mut Cursor::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn tabulated_lambertian_keeps_its_albedo() {
+        let merl = lambertian(0.7);
+        let f = merl.eval(direction(0.3, 1.), direction(0.8, -2.));
+        assert!((f.green - 0.7 / PI).abs() < 1e-6);
+        assert!((merl.albedo(0.6).red - 0.7).abs() < 1e-3);
+    }
+
+    #[test]
+    fn importance_sampling_is_unbiased() {
+        //reflet brillant sur une base diffuse: la moyenne des poids tirés est l'albedo directionnel
+        let merl = Merl::from_fn(|theta_half, _, _| {
+            let lobe = 20. * theta_half.cos().powi(200);
+            Color::new(0.1 + lobe, 0.1 + lobe, 0.1 + lobe)
+        });
+        let hit = Intersection::new(
+            &Ray {
+                origin: crate::point::Point3(0., 0., 1.),
+                direction: Vec3(0., 0., -1.),
+            },
+            1.,
+            crate::point::Point3(0., 0., 0.),
+            Vec3(0., 0., 1.),
+            crate::material::diffuse(0., 0., 0.),
+        );
+        let ray = Ray {
+            origin: crate::point::Point3(-1., 0., 1.),
+            direction: incidence(0.5) * -1.,
+        };
+        let count = 100_000;
+        let mut sum = 0.;
+        for _ in 0..count {
+            if let Some((direction, weight)) = merl.sample(&hit, &ray) {
+                assert!(direction.2 > 0.);
+                sum += weight.green;
+            }
+        }
+        //intégration fine, indépendante de la grille de tabulation
+        let (cosines, azimuths) = (256, 512);
+        let mut expected = 0.;
+        for j in 0..cosines {
+            for l in 0..azimuths {
+                let cosinus = (j as f64 + 0.5) / cosines as f64;
+                let wo = direction(cosinus, (l as f64 + 0.5) / azimuths as f64 * 2. * PI);
+                expected += merl.eval(incidence(0.5), wo).green * cosinus;
+            }
+        }
+        expected *= 2. * PI / (cosines * azimuths) as f64;
+        assert!(
+            (sum / count as f64 - expected).abs() < 0.02 * expected,
+            "{} {}",
+            sum / count as f64,
+            expected
+        );
+    }
+}
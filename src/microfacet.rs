@@ -0,0 +1,309 @@
+use std::f64::consts::PI;
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+use crate::geometry::Intersection;
+use crate::ray::Ray;
+use crate::sampler::random;
+use crate::vec::Vec3;
+
+//réflexion par microfacettes GGX anisotrope (métaux brossés, électroménager, horlogerie):
+//la surface est faite de facettes orientées selon une distribution de rugosités roughness_u le long de la
+//tangente et roughness_v en travers. les directions sont tirées selon les normales visibles (Heitz 2018),
+//l'énergie perdue par les réflexions multiples entre facettes est rendue par la compensation de Turquin (2019)
+#[derive(Clone, Debug)]
+pub struct Microfacet {
+    //lobes mélangés, chacun avec sa part de la réflexion (un lobe net et un lobe voilé pour un poli imparfait)
+    pub lobes: Vec<Lobe>,
+    pub tangent: Tangent,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Lobe {
+    pub weight: f64,
+    //rugosités perceptuelles dans [0, 1], α = rugosité²
+    pub roughness_u: f64,
+    pub roughness_v: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Tangent {
+    //direction de u croissant donnée par l'objet (autour de l'axe y pour une sphère)
+    Uv,
+    //direction fixe, projetée sur le plan tangent
+    Direction(Vec3),
+}
+
+//direction tirée sur la surface
+pub(crate) struct Sample {
+    pub direction: Vec3,
+    //cosinus entre la direction incidente et la facette, pour Fresnel
+    pub cos_half: f64,
+    //poids de l'échantillon à Fresnel près: G2 / G1
+    pub shadowing: f64,
+    //énergie des réflexions multiples relativement à la réflexion simple, (1 - E) / E
+    pub missing: f64,
+}
+
+impl Lobe {
+    pub fn new(weight: f64, roughness_u: f64, roughness_v: f64) -> Lobe {
+        Lobe {
+            weight,
+            roughness_u,
+            roughness_v,
+        }
+    }
+
+    fn alpha(&self) -> (f64, f64) {
+        let alpha = |roughness: f64| roughness.clamp(0., 1.).powi(2).max(1e-4);
+        (alpha(self.roughness_u), alpha(self.roughness_v))
+    }
+}
+
+impl Hash for Microfacet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for lobe in &self.lobes {
+            [lobe.weight, lobe.roughness_u, lobe.roughness_v]
+                .map(f64::to_bits)
+                .hash(state);
+        }
+        match self.tangent {
+            Tangent::Uv => 0u8.hash(state),
+            Tangent::Direction(Vec3(x, y, z)) => [x, y, z].map(f64::to_bits).hash(state),
+        }
+    }
+}
+
+impl Microfacet {
+    //choisit un lobe selon les poids puis une normale de facette visible, et réfléchit le rayon sur celle-ci
+    //None si le rayon réfléchi passe sous la surface (il est masqué: l'énergie est compensée par `missing`)
+    pub(crate) fn sample(&self, hit: &Intersection, incident_ray: &Ray) -> Option<Sample> {
+        let total: f64 = self.lobes.iter().map(|lobe| lobe.weight).sum();
+        let mut choice = random::<f64>() * total;
+        let lobe = self
+            .lobes
+            .iter()
+            .find(|lobe| {
+                choice -= lobe.weight;
+                choice < 0.
+            })
+            .or(self.lobes.last())?;
+        let (alpha_u, alpha_v) = lobe.alpha();
+
+        let (t, b, n) = self.frame(hit);
+        let wo = -incident_ray.direction.unit();
+        let local = |v: Vec3| {
+            Vec3(
+                v.scalar_product(t),
+                v.scalar_product(b),
+                v.scalar_product(n),
+            )
+        };
+        let wo = local(wo);
+        if wo.2 <= 0. {
+            return None;
+        }
+        let m = sample_visible_normal(wo, alpha_u, alpha_v, random(), random());
+        let cos_half = wo.scalar_product(m);
+        let wi = 2. * cos_half * m - wo;
+        if wi.2 <= 0. {
+            return None;
+        }
+        let (lambda_o, lambda_i) = (lambda(wo, alpha_u, alpha_v), lambda(wi, alpha_u, alpha_v));
+        //rugosités vues dans le plan d'incidence et en travers
+        let azimuth = wo.1.atan2(wo.0);
+        let (cos2, sin2) = (azimuth.cos().powi(2), azimuth.sin().powi(2));
+        let albedo = directional_albedo(
+            wo.2,
+            (alpha_u * alpha_u * cos2 + alpha_v * alpha_v * sin2).sqrt(),
+            (alpha_u * alpha_u * sin2 + alpha_v * alpha_v * cos2).sqrt(),
+        );
+        Some(Sample {
+            direction: wi.0 * t + wi.1 * b + wi.2 * n,
+            cos_half,
+            shadowing: (1. + lambda_o) / (1. + lambda_o + lambda_i),
+            missing: (1. - albedo) / albedo,
+        })
+    }
+
+    //repère local: tangente, bitangente, normale (du côté du rayon incident)
+    fn frame(&self, hit: &Intersection) -> (Vec3, Vec3, Vec3) {
+        let tangent = match self.tangent {
+            Tangent::Uv => hit.tangent,
+            Tangent::Direction(direction) => direction,
+        };
+        frame(hit.normale.unit(), tangent)
+    }
+}
+
+//repère orthonormé autour de la normale `n`, la tangente est projetée sur le plan tangent
+pub(crate) fn frame(n: Vec3, tangent: Vec3) -> (Vec3, Vec3, Vec3) {
+    let mut t = tangent - tangent.scalar_product(n) * n;
+    if t.sqr_len() < 1e-12 {
+        //pas de tangente: n'importe quelle direction du plan tangent
+        let axis = if n.0.abs() < 0.9 {
+            Vec3(1., 0., 0.)
+        } else {
+            Vec3(0., 1., 0.)
+        };
+        t = axis - axis.scalar_product(n) * n;
+    }
+    let t = t.unit();
+    (t, n.cross_product(t), n)
+}
+
+//normale de facette visible depuis `wo`, tirée autour de `normal` pour une rugosité isotrope (interfaces
+//rugueuses des vernis); une rugosité nulle donne la normale
+pub(crate) fn sample_facet(normal: Vec3, wo: Vec3, roughness: f64) -> Vec3 {
+    if roughness <= 0. {
+        return normal;
+    }
+    let alpha = Lobe::new(1., roughness, roughness).alpha().0;
+    let (t, b, n) = frame(normal.unit(), Vec3(0., 0., 0.));
+    let local = Vec3(
+        wo.scalar_product(t),
+        wo.scalar_product(b),
+        wo.scalar_product(n),
+    );
+    if local.2 <= 0. {
+        return n;
+    }
+    let m = sample_visible_normal(local.unit(), alpha, alpha, random(), random());
+    m.0 * t + m.1 * b + m.2 * n
+}
+
+//échantillonnage des normales visibles de GGX depuis wo (repère local, z normal), Heitz 2018
+fn sample_visible_normal(wo: Vec3, alpha_u: f64, alpha_v: f64, u1: f64, u2: f64) -> Vec3 {
+    //configuration hémisphérique: facettes d'une surface de rugosité 1
+    let v = Vec3(alpha_u * wo.0, alpha_v * wo.1, wo.2).unit();
+    let length2 = v.0 * v.0 + v.1 * v.1;
+    let t1 = if length2 > 0. {
+        Vec3(-v.1, v.0, 0.) / length2.sqrt()
+    } else {
+        Vec3(1., 0., 0.)
+    };
+    let t2 = v.cross_product(t1);
+    let (r, phi) = (u1.sqrt(), 2. * PI * u2);
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1. + v.2);
+    let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * r * phi.sin();
+    let h = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * v;
+    Vec3(alpha_u * h.0, alpha_v * h.1, h.2.max(0.)).unit()
+}
+
+//Λ de Smith pour GGX anisotrope: G1 = 1 / (1 + Λ)
+fn lambda(w: Vec3, alpha_u: f64, alpha_v: f64) -> f64 {
+    let a2 = (alpha_u * alpha_u * w.0 * w.0 + alpha_v * alpha_v * w.1 * w.1) / (w.2 * w.2);
+    ((1. + a2).sqrt() - 1.) / 2.
+}
+
+//albedo directionnel E de la réflexion simple, Fresnel = 1, tabulé à la première utilisation selon μ = cos θo
+//et les rugosités dans le plan d'incidence et en travers (exact quand l'incidence suit la tangente ou la bitangente)
+const TABLE_MU: usize = 32;
+const TABLE_ALPHA: usize = 16;
+const TABLE_SAMPLES: usize = 16;
+
+fn directional_albedo(cos: f64, alpha_parallel: f64, alpha_across: f64) -> f64 {
+    static TABLE_E: OnceLock<Vec<f64>> = OnceLock::new();
+    let table = TABLE_E.get_or_init(|| {
+        let alpha = |i: usize| (i as f64 / (TABLE_ALPHA - 1) as f64).max(1e-4);
+        let mut table = Vec::with_capacity(TABLE_ALPHA * TABLE_ALPHA * TABLE_MU);
+        for i in 0..TABLE_ALPHA {
+            for j in 0..TABLE_ALPHA {
+                for k in 0..TABLE_MU {
+                    let mu = (k as f64 + 0.5) / TABLE_MU as f64;
+                    table.push(estimate_albedo(mu, alpha(i), alpha(j)));
+                }
+            }
+        }
+        table
+    });
+    //interpolation trilinéaire, μ au centre des cellules
+    let position = |v: f64, size: usize| {
+        let x = v.clamp(0., (size - 1) as f64);
+        let i = (x as usize).min(size - 2);
+        (i, x - i as f64)
+    };
+    let (i, ti) = position(alpha_parallel * (TABLE_ALPHA - 1) as f64, TABLE_ALPHA);
+    let (j, tj) = position(alpha_across * (TABLE_ALPHA - 1) as f64, TABLE_ALPHA);
+    let (k, tk) = position(cos * TABLE_MU as f64 - 0.5, TABLE_MU);
+    let mut e = 0.;
+    for (di, wi) in [(0, 1. - ti), (1, ti)] {
+        for (dj, wj) in [(0, 1. - tj), (1, tj)] {
+            for (dk, wk) in [(0, 1. - tk), (1, tk)] {
+                let index = ((i + di) * TABLE_ALPHA + j + dj) * TABLE_MU + k + dk;
+                e += wi * wj * wk * table[index];
+            }
+        }
+    }
+    e.max(1e-3)
+}
+
+//moyenne de G2 / G1 sur une grille stratifiée de normales visibles (déterministe, sans toucher au générateur)
+fn estimate_albedo(mu: f64, alpha_u: f64, alpha_v: f64) -> f64 {
+    let wo = Vec3((1. - mu * mu).sqrt(), 0., mu);
+    let lambda_o = lambda(wo, alpha_u, alpha_v);
+    let mut sum = 0.;
+    for a in 0..TABLE_SAMPLES {
+        for b in 0..TABLE_SAMPLES {
+            let u1 = (a as f64 + 0.5) / TABLE_SAMPLES as f64;
+            let u2 = (b as f64 + 0.5) / TABLE_SAMPLES as f64;
+            let m = sample_visible_normal(wo, alpha_u, alpha_v, u1, u2);
+            let wi = 2. * wo.scalar_product(m) * m - wo;
+            if wi.2 > 0. {
+                let lambda_i = lambda(wi, alpha_u, alpha_v);
+                sum += (1. + lambda_o) / (1. + lambda_o + lambda_i);
+            }
+        }
+    }
+    sum / (TABLE_SAMPLES * TABLE_SAMPLES) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visible_normals_face_the_viewer() {
+        let wo = Vec3(0.6, 0.3, 0.74).unit();
+        for i in 0..32 {
+            for j in 0..32 {
+                let m = sample_visible_normal(wo, 0.5, 0.05, i as f64 / 32., j as f64 / 32.);
+                assert!((m.len() - 1.).abs() < 1e-9);
+                assert!(m.2 >= 0. && wo.scalar_product(m) >= -1e-9);
+            }
+        }
+        //surface lisse: la facette est la normale
+        let m = sample_visible_normal(wo, 1e-6, 1e-6, 0.3, 0.7);
+        assert!((m - Vec3(0., 0., 1.)).len() < 1e-4);
+    }
+
+    #[test]
+    fn rough_surfaces_lose_energy_to_compensate() {
+        //lisse: tout est réfléchi une fois
+        assert!((directional_albedo(0.8, 0., 0.) - 1.).abs() < 1e-3);
+        //rugueux: une part de l'énergie n'est réfléchie qu'après plusieurs rebonds
+        //(0.351 par intégration directe de D G2 / 4 cos θo sur l'hémisphère)
+        let rough = directional_albedo(0.8, 1., 1.);
+        assert!((rough - 0.351).abs() < 0.01, "{}", rough);
+        //une rugosité en travers du plan d'incidence perd aussi de l'énergie
+        assert!(directional_albedo(0.8, 0.05, 0.8) < 0.9);
+    }
+
+    #[test]
+    fn anisotropy_stretches_along_the_tangent() {
+        //rugosité forte le long de x: les directions réfléchies s'étalent selon x, pas selon y
+        let wo = Vec3(0., 0., 1.);
+        let (mut spread_x, mut spread_y) = (0., 0.);
+        for i in 0..16 {
+            for j in 0..16 {
+                let m =
+                    sample_visible_normal(wo, 0.6, 0.02, (i as f64 + 0.5) / 16., j as f64 / 16.);
+                let wi = 2. * wo.scalar_product(m) * m - wo;
+                spread_x += wi.0.abs();
+                spread_y += wi.1.abs();
+            }
+        }
+        assert!(spread_x > 10. * spread_y);
+    }
+}
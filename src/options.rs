@@ -0,0 +1,186 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use ray::tonemap::{MAX_KELVIN, MIN_KELVIN};
+use ray::{Aov, ColorSpace, DisplayTransform, ToneMap};
+
+pub const USAGE: &str = "usage: ray [--output <file.ppm>] [--progressive <spp per pass>] \
+[--write-interval <seconds>] [--serve <port>] [--seed <n>] [--checkpoint <file>] \
+[--checkpoint-interval <seconds>] [--resume <file>] [--width <px>] [--samples <spp>] \
+[--tile-size <px>] [--threads <n>] [--coordinator <address:port>] [--worker <address:port>] \
+[--stats] [--stats-json <file>] [--aovs <name,...|all>] [--exr <file.exr>] [--denoise] \
+[--exposure <ev>] [--white-balance <kelvin>] [--tonemap <clamp|reinhard|aces>] \
+[--working-space <srgb|acescg|rec2020>] [--no-dither] [--spectral] [--toon]";
+
+pub struct Options {
+    pub output: PathBuf,
+    //nombre d'échantillons par pixel de chaque passe, None: une seule passe
+    pub progressive: Option<u32>,
+    //délai minimum entre deux réécritures de l'image en mode progressif
+    pub write_interval: Duration,
+    pub serve: Option<u16>,
+    pub seed: Option<u64>,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    //reprend le rendu depuis ce checkpoint, qui continue ensuite d'être mis à jour
+    pub resume: Option<PathBuf>,
+    pub width: u32,
+    pub samples: Option<u32>,
+    pub tile_size: Option<u32>,
+    pub threads: Option<usize>,
+    //écoute sur cette adresse et distribue le rendu aux workers qui s'y connectent
+    pub coordinator: Option<String>,
+    //se connecte à un coordinateur et rend les tuiles qu'il envoie
+    pub worker: Option<String>,
+    //affiche les statistiques du rendu à la fin
+    pub stats: bool,
+    pub stats_json: Option<PathBuf>,
+    pub aovs: Vec<Aov>,
+    //image linéaire et aovs au format EXR, par défaut à côté de l'image si des aovs sont demandés
+    pub exr: Option<PathBuf>,
+    //débruite les images écrites, à l'aide des aovs albedo, normal et depth ajoutés au rendu
+    pub denoise: bool,
+    //exposition, balance des blancs et tone mapping des images 8 bits
+    pub display: DisplayTransform,
+    //espace de couleurs du rendu, du film et de l'EXR
+    pub working_space: ColorSpace,
+    //rendu spectral, sans changer la scène
+    pub spectral: bool,
+    //rendu stylisé, dont les images écrites portent les contours tirés des aovs object_id, depth et normal
+    pub toon: bool,
+}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut options = Options {
+            output: PathBuf::from("back.ppm"),
+            progressive: None,
+            write_interval: Duration::from_secs(10),
+            serve: None,
+            seed: None,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(60),
+            resume: None,
+            width: 1920,
+            samples: None,
+            tile_size: None,
+            threads: None,
+            coordinator: None,
+            worker: None,
+            stats: false,
+            stats_json: None,
+            aovs: Vec::new(),
+            exr: None,
+            denoise: false,
+            display: DisplayTransform::default(),
+            working_space: ColorSpace::Srgb,
+            spectral: false,
+            toon: false,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--output" => options.output = PathBuf::from(value(&arg, args.next())?),
+                "--progressive" => options.progressive = Some(number(&arg, args.next())?),
+                "--write-interval" => {
+                    options.write_interval = Duration::from_secs(number(&arg, args.next())?)
+                }
+                "--serve" => options.serve = Some(number(&arg, args.next())?),
+                "--seed" => options.seed = Some(number(&arg, args.next())?),
+                "--checkpoint" => {
+                    options.checkpoint = Some(PathBuf::from(value(&arg, args.next())?))
+                }
+                "--checkpoint-interval" => {
+                    options.checkpoint_interval = Duration::from_secs(number(&arg, args.next())?)
+                }
+                "--width" => options.width = number(&arg, args.next())?,
+                "--samples" => options.samples = Some(number(&arg, args.next())?),
+                "--tile-size" => options.tile_size = Some(number(&arg, args.next())?),
+                "--threads" => options.threads = Some(number(&arg, args.next())?),
+                "--coordinator" => options.coordinator = Some(value(&arg, args.next())?),
+                "--worker" => options.worker = Some(value(&arg, args.next())?),
+                "--resume" => options.resume = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--aovs" => options.aovs = aovs(&value(&arg, args.next())?)?,
+                "--exr" => options.exr = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--denoise" => options.denoise = true,
+                "--exposure" => options.display.exposure = number(&arg, args.next())?,
+                "--white-balance" => {
+                    options.display.white_balance = Some(number(&arg, args.next())?)
+                }
+                "--no-dither" => options.display.dither = false,
+                "--spectral" => options.spectral = true,
+                "--toon" => options.toon = true,
+                "--working-space" => {
+                    let name = value(&arg, args.next())?;
+                    options.working_space = ColorSpace::parse(&name)
+                        .ok_or_else(|| format!("unknown colour space {}", name))?
+                }
+                "--tonemap" => {
+                    let name = value(&arg, args.next())?;
+                    options.display.tone_map = ToneMap::parse(&name)
+                        .ok_or_else(|| format!("unknown tone mapping {}", name))?
+                }
+                "--stats" => options.stats = true,
+                "--stats-json" => {
+                    options.stats_json = Some(PathBuf::from(value(&arg, args.next())?))
+                }
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        if options.width < 2
+            || options.samples == Some(0)
+            || options.tile_size == Some(0)
+            || options.threads == Some(0)
+            || !options.display.exposure.is_finite()
+            || options
+                .display
+                .white_balance
+                .is_some_and(|kelvin| !(MIN_KELVIN..=MAX_KELVIN).contains(&kelvin))
+        {
+            return Err(format!(
+                "width must be at least 2, samples, tile size and threads at least 1, \
+exposure finite and white balance between {} and {} K",
+                MIN_KELVIN, MAX_KELVIN
+            ));
+        }
+        //le rendu distribué n'écrit ni ne reprend de checkpoint
+        if (options.coordinator.is_some() || options.worker.is_some())
+            && (options.checkpoint.is_some() || options.resume.is_some())
+        {
+            return Err(
+                "--checkpoint and --resume cannot be used with --coordinator or --worker".into(),
+            );
+        }
+        let mut required = Vec::new();
+        if options.denoise {
+            required.extend([Aov::Depth, Aov::Normal, Aov::Albedo]);
+        }
+        if options.toon {
+            required.extend([Aov::Depth, Aov::Normal, Aov::ObjectId]);
+        }
+        for aov in required {
+            if !options.aovs.contains(&aov) {
+                options.aovs.push(aov);
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn aovs(list: &str) -> Result<Vec<Aov>, String> {
+    if list == "all" {
+        return Ok(Aov::ALL.to_vec());
+    }
+    list.split(',')
+        .map(|name| Aov::parse(name).ok_or_else(|| format!("unknown aov {}", name)))
+        .collect()
+}
+
+fn value(arg: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("missing value for {}", arg))
+}
+
+fn number<N: std::str::FromStr>(arg: &str, v: Option<String>) -> Result<N, String> {
+    let v = value(arg, v)?;
+    v.parse()
+        .map_err(|_| format!("invalid value for {}: {}", arg, v))
+}
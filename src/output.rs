@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::color::Color;
+use crate::exr::Exr;
+use crate::film::Film;
+use crate::png::Png;
+use crate::ppm::Ppm;
+use crate::tonemap::DisplayTransform;
+
+//le film est linéaire, la transformation d'affichage n'est appliquée qu'à la sortie
+//sans réglage: écrêtage à 1 et encodage sRGB (couleur sRGB linéaire en entrée)
+pub fn display(color: Color) -> Color {
+    DisplayTransform::default().apply(color)
+}
+
+//écriture atomique: on écrit dans un fichier temporaire à côté de la cible puis on le renomme,
+//un lecteur ne voit donc jamais une image à moitié écrite
+pub fn save_ppm(film: &Film, path: &Path) -> std::io::Result<()> {
+    save_ppm_with(film, path, &DisplayTransform::default())
+}
+
+pub fn save_ppm_with(film: &Film, path: &Path, display: &DisplayTransform) -> std::io::Result<()> {
+    let mut tmp = PathBuf::from(path);
+    tmp.set_extension("ppm.tmp");
+
+    let file = File::create(&tmp)?;
+    let mut ppm = Ppm::new(
+        BufWriter::with_capacity((film.width() * 13) as usize, file),
+        film.width(),
+        film.height(),
+        255,
+    )?;
+    for y in 0..film.height() {
+        let colors = display_line(film, y, display, 255.);
+        ppm.next_pixels(&colors)?;
+    }
+    let mut writer = ppm.into_inner();
+    writer.flush()?;
+    drop(writer);
+    std::fs::rename(&tmp, path)
+}
+
+pub fn save_png(film: &Film, path: &Path) -> std::io::Result<()> {
+    save_png_with(film, path, &DisplayTransform::default())
+}
+
+pub fn save_png_with(film: &Film, path: &Path, display: &DisplayTransform) -> std::io::Result<()> {
+    let mut tmp = PathBuf::from(path);
+    tmp.set_extension("png.tmp");
+    std::fs::write(&tmp, encode_png_with(film, display))?;
+    std::fs::rename(&tmp, path)
+}
+
+//image linéaire (sans correction gamma) et aovs du film dans un seul fichier EXR multi-calques
+pub fn save_exr(film: &Film, path: &Path) -> std::io::Result<()> {
+    let mut tmp = PathBuf::from(path);
+    tmp.set_extension("exr.tmp");
+    std::fs::write(&tmp, encode_exr(film))?;
+    std::fs::rename(&tmp, path)
+}
+
+pub fn encode_exr(film: &Film) -> Vec<u8> {
+    let (width, height) = (film.width(), film.height());
+    let pixels = || (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)));
+    let mut exr = Exr::new(width, height);
+    //les valeurs restent dans l'espace de travail du film, décrit par ses primaires
+    exr.chromaticities(film.space().chromaticities());
+    let beauty: Vec<Color> = pixels().map(|(x, y)| film.pixel(x, y)).collect();
+    exr.channel("R", beauty.iter().map(|c| c.red as f32).collect());
+    exr.channel("G", beauty.iter().map(|c| c.green as f32).collect());
+    exr.channel("B", beauty.iter().map(|c| c.blue as f32).collect());
+    for aov in film.aovs() {
+        let values: Vec<[f64; 3]> = pixels()
+            .map(|(x, y)| film.aov(*aov, x, y).unwrap())
+            .collect();
+        for (k, channel) in aov.channels().iter().enumerate() {
+            exr.channel(
+                &format!("{}.{}", aov.name(), channel),
+                values.iter().map(|v| v[k] as f32).collect(),
+            );
+        }
+    }
+    exr.encode()
+}
+
+pub fn encode_png(film: &Film) -> Vec<u8> {
+    encode_png_with(film, &DisplayTransform::default())
+}
+
+pub fn encode_png_with(film: &Film, display: &DisplayTransform) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity((film.width() * film.height() * 3) as usize);
+    for y in 0..film.height() {
+        for color in display_line(film, y, display, 255.99) {
+            let (red, green, blue) = color.scale(255.99);
+            rgb.extend_from_slice(&[red as u8, green as u8, blue as u8]);
+        }
+    }
+    Png::new(film.width(), film.height()).encode(&rgb)
+}
+
+//ligne y du film, convertie de l'espace de travail en sRGB puis transformée pour l'affichage
+fn display_line(film: &Film, y: u32, display: &DisplayTransform, levels: f64) -> Vec<Color> {
+    let space = film.space();
+    film.line(y)
+        .into_iter()
+        .enumerate()
+        .map(|(x, color)| display.pixel(space.to_srgb(color), x as u32, y, levels))
+        .collect()
+}
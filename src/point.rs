@@ -1,10 +1,19 @@
+use std::hash::{Hash, Hasher};
 use std::ops::{Add, Sub};
 
 use crate::vec::Vec3;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub struct Point3(pub f64, pub f64, pub f64);
 
+impl Hash for Point3 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+        self.1.to_bits().hash(state);
+        self.2.to_bits().hash(state);
+    }
+}
+
 impl Add<Vec3> for Point3 {
     type Output = Point3;
 
@@ -20,3 +29,16 @@ impl Sub<Vec3> for Point3 {
         self + -rhs
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translation() {
+        let Point3(x, y, z) = Point3(1., 2., 3.) + Vec3(1., -2., 0.5);
+        assert_eq!((x, y, z), (2., 0., 3.5));
+        let Point3(x, y, z) = Point3(1., 2., 3.) - Vec3(1., -2., 0.5);
+        assert_eq!((x, y, z), (0., 4., 2.5));
+    }
+}
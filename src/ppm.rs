@@ -1,5 +1,5 @@
 use std::io::Write;
-use crate::Color;
+use crate::color::Color;
 
 //fichier graphique de type bitmap textuel
 pub struct Ppm<T>{
@@ -51,4 +51,8 @@ impl<T: Write> Ppm<T>{
         }
         Ok(())
     }
+
+    pub fn into_inner(self) -> T {
+        self.writer
+    }
 }
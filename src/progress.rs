@@ -0,0 +1,135 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::stats::Stats;
+
+//état d'avancement transmis à l'observateur après chaque tuile
+//les échantillons sont comptés par pixel: une tuile de 32x32 à 16 spp compte pour 16384 échantillons
+#[derive(Clone, Debug)]
+pub struct RenderProgress {
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    pub samples_done: u64,
+    pub samples_total: u64,
+    //rayons lancés: rayons caméra et rayons secondaires
+    pub rays: u64,
+    pub elapsed: Duration,
+}
+
+impl RenderProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.samples_total == 0 {
+            1.
+        } else {
+            self.samples_done as f64 / self.samples_total as f64
+        }
+    }
+
+    //temps restant estimé en supposant une vitesse constante, inconnu tant que rien n'est rendu
+    pub fn eta(&self) -> Option<Duration> {
+        if self.samples_done == 0 {
+            return None;
+        }
+        let remaining = self.samples_total.saturating_sub(self.samples_done);
+        Some(
+            self.elapsed
+                .mul_f64(remaining as f64 / self.samples_done as f64),
+        )
+    }
+}
+
+//appelé par les workers, éventuellement depuis plusieurs threads à la fois
+pub trait Observer: Sync {
+    fn progress(&self, progress: &RenderProgress);
+}
+
+impl<F> Observer for F
+where
+    F: Fn(&RenderProgress) + Sync,
+{
+    fn progress(&self, progress: &RenderProgress) {
+        self(progress)
+    }
+}
+
+//jeton d'annulation partagé: les workers le consultent avant chaque tuile,
+//un rendu annulé se termine après les tuiles en cours et laisse un film partiel
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+//suivi d'un rendu qui peut s'étendre sur plusieurs passes
+pub struct Tracker<'a> {
+    observer: &'a dyn Observer,
+    cancel: CancelToken,
+    started: Instant,
+    progress: Mutex<(RenderProgress, Stats)>,
+}
+
+impl<'a> Tracker<'a> {
+    pub fn new(
+        observer: &'a dyn Observer,
+        cancel: &CancelToken,
+        tiles_total: usize,
+        samples_total: u64,
+    ) -> Tracker<'a> {
+        Tracker {
+            observer,
+            cancel: cancel.clone(),
+            started: Instant::now(),
+            progress: Mutex::new((
+                RenderProgress {
+                    tiles_done: 0,
+                    tiles_total,
+                    samples_done: 0,
+                    samples_total,
+                    rays: 0,
+                    elapsed: Duration::ZERO,
+                },
+                Stats::default(),
+            )),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    //samples: échantillons de la tuile, tous pixels confondus, stats: compteurs du thread pendant la tuile
+    pub fn tile_done(&self, samples: u64, stats: Stats) {
+        let progress = {
+            let mut guard = self.progress.lock().unwrap();
+            let (progress, total) = &mut *guard;
+            total.merge(&stats);
+            progress.tiles_done += 1;
+            progress.samples_done += samples;
+            progress.rays = total.rays();
+            progress.elapsed = self.started.elapsed();
+            progress.clone()
+        };
+        self.observer.progress(&progress);
+    }
+
+    pub fn progress(&self) -> RenderProgress {
+        self.progress.lock().unwrap().0.clone()
+    }
+
+    //statistiques fusionnées de toutes les tuiles terminées
+    pub fn stats(&self) -> Stats {
+        self.progress.lock().unwrap().1.clone()
+    }
+}
@@ -0,0 +1,93 @@
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ray::{Observer, RenderProgress};
+
+const WIDTH: usize = 30;
+const REFRESH: Duration = Duration::from_millis(200);
+
+//barre de progression sur stderr, seulement si c'est un terminal
+pub struct ProgressBar {
+    enabled: bool,
+    //dernier affichage, et si la barre est actuellement à l'écran
+    last_draw: Mutex<(Option<Instant>, bool)>,
+}
+
+impl ProgressBar {
+    pub fn new() -> ProgressBar {
+        ProgressBar {
+            enabled: std::io::stderr().is_terminal(),
+            last_draw: Mutex::new((None, false)),
+        }
+    }
+
+    //affiche un message sans qu'il soit mélangé à la barre, qui sera redessinée à la tuile suivante
+    pub fn println(&self, message: &str) {
+        let mut last_draw = self.last_draw.lock().unwrap();
+        if last_draw.1 {
+            eprint!("\r\x1b[K");
+            last_draw.1 = false;
+        }
+        println!("{}", message);
+    }
+
+    pub fn finish(&self) {
+        let mut last_draw = self.last_draw.lock().unwrap();
+        if last_draw.1 {
+            eprintln!();
+            last_draw.1 = false;
+        }
+    }
+}
+
+impl Observer for ProgressBar {
+    fn progress(&self, progress: &RenderProgress) {
+        if !self.enabled {
+            return;
+        }
+        let mut last_draw = self.last_draw.lock().unwrap();
+        let finished = progress.tiles_done == progress.tiles_total;
+        if !finished && last_draw.0.is_some_and(|last| last.elapsed() < REFRESH) {
+            return;
+        }
+        *last_draw = (Some(Instant::now()), true);
+
+        let filled = ((progress.fraction() * WIDTH as f64) as usize).min(WIDTH);
+        let seconds = progress.elapsed.as_secs_f64();
+        let eta = progress.eta().map_or_else(|| "?".to_string(), duration);
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "\r\x1b[K[{}{}] {:5.1}% {}/{} tiles, {:.1} Mrays ({:.2} Mrays/s), elapsed {}, eta {}",
+            "#".repeat(filled),
+            "-".repeat(WIDTH - filled),
+            progress.fraction() * 100.,
+            progress.tiles_done,
+            progress.tiles_total,
+            progress.rays as f64 / 1e6,
+            if seconds > 0. {
+                progress.rays as f64 / 1e6 / seconds
+            } else {
+                0.
+            },
+            duration(progress.elapsed),
+            eta
+        );
+        let _ = stderr.flush();
+    }
+}
+
+fn duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
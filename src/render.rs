@@ -0,0 +1,398 @@
+use std::ops::Mul;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::aov::{self, Aov, Path};
+use crate::bvh::Bvh;
+use crate::cam::Camera;
+use crate::color::Color;
+use crate::colorspace::ColorSpace;
+use crate::film::{Film, TileSamples};
+use crate::geometry::{Geometry, Intersect, Intersection};
+use crate::progress::{CancelToken, Observer, RenderProgress, Tracker};
+use crate::ray::Ray;
+use crate::sampler::{pixel_seed, random, reseed};
+use crate::spectrum::{self, Spectrum, Wavelengths};
+use crate::stats::{self, Stats};
+use crate::subsurface::Walk;
+use crate::tile::{spiral_tiles, Tile, WorkQueue};
+use crate::toon;
+
+pub struct Scene {
+    pub camera: Camera,
+    pub world: Bvh,
+}
+
+impl Scene {
+    pub fn new(camera: Camera, objects: Vec<Arc<Geometry>>) -> Scene {
+        Scene {
+            camera,
+            world: Bvh::new(objects),
+        }
+    }
+}
+
+pub struct Settings {
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: u32,
+    pub max_reflection: u16,
+    pub tile_size: u32,
+    pub threads: usize,
+    //graine du générateur: deux rendus de même graine donnent la même image
+    pub seed: u64,
+    //canaux à produire en plus de l'image
+    pub aovs: Vec<Aov>,
+    //espace de couleurs du rendu et du film
+    pub working_space: ColorSpace,
+    //transport de la lumière par longueurs d'onde plutôt qu'en rgb (cf spectrum)
+    pub spectral: bool,
+    //rendu stylisé: aplats et liseré au premier point touché, sans rebond (cf toon)
+    pub toon: bool,
+}
+
+impl Settings {
+    //réglages par défaut: 50 rebonds, tuiles de 32 pixels, un thread par coeur, graine 0, rendu rgb en sRGB
+    pub fn new(width: u32, height: u32, samples_per_pixel: u32) -> Settings {
+        Settings {
+            width,
+            height,
+            samples_per_pixel,
+            max_reflection: 50,
+            tile_size: 32,
+            threads: Settings::available_threads(),
+            seed: 0,
+            aovs: Vec::new(),
+            working_space: ColorSpace::Srgb,
+            spectral: false,
+            toon: false,
+        }
+    }
+
+    //nombre de threads par défaut: un par coeur disponible
+    pub fn available_threads() -> usize {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    }
+}
+
+//point d'entrée de la bibliothèque: rend toute l'image en une passe
+pub struct Renderer;
+
+impl Renderer {
+    pub fn render(scene: &Scene, settings: &Settings) -> Film {
+        Renderer::render_with(
+            scene,
+            settings,
+            &|_: &RenderProgress| {},
+            &CancelToken::new(),
+        )
+    }
+
+    //rendu suivi par un observateur et interruptible: après annulation, le film ne contient que les tuiles terminées
+    pub fn render_with(
+        scene: &Scene,
+        settings: &Settings,
+        observer: &dyn Observer,
+        cancel: &CancelToken,
+    ) -> Film {
+        Renderer::render_tracked(scene, settings, observer, cancel).0
+    }
+
+    //rendu complet accompagné des compteurs fusionnés de tous les threads
+    pub fn render_stats(scene: &Scene, settings: &Settings) -> (Film, Stats) {
+        Renderer::render_tracked(
+            scene,
+            settings,
+            &|_: &RenderProgress| {},
+            &CancelToken::new(),
+        )
+    }
+
+    fn render_tracked(
+        scene: &Scene,
+        settings: &Settings,
+        observer: &dyn Observer,
+        cancel: &CancelToken,
+    ) -> (Film, Stats) {
+        let tiles = spiral_tiles(settings.width, settings.height, settings.tile_size).len();
+        let samples =
+            settings.width as u64 * settings.height as u64 * settings.samples_per_pixel as u64;
+        let tracker = Tracker::new(observer, cancel, tiles, samples);
+        let film = Mutex::new(
+            Film::with_aovs(settings.width, settings.height, &settings.aovs)
+                .in_space(settings.working_space),
+        );
+        let started = Instant::now();
+        render_pass(
+            scene,
+            settings,
+            0,
+            settings.samples_per_pixel,
+            &film,
+            &tracker,
+        );
+        let mut stats = tracker.stats();
+        stats.add_time("render", started.elapsed());
+        (film.into_inner().unwrap(), stats)
+    }
+}
+
+pub fn ray_color(ray: &Ray, world: &dyn Intersect, rec_depth: u16) -> Color {
+    let mut path = Path {
+        color: Color::EMPTY,
+        bounces: 0,
+        primary: None,
+    };
+    radiance(ray, world, rec_depth, ColorSpace::Srgb, &mut path)
+}
+
+//suit le chemin d'un rayon caméra en gardant la première intersection et le nombre de rebonds, pour les aovs
+//les couleurs de la scène (sRGB) sont converties dans l'espace de travail des réglages
+pub fn trace(ray: &Ray, world: &dyn Intersect, settings: &Settings) -> Path {
+    let mut path = Path {
+        color: Color::EMPTY,
+        bounces: 0,
+        primary: None,
+    };
+    let (depth, space) = (settings.max_reflection, settings.working_space);
+    path.color = if settings.toon {
+        toon_radiance(ray, world, space, &mut path)
+    } else if settings.spectral {
+        let mut wavelengths = Wavelengths::sample();
+        let radiance = radiance_spectral(ray, world, depth, &mut wavelengths, &mut path);
+        space.from_srgb(wavelengths.to_srgb(radiance))
+    } else {
+        radiance(ray, world, depth, space, &mut path)
+    };
+    path
+}
+
+//le rendu stylisé remplace le transport de la lumière: le premier point touché est dessiné, le ciel reste le fond
+fn toon_radiance(ray: &Ray, world: &dyn Intersect, space: ColorSpace, path: &mut Path) -> Color {
+    stats::record_ray();
+    match world.intersect(ray, 0.001, f64::INFINITY) {
+        Some(hit) => {
+            let color = toon::shade(&hit, ray);
+            path.primary = Some(hit);
+            space.from_srgb(color)
+        }
+        None => space.from_srgb(sky(ray)),
+    }
+}
+
+//gradient de couleur (blanc..bleu) pour le fond si pas de HIT
+fn sky(ray: &Ray) -> Color {
+    const WHITE: Color = Color::new(1., 1., 1.);
+    const BLUE: Color = Color::new(0.5, 0.7, 1.0);
+
+    let t = 0.5 * (ray.direction.unit().y() + 1.);
+    WHITE * (1.0 - t) + BLUE * t
+}
+
+fn radiance(
+    ray: &Ray,
+    world: &dyn Intersect,
+    rec_depth: u16,
+    space: ColorSpace,
+    path: &mut Path,
+) -> Color {
+    const BLACK: Color = Color::new(0., 0., 0.);
+
+    //si le rayon a trop rebondi, il n'y a peu de lumière qui peut venir de cette direction -> noir
+    if rec_depth == 0 {
+        return BLACK;
+    }
+    stats::record_ray();
+
+    // 0.001 pour être sûr d'être > 0. car à cause de l'erreur d'echantillon, lors d'une reflection, le point de deépart peut se
+    // trouver legerement avant 0 (-0.000000000000000000001), et donc rebondir sur la surface intérieure de l'objet -> obscurcissement
+    // -> http://www.opengl-tutorial.org/intermediate-tutorials/tutorial-16-shadow-mapping/#shadow-acne
+    if let Some(hit) = world.intersect(ray, 0.001, f64::INFINITY) {
+        // le hit avec le materiau définit si il doit y avoir un rayon reflechi/refracté, et avec quelle attenuation
+        // l'attenuation est la couleur de l'objet 0 <= (r,g,b) <= 1
+        // un rayon secondaire est lancé depuis le hit point dans la direction du rayon réfléchi/refracté, etc...
+        // récursivité: chaque rayon réfl/refr peut frapper un autre objet et rebondir en fonction du matériau
+        //milieu traversé depuis l'origine du rayon: absorption (verre teinté dans la masse) ou
+        //marche aléatoire jusqu'à la surface (diffusion sous la surface)
+        let Some((hit, ray, transmittance)) =
+            random_walk(world, ray, hit, |hit, ray| hit.material.walk(hit, ray))
+        else {
+            return BLACK;
+        };
+        let scattered = hit.material.scatter(&hit, &ray);
+        if path.primary.is_none() {
+            path.primary = Some(hit);
+        }
+        if let Some(reflexion) = scattered {
+            path.bounces += 1;
+            // le nombre de rebonds va impacter la luminosité et la couleur
+            space.from_srgb(reflexion.attenuation * transmittance)
+                * radiance(&reflexion.reflected_ray, world, rec_depth - 1, space, path)
+        } else {
+            //absorption totale si HIT mais pas de rayon réfléchi/réfracté
+            BLACK
+        }
+    } else {
+        space.from_srgb(sky(ray))
+    }
+}
+
+//même parcours que radiance(), la lumière est transportée aux longueurs d'onde du chemin
+fn radiance_spectral(
+    ray: &Ray,
+    world: &dyn Intersect,
+    rec_depth: u16,
+    wavelengths: &mut Wavelengths,
+    path: &mut Path,
+) -> Spectrum {
+    if rec_depth == 0 {
+        return Spectrum::ZERO;
+    }
+    stats::record_ray();
+
+    if let Some(hit) = world.intersect(ray, 0.001, f64::INFINITY) {
+        let Some((hit, ray, transmittance)) = random_walk(world, ray, hit, |hit, ray| {
+            hit.material.walk_spectral(hit, ray, wavelengths)
+        }) else {
+            return Spectrum::ZERO;
+        };
+        let scattered = hit.material.scatter_spectral(&hit, &ray, wavelengths);
+        if path.primary.is_none() {
+            path.primary = Some(hit);
+        }
+        if let Some(reflexion) = scattered {
+            path.bounces += 1;
+            reflexion.attenuation
+                * transmittance
+                * radiance_spectral(
+                    &reflexion.reflected_ray,
+                    world,
+                    rec_depth - 1,
+                    wavelengths,
+                    path,
+                )
+        } else {
+            Spectrum::ZERO
+        }
+    } else {
+        //le ciel est une source de lumière: son spectre suit celui de D65
+        spectrum::illuminant(sky(ray), wavelengths)
+    }
+}
+
+//au delà, une marche aléatoire qui n'est pas ressortie de l'objet est abandonnée (son énergie est perdue)
+const MAX_WALK_STEPS: u32 = 1024;
+
+//suit les diffusions d'un rayon dans un milieu jusqu'à ce qu'il atteigne une surface. les pas de la marche ne
+//comptent pas dans les rebonds: retourne le point de surface, le rayon qui l'atteint et le poids accumulé
+fn random_walk<W: Copy + Mul<Output = W>>(
+    world: &dyn Intersect,
+    ray: &Ray,
+    hit: Intersection,
+    step: impl Fn(&Intersection, &Ray) -> Walk<W>,
+) -> Option<(Intersection, Ray, W)> {
+    let (mut hit, mut weight) = (hit, None::<W>);
+    let mut ray = Ray {
+        origin: ray.origin,
+        direction: ray.direction,
+    };
+    let accumulate = |weight: Option<W>, w: W| weight.map_or(w, |weight| weight * w);
+    for _ in 0..MAX_WALK_STEPS {
+        match step(&hit, &ray) {
+            Walk::Reached(w) => return Some((hit, ray, accumulate(weight, w))),
+            Walk::Scattered(next, w) => {
+                weight = Some(accumulate(weight, w));
+                stats::record_ray();
+                hit = world.intersect(&next, 0.001, f64::INFINITY)?;
+                ray = next;
+            }
+        }
+    }
+    None
+}
+
+//rend la passe numéro `pass`, de `samples` échantillons par pixel, sur toute l'image
+//chaque worker écrit directement ses tuiles dans le film partagé, et s'arrête si le rendu est annulé
+pub fn render_pass(
+    scene: &Scene,
+    settings: &Settings,
+    pass: u32,
+    samples: u32,
+    film: &Mutex<Film>,
+    tracker: &Tracker,
+) {
+    let tiles = spiral_tiles(settings.width, settings.height, settings.tile_size);
+    let queue = WorkQueue::new(tiles, settings.threads);
+
+    std::thread::scope(|scope| {
+        for worker in 0..settings.threads {
+            let queue = &queue;
+            scope.spawn(move || {
+                while !tracker.is_cancelled() {
+                    let Some(tile) = queue.next(worker) else {
+                        break;
+                    };
+                    let started = Instant::now();
+                    let result = render_tile(scene, settings, pass, samples, &tile);
+                    stats::record_time("tracing", started.elapsed());
+                    film.lock().unwrap().add_tile(&tile, &result, samples, pass);
+                    tracker.tile_done(tile.pixel_count() as u64 * samples as u64, stats::take());
+                }
+            });
+        }
+    });
+}
+
+//rend une tuile, retourne pour chaque pixel (ligne par ligne) la somme de ses échantillons et ses aovs
+pub fn render_tile(
+    scene: &Scene,
+    settings: &Settings,
+    pass: u32,
+    samples: u32,
+    tile: &Tile,
+) -> TileSamples {
+    let stride = aov::stride(&settings.aovs);
+    let mut colors = Vec::with_capacity(tile.pixel_count());
+    let mut aovs = Vec::with_capacity(tile.pixel_count() * stride);
+    let mut values = Vec::with_capacity(stride);
+    for (i, j) in tile.pixels() {
+        reseed(pixel_seed(settings.seed, pass, i, j));
+        //la ligne 0 est en haut de l'image alors que v=0 est en bas du viewport
+        let j = settings.height - 1 - j;
+        let mut color = Color::EMPTY;
+        let pixel = aovs.len();
+        aovs.resize(pixel + stride, 0.);
+        for sample in 0..samples {
+            let u = (i as f64 + random::<f64>()) / (settings.width as f64 - 1.);
+            let v = (j as f64 + random::<f64>()) / (settings.height as f64 - 1.);
+            let ray = scene.camera.ray(u, v);
+            let traced = stats::traced();
+            let path = trace(&ray, &scene.world, settings);
+            stats::record_path(stats::traced() - traced);
+            color = color + path.color;
+
+            values.clear();
+            for aov in &settings.aovs {
+                aov.push(&ray, &path, settings.working_space, &mut values);
+            }
+            let mut offset = pixel;
+            let mut values = values.iter();
+            for aov in &settings.aovs {
+                for (value, sum) in values
+                    .by_ref()
+                    .take(aov.channels().len())
+                    .zip(&mut aovs[offset..])
+                {
+                    if aov.averaged() {
+                        *sum += value;
+                    } else if sample == 0 {
+                        *sum = *value;
+                    }
+                }
+                offset += aov.channels().len();
+            }
+        }
+        colors.push(color);
+    }
+    TileSamples { colors, aovs }
+}
@@ -0,0 +1,115 @@
+use std::cell::RefCell;
+
+use rand::distributions::{Distribution, Standard};
+use rand::RngCore;
+
+//générateur pseudo-aléatoire xoshiro256** : rapide, et surtout son état est reproductible à partir d'une graine.
+//chaque thread a le sien, re-initialisé pour chaque pixel de chaque passe à partir de (graine, passe, pixel):
+//le rendu ne dépend donc pas de l'ordonnancement des threads, et l'état à sauvegarder se résume à la graine
+//et au nombre de passes effectuées
+#[derive(Clone)]
+pub struct Sampler {
+    state: [u64; 4],
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Sampler {
+        let mut seed = seed;
+        Sampler {
+            state: [
+                splitmix(&mut seed),
+                splitmix(&mut seed),
+                splitmix(&mut seed),
+                splitmix(&mut seed),
+            ],
+        }
+    }
+}
+
+impl RngCore for Sampler {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+fn splitmix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+//graine d'un pixel pour une passe donnée
+pub fn pixel_seed(seed: u64, pass: u32, x: u32, y: u32) -> u64 {
+    let mut state = seed ^ ((pass as u64) << 32 | 0x5bd1_e995);
+    let pass_seed = splitmix(&mut state);
+    let mut state = pass_seed ^ ((x as u64) << 32 | y as u64);
+    splitmix(&mut state)
+}
+
+thread_local! {
+    static SAMPLER: RefCell<Sampler> = RefCell::new(Sampler::new(rand::random()));
+}
+
+//re-initialise le générateur du thread courant
+pub fn reseed(seed: u64) {
+    SAMPLER.with(|sampler| *sampler.borrow_mut() = Sampler::new(seed));
+}
+
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    SAMPLER.with(|sampler| Standard.sample(&mut *sampler.borrow_mut()))
+}
+
+//accès au générateur du thread courant, à la manière de rand::thread_rng()
+pub fn rng() -> ThreadSampler {
+    ThreadSampler
+}
+
+pub struct ThreadSampler;
+
+impl RngCore for ThreadSampler {
+    fn next_u32(&mut self) -> u32 {
+        SAMPLER.with(|sampler| sampler.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        SAMPLER.with(|sampler| sampler.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        SAMPLER.with(|sampler| sampler.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        SAMPLER.with(|sampler| sampler.borrow_mut().try_fill_bytes(dest))
+    }
+}
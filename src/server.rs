@@ -0,0 +1,121 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::film::Film;
+use crate::output::encode_png_with;
+use crate::tonemap::DisplayTransform;
+
+//délai accordé à un client pour envoyer sa requête: un client lent ne bloque pas le serveur, qui n'a qu'un thread
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+//avancement d'un rendu progressif, partagé entre les workers et le serveur d'aperçu
+pub struct Progress {
+    pub pass: u32,
+    pub passes: u32,
+    pub samples_per_pixel: u32,
+    pub target_samples_per_pixel: u32,
+    pub started: Instant,
+}
+
+impl Progress {
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"pass\":{},\"passes\":{},\"samples_per_pixel\":{},\"target_samples_per_pixel\":{},\"elapsed_seconds\":{:.3},\"done\":{}}}",
+            self.pass,
+            self.passes,
+            self.samples_per_pixel,
+            self.target_samples_per_pixel,
+            self.started.elapsed().as_secs_f64(),
+            self.samples_per_pixel >= self.target_samples_per_pixel
+        )
+    }
+}
+
+//page de visualisation: recharge l'image et l'avancement toutes les 2 secondes
+const INDEX: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>ray</title></head>
+<body style="background:#222;color:#ddd;font-family:monospace">
+<div id="progress">...</div>
+<img id="frame" src="/frame.png" style="max-width:100%">
+<script>
+setInterval(function () {
+  document.getElementById("frame").src = "/frame.png?" + Date.now();
+  fetch("/progress").then(function (r) { return r.json(); }).then(function (p) {
+    document.getElementById("progress").textContent =
+      "pass " + p.pass + "/" + p.passes + " - " + p.samples_per_pixel + "/" +
+      p.target_samples_per_pixel + " spp - " + p.elapsed_seconds.toFixed(0) + "s";
+  });
+}, 2000);
+</script>
+</body>
+</html>
+"#;
+
+//mini serveur HTTP sur localhost: /frame.png (dernière image moyenne), /progress (json) et / (visionneuse).
+//retourne le port d'écoute, choisi par le système quand `port` vaut 0
+pub fn serve(
+    port: u16,
+    film: Arc<Mutex<Film>>,
+    progress: Arc<Mutex<Progress>>,
+    display: DisplayTransform,
+) -> std::io::Result<u16> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let port = listener.local_addr()?.port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            //une requête en échec ne doit pas arrêter le serveur
+            let _ = respond(stream, &film, &progress, &display);
+        }
+    });
+    Ok(port)
+}
+
+fn respond(
+    stream: TcpStream,
+    film: &Mutex<Film>,
+    progress: &Mutex<Progress>,
+    display: &DisplayTransform,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    //on consomme les entêtes jusqu'à la ligne vide
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("/").split('?').next().unwrap_or("/");
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/") => ("200 OK", "text/html", INDEX.as_bytes().to_vec()),
+        ("GET", "/frame.png") => {
+            //copie du film pour ne pas bloquer les workers pendant l'encodage
+            let film = film.lock().unwrap().clone();
+            ("200 OK", "image/png", encode_png_with(&film, display))
+        }
+        ("GET", "/progress") => (
+            "200 OK",
+            "application/json",
+            progress.lock().unwrap().to_json().into_bytes(),
+        ),
+        _ => ("404 Not Found", "text/plain", b"not found".to_vec()),
+    };
+
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
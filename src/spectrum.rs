@@ -0,0 +1,308 @@
+use std::ops::{Add, Mul};
+use std::sync::OnceLock;
+
+use crate::color::Color;
+use crate::colorspace::{invert, multiply, Matrix};
+use crate::sampler::random;
+
+//rendu spectral par longueur d'onde "héros" (Wilkie et al. 2014): chaque chemin transporte
+//WAVELENGTHS longueurs d'onde régulièrement espacées sur le visible, décalées d'un tirage aléatoire.
+//les couleurs rgb de la scène sont converties en spectres (réflectances et ciel), la radiance
+//de chaque longueur d'onde est projetée sur les fonctions colorimétriques CIE 1931 pour obtenir du XYZ
+
+pub const LAMBDA_MIN: f64 = 380.;
+pub const LAMBDA_MAX: f64 = 720.;
+pub const WAVELENGTHS: usize = 4;
+
+//valeur par longueur d'onde d'un chemin
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Spectrum(pub [f64; WAVELENGTHS]);
+
+impl Spectrum {
+    pub const ZERO: Spectrum = Spectrum([0.; WAVELENGTHS]);
+    pub const ONE: Spectrum = Spectrum([1.; WAVELENGTHS]);
+
+    pub fn map<F: Fn(f64) -> f64>(self, f: F) -> Spectrum {
+        Spectrum(self.0.map(f))
+    }
+}
+
+impl Mul for Spectrum {
+    type Output = Spectrum;
+
+    fn mul(self, rhs: Spectrum) -> Spectrum {
+        Spectrum(std::array::from_fn(|i| self.0[i] * rhs.0[i]))
+    }
+}
+
+impl Mul<f64> for Spectrum {
+    type Output = Spectrum;
+
+    fn mul(self, rhs: f64) -> Spectrum {
+        self.map(|v| v * rhs)
+    }
+}
+
+impl Add for Spectrum {
+    type Output = Spectrum;
+
+    fn add(self, rhs: Spectrum) -> Spectrum {
+        Spectrum(std::array::from_fn(|i| self.0[i] + rhs.0[i]))
+    }
+}
+
+//longueurs d'onde (nm) d'un chemin, la première est le héros
+#[derive(Copy, Clone, Debug)]
+pub struct Wavelengths {
+    pub lambda: [f64; WAVELENGTHS],
+    //une longueur d'onde dont le chemin a divergé de celui du héros (dispersion) ne contribue plus
+    pub terminated: bool,
+}
+
+impl Wavelengths {
+    //tirage uniforme du héros, les autres suivent à intervalles réguliers (modulo le domaine)
+    pub fn sample() -> Wavelengths {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = random::<f64>();
+        Wavelengths {
+            lambda: std::array::from_fn(|i| {
+                let offset = (hero + i as f64 / WAVELENGTHS as f64).fract();
+                LAMBDA_MIN + offset * range
+            }),
+            terminated: false,
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    //garde le seul héros, pondéré pour rester sans biais (cf radiance())
+    pub fn terminate_secondary(&mut self) {
+        self.terminated = true;
+    }
+
+    pub fn map<F: Fn(f64) -> f64>(&self, f: F) -> Spectrum {
+        Spectrum(self.lambda.map(f))
+    }
+
+    //estimation XYZ d'un échantillon: moyenne des longueurs d'onde divisée par la densité uniforme
+    //normalisée pour qu'un ciel blanc (D65) ait une luminance Y de 1
+    pub fn to_xyz(&self, radiance: Spectrum) -> [f64; 3] {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let (count, weight) = if self.terminated {
+            (1, 1.)
+        } else {
+            (WAVELENGTHS, 1. / WAVELENGTHS as f64)
+        };
+        let mut xyz = [0.; 3];
+        for (lambda, value) in self.lambda.iter().zip(radiance.0).take(count) {
+            let cmf = cmf(*lambda);
+            for (sum, c) in xyz.iter_mut().zip(cmf) {
+                *sum += value * c * range * weight;
+            }
+        }
+        xyz.map(|v| v / tables().y_d65)
+    }
+
+    //couleur sRGB linéaire d'un échantillon spectral
+    pub fn to_srgb(&self, radiance: Spectrum) -> Color {
+        let [x, y, z] = self.to_xyz(radiance);
+        multiply(&XYZ_TO_SRGB, Color::new(x, y, z))
+    }
+}
+
+const XYZ_TO_SRGB: Matrix = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+//fonctions colorimétriques CIE 1931 (observateur 2°), approximation analytique multi-lobes de Wyman, Sloan et Shirley (2013)
+pub fn cmf(lambda: f64) -> [f64; 3] {
+    let g = |mu: f64, low: f64, high: f64| {
+        let sigma = if lambda < mu { low } else { high };
+        (-0.5 * ((lambda - mu) / sigma).powi(2)).exp()
+    };
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+//distribution spectrale relative de l'illuminant D65, de 380 à 720 nm par pas de 10 nm
+const D65: [f64; 35] = [
+    49.98, 54.65, 82.75, 91.49, 93.43, 86.68, 104.86, 117.01, 117.81, 114.86, 115.92, 108.81,
+    109.35, 107.80, 104.79, 107.69, 104.41, 104.05, 100.00, 96.33, 95.79, 88.69, 90.01, 89.60,
+    87.70, 83.29, 83.70, 80.03, 80.21, 82.28, 78.28, 69.72, 71.61, 74.35, 61.60,
+];
+
+pub fn d65(lambda: f64) -> f64 {
+    let position = ((lambda - LAMBDA_MIN) / 10.).clamp(0., (D65.len() - 1) as f64);
+    let i = (position as usize).min(D65.len() - 2);
+    let t = position - i as f64;
+    D65[i] * (1. - t) + D65[i + 1] * t
+}
+
+//conversion rgb -> spectre: combinaison de trois spectres de base lisses (bleu, vert, rouge) dont la somme vaut 1
+//partout, si bien que le blanc donne un spectre plat. les coefficients sont obtenus par l'inverse de la matrice
+//qui donne la couleur sRGB de chaque spectre de base éclairé par D65: la couleur est conservée aux erreurs
+//d'intégration près, tant que les coefficients restent dans [0, 1] (couleurs pas trop saturées)
+fn basis(lambda: f64) -> [f64; 3] {
+    let sigmoid = |x: f64| 1. / (1. + (-x).exp());
+    let blue_green = sigmoid((lambda - 490.) / 12.);
+    let green_red = sigmoid((lambda - 585.) / 12.);
+    [green_red, blue_green - green_red, 1. - blue_green]
+}
+
+struct Tables {
+    //intégrale de D65 * ȳ sur le domaine
+    y_d65: f64,
+    //coefficients des spectres de base (rouge, vert, bleu) en fonction de la couleur sRGB
+    rgb_to_basis: Matrix,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        //intégration par pas de 1 nm
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let lambdas = (0..steps).map(|i| LAMBDA_MIN + i as f64 + 0.5);
+        let y_d65: f64 = lambdas.clone().map(|l| d65(l) * cmf(l)[1]).sum();
+        let mut basis_xyz = [[0.; 3]; 3];
+        for lambda in lambdas {
+            let (cmf, basis, d65) = (cmf(lambda), basis(lambda), d65(lambda));
+            for (row, c) in basis_xyz.iter_mut().zip(cmf) {
+                for (value, b) in row.iter_mut().zip(basis) {
+                    *value += c * b * d65 / y_d65;
+                }
+            }
+        }
+        //colonnes: couleur sRGB de chacun des spectres de base
+        let mut basis_rgb = [[0.; 3]; 3];
+        for j in 0..3 {
+            let column = multiply(
+                &XYZ_TO_SRGB,
+                Color::new(basis_xyz[0][j], basis_xyz[1][j], basis_xyz[2][j]),
+            );
+            basis_rgb[0][j] = column.red;
+            basis_rgb[1][j] = column.green;
+            basis_rgb[2][j] = column.blue;
+        }
+        Tables {
+            y_d65,
+            rgb_to_basis: invert(&basis_rgb),
+        }
+    })
+}
+
+//réflectance (albedo, atténuation) rgb -> valeurs aux longueurs d'onde, bornées à [0, 1]
+pub fn reflectance(color: Color, wavelengths: &Wavelengths) -> Spectrum {
+    let c = multiply(&tables().rgb_to_basis, color);
+    wavelengths.map(|lambda| {
+        let [r, g, b] = basis(lambda);
+        (c.red * r + c.green * g + c.blue * b).clamp(0., 1.)
+    })
+}
+
+//émission rgb -> radiance spectrale: la réflectance équivalente éclairée par D65
+pub fn illuminant(color: Color, wavelengths: &Wavelengths) -> Spectrum {
+    let c = multiply(&tables().rgb_to_basis, color);
+    wavelengths.map(|lambda| {
+        let [r, g, b] = basis(lambda);
+        (c.red * r + c.green * g + c.blue * b).max(0.) * d65(lambda)
+    })
+}
+
+//couleur sRGB linéaire d'une réflectance spectrale éclairée par D65 (ex: métaux définis par leurs indices)
+pub fn reflectance_to_srgb<F: Fn(f64) -> f64>(reflectance: F) -> Color {
+    reflectance_to_srgb_sampled((LAMBDA_MAX - LAMBDA_MIN) as usize, reflectance)
+}
+
+//même intégration sur `steps` intervalles seulement, pour les réflectances évaluées à chaque rebond
+pub fn reflectance_to_srgb_sampled<F: Fn(f64) -> f64>(steps: usize, reflectance: F) -> Color {
+    let tables = tables();
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+    let mut xyz = [0.; 3];
+    for i in 0..steps {
+        let lambda = LAMBDA_MIN + (i as f64 + 0.5) * step;
+        let value = reflectance(lambda) * d65(lambda) * step / tables.y_d65;
+        for (sum, c) in xyz.iter_mut().zip(cmf(lambda)) {
+            *sum += value * c;
+        }
+    }
+    multiply(&XYZ_TO_SRGB, Color::new(xyz[0], xyz[1], xyz[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wavelengths(hero: f64) -> Wavelengths {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        Wavelengths {
+            lambda: std::array::from_fn(|i| {
+                LAMBDA_MIN + ((hero + i as f64 / WAVELENGTHS as f64).fract()) * range
+            }),
+            terminated: false,
+        }
+    }
+
+    //moyenne des estimations sur des héros régulièrement répartis: approxime l'intégrale sur le visible
+    fn integrate<F: Fn(&Wavelengths) -> Spectrum>(f: F) -> Color {
+        let n = 500;
+        let mut sum = Color::EMPTY;
+        for i in 0..n {
+            let w = wavelengths((i as f64 + 0.5) / n as f64);
+            sum = sum + w.to_srgb(f(&w));
+        }
+        sum / n as f64
+    }
+
+    fn close(a: Color, b: Color, epsilon: f64) -> bool {
+        (a.red - b.red).abs() < epsilon
+            && (a.green - b.green).abs() < epsilon
+            && (a.blue - b.blue).abs() < epsilon
+    }
+
+    #[test]
+    fn white_sky_stays_white() {
+        let white = Color::new(1., 1., 1.);
+        let sky = integrate(|w| illuminant(white, w));
+        assert!(close(sky, white, 0.01), "{:?}", sky);
+    }
+
+    #[test]
+    fn upsampling_preserves_moderate_colours() {
+        let white = Color::new(1., 1., 1.);
+        for color in [
+            Color::new(0.5, 0.5, 0.5),
+            Color::new(0.7, 0.6, 0.5),
+            Color::new(0.2, 0.4, 0.6),
+            Color::new(0.5, 0.7, 1.0),
+        ] {
+            let lit = integrate(|w| reflectance(color, w) * illuminant(white, w));
+            assert!(close(lit, color, 0.02), "{:?} -> {:?}", color, lit);
+            let emitted = integrate(|w| illuminant(color, w));
+            assert!(close(emitted, color, 0.02), "{:?} -> {:?}", color, emitted);
+        }
+        //une réflectance reste physique même pour une couleur saturée
+        let w = wavelengths(0.3);
+        for v in reflectance(Color::new(1., 0., 0.), &w).0 {
+            assert!((0. ..=1.).contains(&v));
+        }
+    }
+
+    #[test]
+    fn wavelengths_cover_the_visible_range() {
+        for _ in 0..100 {
+            let w = Wavelengths::sample();
+            for lambda in w.lambda {
+                assert!((LAMBDA_MIN..LAMBDA_MAX).contains(&lambda));
+            }
+            let gap = (w.lambda[1] - w.lambda[0]).rem_euclid(LAMBDA_MAX - LAMBDA_MIN);
+            assert!((gap - (LAMBDA_MAX - LAMBDA_MIN) / WAVELENGTHS as f64).abs() < 1e-9);
+        }
+    }
+}
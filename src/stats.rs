@@ -0,0 +1,155 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::time::Duration;
+
+//compteurs d'un rendu: chaque thread incrémente les siens sans synchronisation,
+//ils sont retirés après chaque tuile et fusionnés dans les statistiques du rendu
+#[derive(Clone, Default, Debug)]
+pub struct Stats {
+    pub camera_rays: u64,
+    //rayons réfléchis, réfractés ou diffusés
+    pub secondary_rays: u64,
+    //intersections exactes calculées avec un objet
+    pub primitive_tests: u64,
+    //noeuds et feuilles du bvh visités
+    pub node_visits: u64,
+    //path_lengths[n]: nombre de chemins ayant rebondi n fois
+    pub path_lengths: Vec<u64>,
+    //temps par phase, dans l'ordre de première apparition; "tracing" cumule le temps de tous les threads
+    pub phases: Vec<(String, Duration)>,
+}
+
+thread_local! {
+    static STATS: RefCell<Stats> = RefCell::new(Stats::default());
+    //rayons lancés par le thread, pour mesurer la longueur de chaque chemin
+    static TRACED: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn record_ray() {
+    TRACED.with(|traced| traced.set(traced.get() + 1));
+}
+
+pub fn traced() -> u64 {
+    TRACED.with(Cell::get)
+}
+
+//rays: nombre de rayons lancés pour un échantillon, rayon caméra compris
+pub fn record_path(rays: u64) {
+    STATS.with(|stats| {
+        let mut stats = stats.borrow_mut();
+        stats.camera_rays += 1;
+        if rays > 0 {
+            let bounces = (rays - 1) as usize;
+            stats.secondary_rays += rays - 1;
+            if stats.path_lengths.len() <= bounces {
+                stats.path_lengths.resize(bounces + 1, 0);
+            }
+            stats.path_lengths[bounces] += 1;
+        }
+    });
+}
+
+pub fn record_traversal(node_visits: u64, primitive_tests: u64) {
+    STATS.with(|stats| {
+        let mut stats = stats.borrow_mut();
+        stats.node_visits += node_visits;
+        stats.primitive_tests += primitive_tests;
+    });
+}
+
+pub fn record_time(phase: &str, duration: Duration) {
+    STATS.with(|stats| stats.borrow_mut().add_time(phase, duration));
+}
+
+//retire les compteurs du thread courant
+pub fn take() -> Stats {
+    STATS.with(|stats| std::mem::take(&mut *stats.borrow_mut()))
+}
+
+impl Stats {
+    pub fn rays(&self) -> u64 {
+        self.camera_rays + self.secondary_rays
+    }
+
+    pub fn add_time(&mut self, phase: &str, duration: Duration) {
+        match self.phases.iter_mut().find(|(name, _)| name == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.phases.push((phase.to_string(), duration)),
+        }
+    }
+
+    pub fn merge(&mut self, other: &Stats) {
+        self.camera_rays += other.camera_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.primitive_tests += other.primitive_tests;
+        self.node_visits += other.node_visits;
+        if self.path_lengths.len() < other.path_lengths.len() {
+            self.path_lengths.resize(other.path_lengths.len(), 0);
+        }
+        for (total, count) in self.path_lengths.iter_mut().zip(&other.path_lengths) {
+            *total += count;
+        }
+        for (phase, duration) in &other.phases {
+            self.add_time(phase, *duration);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let path_lengths: Vec<String> = self.path_lengths.iter().map(u64::to_string).collect();
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .map(|(phase, duration)| format!("\"{}\":{:.6}", phase, duration.as_secs_f64()))
+            .collect();
+        format!(
+            "{{\"camera_rays\":{},\"secondary_rays\":{},\"primitive_tests\":{},\"node_visits\":{},\"path_lengths\":[{}],\"phase_seconds\":{{{}}}}}",
+            self.camera_rays,
+            self.secondary_rays,
+            self.primitive_tests,
+            self.node_visits,
+            path_lengths.join(","),
+            phases.join(",")
+        )
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per_ray = |count: u64| match self.rays() {
+            0 => 0.,
+            rays => count as f64 / rays as f64,
+        };
+        writeln!(f, "camera rays      {:>14}", self.camera_rays)?;
+        writeln!(f, "secondary rays   {:>14}", self.secondary_rays)?;
+        writeln!(
+            f,
+            "primitive tests  {:>14} ({:.1} per ray)",
+            self.primitive_tests,
+            per_ray(self.primitive_tests)
+        )?;
+        writeln!(
+            f,
+            "node visits      {:>14} ({:.1} per ray)",
+            self.node_visits,
+            per_ray(self.node_visits)
+        )?;
+        writeln!(f, "path lengths (bounces: paths)")?;
+        let paths: u64 = self.path_lengths.iter().sum();
+        for (bounces, count) in self.path_lengths.iter().enumerate() {
+            if *count > 0 {
+                writeln!(
+                    f,
+                    "  {:>3}: {:>14} {:5.1}%",
+                    bounces,
+                    count,
+                    *count as f64 * 100. / paths as f64
+                )?;
+            }
+        }
+        writeln!(f, "time per phase")?;
+        for (phase, duration) in &self.phases {
+            writeln!(f, "  {:<14} {:>10.3}s", phase, duration.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
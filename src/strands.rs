@@ -0,0 +1,282 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::curve::{curve, CurveShape};
+use crate::geometry::Geometry;
+use crate::material::GenericMaterial;
+use crate::point::Point3;
+use crate::vec::Vec3;
+
+//brins (cheveux, fourrure) décrits par une suite de sommets et l'épaisseur en chaque sommet.
+//deux formats de fichier:
+// - texte: un sommet "x y z épaisseur" par ligne, une ligne vide entre deux brins, # pour les commentaires
+// - binaire .hair (Cem Yuksel): en-tête de 128 octets commençant par "HAIR", puis les tableaux annoncés par l'en-tête
+const MAGIC: &[u8; 4] = b"HAIR";
+const HEADER_SIZE: usize = 128;
+const HAS_SEGMENTS: u32 = 1;
+const HAS_POINTS: u32 = 1 << 1;
+const HAS_THICKNESS: u32 = 1 << 2;
+
+#[derive(Clone, Debug)]
+pub struct Strand {
+    pub points: Vec<Point3>,
+    pub widths: Vec<f64>,
+}
+
+pub fn load(path: &Path) -> std::io::Result<Vec<Strand>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(MAGIC) {
+        read_hair(&mut reader)
+    } else {
+        read_text(reader)
+    }
+}
+
+pub fn read_text<R: BufRead>(reader: R) -> std::io::Result<Vec<Strand>> {
+    let mut strands = Vec::new();
+    let mut strand = Strand {
+        points: Vec::new(),
+        widths: Vec::new(),
+    };
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            if !strand.points.is_empty() {
+                strands.push(std::mem::replace(
+                    &mut strand,
+                    Strand {
+                        points: Vec::new(),
+                        widths: Vec::new(),
+                    },
+                ));
+            }
+            continue;
+        }
+        let values = line
+            .split_whitespace()
+            .map(str::parse::<f64>)
+            .collect::<Result<Vec<_>, _>>();
+        match values.as_deref() {
+            Ok(&[x, y, z, width]) => {
+                strand.points.push(Point3(x, y, z));
+                strand.widths.push(width);
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("line {}: expected \"x y z width\"", number + 1),
+                ))
+            }
+        }
+    }
+    if !strand.points.is_empty() {
+        strands.push(strand);
+    }
+    Ok(strands)
+}
+
+pub fn read_hair<R: Read>(reader: &mut R) -> std::io::Result<Vec<Strand>> {
+    let mut header = [0; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "not a .hair file"));
+    }
+    let u32_at =
+        |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+    let (count, point_count, flags) = (u32_at(4) as usize, u32_at(8) as usize, u32_at(12));
+    let default_segments = u32_at(16) as usize;
+    let default_thickness = f32::from_bits(u32_at(20)) as f64;
+    if flags & HAS_POINTS == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            ".hair file without points",
+        ));
+    }
+
+    //les tableaux ne sont alloués qu'au fur et à mesure de leur lecture: un en-tête corrompu annonçant des
+    //millions de sommets échoue sur la fin du fichier au lieu d'allouer toute la mémoire
+    let segments: Vec<usize> = if flags & HAS_SEGMENTS != 0 {
+        read_bytes(reader, 2 * count)?
+            .chunks(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let points = read_f32s(reader, 3 * point_count)?;
+    let thickness = if flags & HAS_THICKNESS != 0 {
+        read_f32s(reader, point_count)?
+    } else {
+        vec![default_thickness; point_count]
+    };
+    //la transparence et la couleur par sommet, qui suivent, ne sont pas utilisées.
+    //chaque brin consomme au moins un sommet: la boucle s'arrête sur une erreur bien avant `count` si l'en-tête ment
+    let mut strands = Vec::new();
+    let mut first = 0;
+    for i in 0..count {
+        let segments = segments.get(i).copied().unwrap_or(default_segments);
+        let last = first + segments + 1;
+        if last > point_count {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "more segments than points in .hair file",
+            ));
+        }
+        strands.push(Strand {
+            points: (first..last)
+                .map(|i| Point3(points[3 * i], points[3 * i + 1], points[3 * i + 2]))
+                .collect(),
+            widths: thickness[first..last].to_vec(),
+        });
+        first = last;
+    }
+    Ok(strands)
+}
+
+//lit exactement `count` octets
+fn read_bytes<R: Read>(reader: &mut R, count: usize) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(count as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != count {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "truncated .hair file"));
+    }
+    Ok(bytes)
+}
+
+fn read_f32s<R: Read>(reader: &mut R, count: usize) -> std::io::Result<Vec<f64>> {
+    Ok(read_bytes(reader, 4 * count)?
+        .chunks(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+        .collect())
+}
+
+//une courbe de Bézier par segment de chaque brin: la spline de Catmull-Rom qui passe par les sommets,
+//l'épaisseur varie linéairement d'un sommet au suivant. chaque sommet doit avoir son épaisseur
+pub fn curves(
+    strands: &[Strand],
+    shape: CurveShape,
+    material: &GenericMaterial,
+) -> std::io::Result<Vec<Arc<Geometry>>> {
+    let mut curves = Vec::new();
+    for (index, strand) in strands.iter().enumerate() {
+        if strand.widths.len() != strand.points.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "strand {}: {} points but {} widths",
+                    index,
+                    strand.points.len(),
+                    strand.widths.len()
+                ),
+            ));
+        }
+        let points = &strand.points;
+        let n = points.len();
+        for i in 0..n.saturating_sub(1) {
+            let previous = points[i.saturating_sub(1)];
+            let next = points[(i + 2).min(n - 1)];
+            let (start, end) = (points[i], points[i + 1]);
+            let control = [
+                start,
+                start + Vec3::points(previous, end) / 6.,
+                end - Vec3::points(start, next) / 6.,
+                end,
+            ];
+            curves.push(Arc::new(curve(
+                control,
+                [strand.widths[i], strand.widths[i + 1]],
+                shape,
+                material.clone(),
+            )));
+        }
+    }
+    Ok(curves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::diffuse;
+    use std::io::Cursor;
+
+    fn xyz(p: Point3) -> (f64, f64, f64) {
+        (p.0, p.1, p.2)
+    }
+
+    #[test]
+    fn reads_text_strands() {
+        let text = "# deux brins\n0 0 0 0.1\n0 1 0 0.05\n0 2 0.5 0.01\n\n\n1 0 0 0.2 # racine\n1 1 0 0.1\n";
+        let strands = read_text(Cursor::new(text)).unwrap();
+        assert_eq!(strands.len(), 2);
+        assert_eq!(strands[0].points.len(), 3);
+        assert_eq!(xyz(strands[1].points[0]), (1., 0., 0.));
+        assert_eq!(strands[1].widths, vec![0.2, 0.1]);
+        assert!(read_text(Cursor::new("0 0 0\n")).is_err());
+        assert_eq!(
+            curves(&strands, CurveShape::Flat, &diffuse(0.5, 0.5, 0.5))
+                .unwrap()
+                .len(),
+            3
+        );
+        let mut uneven = strands[0].clone();
+        uneven.widths.pop();
+        assert!(curves(&[uneven], CurveShape::Flat, &diffuse(0.5, 0.5, 0.5)).is_err());
+    }
+
+    #[test]
+    fn reads_hair_files() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        for value in [2u32, 5, HAS_SEGMENTS | HAS_POINTS | 1 << 4, 0] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(0.02f32.to_le_bytes());
+        bytes.resize(HEADER_SIZE, 0);
+        for segments in [2u16, 1] {
+            bytes.extend(segments.to_le_bytes());
+        }
+        for i in 0..15 {
+            bytes.extend((i as f32).to_le_bytes());
+        }
+        //couleurs, ignorées
+        bytes.extend(vec![0; 4 * 15]);
+        let strands = read_hair(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(strands.len(), 2);
+        assert_eq!(strands[0].points.len(), 3);
+        assert_eq!(xyz(strands[1].points[1]), (12., 13., 14.));
+        assert!((strands[1].widths[0] - 0.02).abs() < 1e-9);
+
+        //plus de segments que de sommets
+        bytes[HEADER_SIZE] = 4;
+        assert!(read_hair(&mut Cursor::new(&bytes)).is_err());
+        //en-tête annonçant des milliards de brins et de sommets: erreur, sans tout allouer
+        for offset in [4, 8] {
+            let mut corrupt = bytes.clone();
+            corrupt[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            assert!(read_hair(&mut Cursor::new(&corrupt)).is_err());
+        }
+    }
+
+    #[test]
+    fn curves_pass_through_the_vertices() {
+        let strand = Strand {
+            points: vec![Point3(0., 0., 0.), Point3(1., 1., 0.), Point3(2., 0., 0.)],
+            widths: vec![0.1, 0.1, 0.1],
+        };
+        let curves = curves(&[strand], CurveShape::Cylinder, &diffuse(0.5, 0.5, 0.5)).unwrap();
+        let control = |i: usize| match &*curves[i] {
+            Geometry::Curve(curve) => curve.control,
+            _ => unreachable!(),
+        };
+        assert_eq!(xyz(control(0)[3]), (1., 1., 0.));
+        assert_eq!(xyz(control(1)[0]), (1., 1., 0.));
+        //tangente continue au sommet intérieur: horizontale, comme la corde des sommets voisins
+        let before = Vec3::points(control(0)[2], control(0)[3]);
+        let after = Vec3::points(control(1)[0], control(1)[1]);
+        assert!(before.1.abs() < 1e-12 && after.1.abs() < 1e-12);
+        assert!((before.0 - after.0).abs() < 1e-12);
+    }
+}
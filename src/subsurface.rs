@@ -0,0 +1,134 @@
+use std::hash::{Hash, Hasher};
+
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::sampler::random;
+
+//diffusion sous la surface par marche aléatoire (Chiang et al., "Practical and controllable subsurface scattering", 2016):
+//sous la surface, la lumière parcourt des distances tirées selon le coefficient d'extinction, puis est
+//diffusée dans une direction quelconque (fonction de phase isotrope) jusqu'à ressortir de l'objet fermé.
+//les distances sont dans l'unité de la scène
+#[derive(Copy, Clone, Debug)]
+pub struct Subsurface {
+    //coefficient d'extinction σt = 1 / libre parcours moyen, par canal
+    pub extinction: Color,
+    //albedo de diffusion simple σs / σt, par canal
+    pub albedo: Color,
+    pub boundary: Boundary,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Boundary {
+    //interface lisse d'indice donné: réflexion et réfraction de Fresnel à l'entrée comme à la sortie
+    Refractive(f64),
+    //interface rugueuse: le rayon traverse dans une direction diffuse, sans réflexion
+    Diffuse,
+}
+
+//issue d'un segment de la marche, le long d'un rayon qui se propage dans le milieu
+pub enum Walk<W> {
+    //le rayon est diffusé avant d'atteindre la surface: nouveau rayon et poids du segment
+    Scattered(Ray, W),
+    //le rayon atteint la surface avec ce poids
+    Reached(W),
+}
+
+impl Subsurface {
+    //`albedo`: couleur apparente de l'objet épais (albedo multiple), `mean_free_path`: distance moyenne
+    //entre deux diffusions, par canal. plus elle est grande devant l'objet, plus il paraît translucide
+    pub fn new(albedo: Color, mean_free_path: Color, boundary: Boundary) -> Subsurface {
+        Subsurface {
+            extinction: mean_free_path.map_each(|d| 1. / d.max(1e-9)),
+            albedo: albedo.map_each(single_scattering_albedo),
+            boundary,
+        }
+    }
+}
+
+impl Hash for Subsurface {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.extinction.hash(state);
+        self.albedo.hash(state);
+        match self.boundary {
+            Boundary::Refractive(indice) => indice.to_bits().hash(state),
+            Boundary::Diffuse => 0u8.hash(state),
+        }
+    }
+}
+
+//inversion de l'albedo multiple (couleur apparente d'un milieu semi-infini) en albedo de diffusion simple,
+//ajustement de van de Hulst utilisé par Chiang et al.
+pub fn single_scattering_albedo(albedo: f64) -> f64 {
+    let a = albedo.clamp(0., 1.);
+    let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+    (1. - s * s).clamp(0., 1.)
+}
+
+//tire la distance du prochain évènement le long d'un segment de longueur `distance`, pour N canaux dont
+//les `channels` premiers sont transportés. le canal qui guide le tirage est choisi au hasard, les poids sont
+//divisés par la densité moyenne sur les canaux (échantillonnage spectral MIS, cf Chiang et al.)
+//retourne la distance de diffusion, ou None si la surface est atteinte, et le poids de chaque canal
+pub(crate) fn sample<const N: usize>(
+    extinction: [f64; N],
+    albedo: [f64; N],
+    channels: usize,
+    distance: f64,
+) -> (Option<f64>, [f64; N]) {
+    let channel = ((random::<f64>() * channels as f64) as usize).min(channels - 1);
+    let t = -(1. - random::<f64>()).ln() / extinction[channel];
+    let mean = |f: &dyn Fn(usize) -> f64| (0..channels).map(f).sum::<f64>() / channels as f64;
+    let transmittance = |k: usize, t: f64| (-extinction[k] * t).exp();
+    if t < distance {
+        let density = mean(&|k| extinction[k] * transmittance(k, t));
+        let weight =
+            std::array::from_fn(|k| albedo[k] * extinction[k] * transmittance(k, t) / density);
+        (Some(t), weight)
+    } else {
+        let density = mean(&|k| transmittance(k, distance));
+        let weight = std::array::from_fn(|k| transmittance(k, distance) / density);
+        (None, weight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn albedo_inversion_keeps_the_bounds() {
+        assert!(single_scattering_albedo(0.) < 1e-3);
+        assert!((single_scattering_albedo(1.) - 1.).abs() < 1e-3);
+        //il faut un albedo simple très proche de 1 pour qu'un objet épais paraisse clair
+        let mut previous = 0.;
+        for i in 1..=10 {
+            let a = single_scattering_albedo(i as f64 / 10.);
+            assert!(a > previous && a >= i as f64 / 10. - 1e-4);
+            previous = a;
+        }
+    }
+
+    #[test]
+    fn sampling_is_unbiased_for_every_channel() {
+        //moyenne des poids de passage = transmittance de chaque canal, quel que soit le canal tiré
+        let extinction = [0.5, 2., 8.];
+        let distance = 0.4;
+        let count = 200_000;
+        let mut passed = [0.; 3];
+        let mut scattered = [0.; 3];
+        for _ in 0..count {
+            match sample(extinction, [1.; 3], 3, distance) {
+                (None, weight) => passed.iter_mut().zip(weight).for_each(|(p, w)| *p += w),
+                (Some(t), weight) => {
+                    assert!(t < distance);
+                    scattered.iter_mut().zip(weight).for_each(|(s, w)| *s += w)
+                }
+            }
+        }
+        for k in 0..3 {
+            let expected = (-extinction[k] * distance).exp();
+            assert!((passed[k] / count as f64 - expected).abs() < 0.01);
+            //albedo 1: ce qui ne passe pas est diffusé
+            assert!((scattered[k] / count as f64 - (1. - expected)).abs() < 0.01);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::hash::{Hash, Hasher};
+
+//valeurs scalaires plaquées sur les coordonnées uv des objets (épaisseur d'un film, masque de mélange),
+//ligne 0 en haut (v = 1) comme une image
+#[derive(Clone, Debug, PartialEq)]
+pub struct Texture {
+    width: usize,
+    height: usize,
+    values: Vec<f64>,
+}
+
+impl Texture {
+    pub fn new(width: usize, height: usize, values: Vec<f64>) -> Texture {
+        assert!(width > 0 && height > 0 && values.len() == width * height);
+        Texture {
+            width,
+            height,
+            values,
+        }
+    }
+
+    //image 8 bits en niveaux de gris: 0 donne `min`, 255 donne `max`
+    pub fn from_grey(width: usize, height: usize, grey: &[u8], min: f64, max: f64) -> Texture {
+        let values = grey
+            .iter()
+            .map(|&g| min + (max - min) * g as f64 / 255.)
+            .collect();
+        Texture::new(width, height, values)
+    }
+
+    //interpolation bilinéaire, u fait le tour de l'objet, v est borné
+    pub fn sample(&self, (u, v): (f64, f64)) -> f64 {
+        let x = u.rem_euclid(1.) * self.width as f64 - 0.5;
+        let y = (1. - v.clamp(0., 1.)) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let value = |x: f64, y: f64| {
+            let column = (x as isize).rem_euclid(self.width as isize) as usize;
+            let row = (y as isize).clamp(0, self.height as isize - 1) as usize;
+            self.values[row * self.width + column]
+        };
+        let top = value(x0, y0) * (1. - tx) + value(x0 + 1., y0) * tx;
+        let bottom = value(x0, y0 + 1.) * (1. - tx) + value(x0 + 1., y0 + 1.) * tx;
+        top * (1. - ty) + bottom * ty
+    }
+}
+
+impl Hash for Texture {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.width, self.height).hash(state);
+        self.values
+            .iter()
+            .for_each(|value| value.to_bits().hash(state));
+    }
+}
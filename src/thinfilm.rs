@@ -0,0 +1,170 @@
+use std::f64::consts::PI;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::material::Complex;
+use crate::texture::Texture;
+
+//film mince déposé sur un matériau (bulle de savon, nappe d'huile, métal anodisé): la lumière réfléchie
+//par les deux faces du film interfère, la réflectance dépend de la longueur d'onde, de l'angle et de l'épaisseur.
+//l'épaisseur, de l'ordre de la longueur d'onde, est donnée en nm
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThinFilm {
+    pub indice: f64,
+    pub thickness: Thickness,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Thickness {
+    Constant(f64),
+    //de `bottom` à `top` selon la coordonnée v (pôles sud et nord d'une sphère):
+    //une bulle s'affine vers le haut en s'égouttant
+    Gradient { bottom: f64, top: f64 },
+    //épaisseurs plaquées sur les coordonnées uv
+    Map(Arc<ThicknessMap>),
+}
+
+//carte d'épaisseurs en nm, ligne 0 en haut (v = 1) comme une image
+pub type ThicknessMap = Texture;
+
+impl Thickness {
+    pub fn at(&self, uv: (f64, f64)) -> f64 {
+        match self {
+            Thickness::Constant(thickness) => *thickness,
+            Thickness::Gradient { bottom, top } => bottom + (top - bottom) * uv.1.clamp(0., 1.),
+            Thickness::Map(map) => map.sample(uv),
+        }
+    }
+}
+
+impl Hash for ThinFilm {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.indice.to_bits().hash(state);
+        match &self.thickness {
+            Thickness::Constant(thickness) => thickness.to_bits().hash(state),
+            Thickness::Gradient { bottom, top } => [bottom, top].map(|v| v.to_bits()).hash(state),
+            Thickness::Map(map) => map.hash(state),
+        }
+    }
+}
+
+impl ThinFilm {
+    //réflectance (lumière non polarisée) de l'empilement milieu n0 | film | substrat n2, pour une épaisseur
+    //donnée: somme d'Airy des réflexions multiples dans le film, r = (r01 + r12 e^iδ) / (1 + r01 r12 e^iδ).
+    //le substrat peut être absorbant (indice complexe d'un métal); les angles complexes couvrent la réflexion totale
+    pub(crate) fn reflectance(
+        &self,
+        lambda: f64,
+        cos_0: f64,
+        thickness: f64,
+        n0: f64,
+        n2: Complex,
+    ) -> f64 {
+        let real = |v: f64| Complex(v, 0.);
+        let cos_0 = cos_0.clamp(0., 1.);
+        let (m0, m1) = (real(n0), real(self.indice));
+        //Snell-Descartes: n0 sin θ0 = n sin θ, cos θ = sqrt(1 - sin² θ)
+        let sin2_0 = real(n0 * n0 * (1. - cos_0 * cos_0));
+        let cosine = |n: Complex| real(1.).sub(sin2_0.div(n.mul(n))).sqrt();
+        let (c0, c1, c2) = (real(cos_0), cosine(m1), cosine(n2));
+
+        let fresnel = |a: Complex, b: Complex| a.sub(b).div(a.add(b));
+        let r01_s = fresnel(m0.mul(c0), m1.mul(c1));
+        let r12_s = fresnel(m1.mul(c1), n2.mul(c2));
+        let r01_p = fresnel(m1.mul(c0), m0.mul(c1));
+        let r12_p = fresnel(n2.mul(c1), m1.mul(c2));
+
+        //déphasage d'un aller-retour dans le film
+        let delta = m1.mul(c1).mul(real(4. * PI * thickness / lambda));
+        let phase = exp_i(delta);
+        let airy = |r01: Complex, r12: Complex| {
+            let r12 = r12.mul(phase);
+            r01.add(r12).div(real(1.).add(r01.mul(r12))).norm()
+        };
+        ((airy(r01_s, r12_s) + airy(r01_p, r12_p)) / 2.).clamp(0., 1.)
+    }
+}
+
+//e^(iz) = e^(-Im z) (cos Re z + i sin Re z)
+fn exp_i(z: Complex) -> Complex {
+    let magnitude = (-z.1).exp();
+    Complex(magnitude * z.0.cos(), magnitude * z.0.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Conductor;
+
+    fn film(indice: f64) -> ThinFilm {
+        ThinFilm {
+            indice,
+            thickness: Thickness::Constant(0.),
+        }
+    }
+
+    //réflectance d'une interface simple, sans film
+    fn fresnel(cos: f64, n0: f64, n2: f64) -> f64 {
+        film(n0).reflectance(550., cos, 0., n0, Complex(n2, 0.))
+    }
+
+    #[test]
+    fn without_film_it_is_plain_fresnel() {
+        assert!((fresnel(1., 1., 1.5) - 0.04).abs() < 1e-12);
+        //un film d'épaisseur nulle, ou du même indice que le milieu, ne change rien
+        for thickness in [0., 123., 480.] {
+            let same = film(1.).reflectance(500., 0.8, thickness, 1., Complex(1.5, 0.));
+            assert!((same - fresnel(0.8, 1., 1.5)).abs() < 1e-12);
+        }
+        let none = film(1.38).reflectance(500., 0.8, 0., 1., Complex(1.5, 0.));
+        assert!((none - fresnel(0.8, 1., 1.5)).abs() < 1e-12);
+        //sur un métal: même valeur que les équations de Fresnel complexes
+        let (n, k) = Conductor::Gold.indice(600.);
+        let gold = film(1.).reflectance(600., 0.7, 250., 1., Complex(n, k));
+        assert!((gold - Conductor::Gold.fresnel(600., 0.7)).abs() < 1e-9);
+        //réflexion totale depuis l'intérieur du verre
+        assert!((fresnel(0.3, 1.5, 1.) - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn quarter_wave_coating_is_antireflective() {
+        //couche antireflet: indice sqrt(n) et épaisseur λ / 4n, la réflexion s'annule à λ
+        let n = 1.5f64;
+        let coating = film(n.sqrt());
+        let thickness = 550. / (4. * n.sqrt());
+        assert!(coating.reflectance(550., 1., thickness, 1., Complex(n, 0.)) < 1e-12);
+        //mais pas aux autres longueurs d'onde
+        assert!(coating.reflectance(400., 1., thickness, 1., Complex(n, 0.)) > 1e-3);
+    }
+
+    #[test]
+    fn soap_film_reflects_colours_depending_on_thickness() {
+        let soap = film(1.33);
+        let reflectance =
+            |lambda, thickness| soap.reflectance(lambda, 1., thickness, 1., Complex(1., 0.));
+        //film très mince: les deux réflexions, en opposition de phase, s'annulent (zone noire des bulles)
+        assert!(reflectance(550., 1.) < 1e-3);
+        //maximum d'interférence constructive à 2 n d = λ (m + 1/2)
+        let thickness = 550. / (4. * 1.33);
+        assert!(reflectance(550., thickness) > reflectance(420., thickness));
+        let peak = 4. * 0.02 / (1. + 0.02f64).powi(2);
+        assert!((reflectance(550., thickness) - peak).abs() < 0.01);
+    }
+
+    #[test]
+    fn maps_are_sampled_in_uv() {
+        let map = ThicknessMap::from_grey(2, 2, &[0, 255, 255, 0], 100., 600.);
+        //centre des texels: ligne du haut en v = 0.75
+        assert!((map.sample((0.25, 0.75)) - 100.).abs() < 1e-9);
+        assert!((map.sample((0.75, 0.75)) - 600.).abs() < 1e-9);
+        assert!((map.sample((0.25, 0.25)) - 600.).abs() < 1e-9);
+        //u boucle, v est borné
+        assert!((map.sample((1.25, 1.5)) - 100.).abs() < 1e-9);
+        assert!((map.sample((0.5, 0.75)) - 350.).abs() < 1e-9);
+        let gradient = Thickness::Gradient {
+            bottom: 800.,
+            top: 200.,
+        };
+        assert_eq!(gradient.at((0.3, 0.5)), 500.);
+    }
+}
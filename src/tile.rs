@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+//zone rectangulaire de l'image, en pixels, origine en haut à gauche
+#[derive(Copy, Clone)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    pub fn pixel_count(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    //parcours des pixels de la tuile ligne par ligne, en coordonnées image
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> {
+        let Tile {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+        (y..y + height).flat_map(move |j| (x..x + width).map(move |i| (i, j)))
+    }
+}
+
+//découpe l'image en tuiles carrées de `size` pixels (les tuiles du bord peuvent être plus petites)
+//et les ordonne en spirale depuis le centre: le sujet est en général au centre, on le voit apparaitre en premier
+pub fn spiral_tiles(width: u32, height: u32, size: u32) -> Vec<Tile> {
+    let columns = width.div_ceil(size) as i64;
+    let rows = height.div_ceil(size) as i64;
+    let total = (columns * rows) as usize;
+
+    let mut tiles = Vec::with_capacity(total);
+    let (mut c, mut r) = ((columns - 1) / 2, (rows - 1) / 2);
+    // droite, bas, gauche, haut; la longueur du segment augmente de 1 tous les 2 changements de direction
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut direction = 0;
+    let mut segment = 1;
+    while tiles.len() < total {
+        for _ in 0..2 {
+            let (dc, dr) = directions[direction % 4];
+            for _ in 0..segment {
+                if c >= 0 && c < columns && r >= 0 && r < rows {
+                    let (x, y) = (c as u32 * size, r as u32 * size);
+                    tiles.push(Tile {
+                        x,
+                        y,
+                        width: size.min(width - x),
+                        height: size.min(height - y),
+                    });
+                }
+                c += dc;
+                r += dr;
+            }
+            direction += 1;
+        }
+        segment += 1;
+    }
+    tiles
+}
+
+//file de travail avec vol de tâches: chaque worker a sa propre file, et quand elle est vide
+//il vole la dernière tuile d'un autre worker, pour qu'aucun coeur ne reste inactif en fin de rendu
+pub struct WorkQueue {
+    queues: Vec<Mutex<VecDeque<Tile>>>,
+}
+
+impl WorkQueue {
+    pub fn new(tiles: Vec<Tile>, workers: usize) -> WorkQueue {
+        let workers = workers.max(1);
+        let mut queues: Vec<VecDeque<Tile>> = (0..workers).map(|_| VecDeque::new()).collect();
+        //distribution en round-robin pour que tous les workers commencent par le centre de l'image
+        for (i, tile) in tiles.into_iter().enumerate() {
+            queues[i % workers].push_back(tile);
+        }
+        WorkQueue {
+            queues: queues.into_iter().map(Mutex::new).collect(),
+        }
+    }
+
+    pub fn next(&self, worker: usize) -> Option<Tile> {
+        let own = worker % self.queues.len();
+        if let Some(tile) = self.queues[own].lock().unwrap().pop_front() {
+            return Some(tile);
+        }
+        //on vole par la fin de la file: ce sont les tuiles les plus éloignées de ce que traite la victime
+        (1..self.queues.len())
+            .map(|offset| (own + offset) % self.queues.len())
+            .find_map(|victim| self.queues[victim].lock().unwrap().pop_back())
+    }
+}
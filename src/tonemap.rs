@@ -0,0 +1,274 @@
+use crate::color::Color;
+use crate::colorspace::{multiply, srgb_encode};
+
+//transformation d'affichage: le film garde la radiance linéaire, non bornée, et n'est converti
+//en couleurs affichables qu'à l'écriture des images 8 bits (l'EXR reste linéaire)
+//ordre: balance des blancs, exposition, opérateur de tone mapping, puis encodage sRGB
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DisplayTransform {
+    //en stops (EV): +1 double la luminosité
+    pub exposure: f64,
+    //température (K) de l'éclairage à neutraliser, None: pas de correction
+    pub white_balance: Option<f64>,
+    pub tone_map: ToneMap,
+    //bruit d'un demi pas de quantification ajouté avant la conversion en 8 bits, contre les bandes dans les dégradés
+    pub dither: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneMap {
+    //les valeurs au delà de 1 sont écrêtées
+    Clamp,
+    //L / (1 + L) sur la luminance, la teinte est conservée
+    Reinhard,
+    //approximation de la courbe ACES (RRT + ODT sRGB) par Stephen Hill
+    Aces,
+}
+
+impl ToneMap {
+    pub const ALL: [ToneMap; 3] = [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMap::Clamp => "clamp",
+            ToneMap::Reinhard => "reinhard",
+            ToneMap::Aces => "aces",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<ToneMap> {
+        ToneMap::ALL.iter().copied().find(|t| t.name() == name)
+    }
+
+    //couleur linéaire >= 0 -> couleur linéaire dans [0, 1]
+    pub fn apply(self, color: Color) -> Color {
+        match self {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => {
+                let l = luminance(color);
+                if l > 0. {
+                    color * (1. / (1. + l))
+                } else {
+                    color
+                }
+            }
+            ToneMap::Aces => {
+                let v = multiply(&ACES_INPUT, color).map_each(|v| {
+                    (v * (v + 0.0245786) - 0.000090537)
+                        / (v * (0.983729 * v + 0.4329510) + 0.238081)
+                });
+                multiply(&ACES_OUTPUT, v)
+            }
+        }
+        .map_each(|v| v.clamp(0., 1.))
+    }
+}
+
+impl Default for DisplayTransform {
+    //pas de correction: écrêtage à 1 puis encodage sRGB, avec dithering
+    fn default() -> Self {
+        DisplayTransform {
+            exposure: 0.,
+            white_balance: None,
+            tone_map: ToneMap::Clamp,
+            dither: true,
+        }
+    }
+}
+
+impl DisplayTransform {
+    //couleur sRGB linéaire -> couleur encodée dans [0, 1]
+    pub fn apply(&self, color: Color) -> Color {
+        let mut color = color;
+        if let Some(kelvin) = self.white_balance {
+            color = color * white_balance(kelvin);
+        }
+        if self.exposure != 0. {
+            color = color * 2f64.powf(self.exposure);
+        }
+        self.tone_map
+            .apply(color.map_each(|v| v.max(0.)))
+            .map_each(srgb_encode)
+    }
+
+    //couleur du pixel (x, y) prête à être quantifiée sur `levels` niveaux (cf Color::scale)
+    //le bruit de dithering est triangulaire, d'amplitude un pas, et ne dépend que de la position du pixel:
+    //deux écritures de la même image donnent les mêmes fichiers
+    pub fn pixel(&self, color: Color, x: u32, y: u32, levels: f64) -> Color {
+        let color = self.apply(color);
+        if !self.dither {
+            return color;
+        }
+        let noise = |channel: u32| {
+            let h = hash(x, y, channel);
+            let (a, b) = ((h >> 40) as f64, (h & 0xff_ffff) as f64);
+            //somme de deux uniformes sur [0, 1[ moins 1: triangulaire sur ]-1, 1[, centrée sur 0
+            //+0.5: Color::scale tronque, on arrondit donc au plus proche en moyenne
+            (a + b) / (1u64 << 24) as f64 - 0.5
+        };
+        //valeurs déjà à 0 ou 1 (noir, blanc écrêté) laissées intactes
+        let dither = |v: f64, channel| {
+            if v <= 0. || v >= 1. {
+                v
+            } else {
+                v + noise(channel) / levels
+            }
+        };
+        Color::new(
+            dither(color.red, 0),
+            dither(color.green, 1),
+            dither(color.blue, 2),
+        )
+    }
+}
+
+//hash entier (splitmix64) de la position et du canal
+fn hash(x: u32, y: u32, channel: u32) -> u64 {
+    let mut z =
+        ((x as u64) << 34 ^ (y as u64) << 2 ^ channel as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+//sRGB / ACEScg (AP1) avec adaptation D65 -> D60, puis RRT + ODT simplifiés, puis retour en sRGB
+const ACES_INPUT: [[f64; 3]; 3] = [
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+];
+const ACES_OUTPUT: [[f64; 3]; 3] = [
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+];
+
+fn luminance(c: Color) -> f64 {
+    0.2126 * c.red + 0.7152 * c.green + 0.0722 * c.blue
+}
+
+//gains rgb qui rendent neutre une lumière de corps noir à `kelvin`, à luminance constante
+//(6504K, le blanc D65 du sRGB, ne change rien)
+pub fn white_balance(kelvin: f64) -> Color {
+    let white = blackbody(6504.);
+    let light = blackbody(kelvin);
+    let gains = Color::new(
+        white.red / light.red,
+        white.green / light.green,
+        white.blue / light.blue,
+    );
+    gains * (1. / luminance(gains))
+}
+
+//domaine de validité de l'approximation du lieu de Planck, et donc des températures de balance des blancs
+pub const MIN_KELVIN: f64 = 1667.;
+pub const MAX_KELVIN: f64 = 25000.;
+
+//couleur sRGB linéaire d'un corps noir, de luminance 1
+//position sur le lieu de Planck par l'approximation de Kim et al. (2002)
+fn blackbody(kelvin: f64) -> Color {
+    let t = kelvin.clamp(MIN_KELVIN, MAX_KELVIN);
+    let x = if t <= 4000. {
+        -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t.powi(3) + 2.1070379e6 / t.powi(2) + 0.2226347e3 / t + 0.240390
+    };
+    let y = if t <= 2222. {
+        -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+    } else if t <= 4000. {
+        -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x.powi(3) - 5.87338670 * x.powi(2) + 3.75112997 * x - 0.37001483
+    };
+    //xyY (Y = 1) -> XYZ -> sRGB linéaire
+    let xyz = Color::new(x / y, 1., (1. - x - y) / y);
+    multiply(
+        &[
+            [3.2404542, -1.5371385, -0.4985314],
+            [-0.9692660, 1.8760108, 0.0415560],
+            [0.0556434, -0.2040259, 1.0572252],
+        ],
+        xyz,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Color, b: Color, epsilon: f64) -> bool {
+        (a.red - b.red).abs() < epsilon
+            && (a.green - b.green).abs() < epsilon
+            && (a.blue - b.blue).abs() < epsilon
+    }
+
+    #[test]
+    fn default_is_srgb_with_clamping() {
+        let display = DisplayTransform::default();
+        let Color { red, green, blue } = display.apply(Color::new(0.25, 4., -1.));
+        assert!((red - 0.5371).abs() < 1e-4);
+        assert!((green - 1.).abs() < 1e-12 && blue == 0.);
+    }
+
+    #[test]
+    fn dithering_averages_to_the_exact_value() {
+        let display = DisplayTransform::default();
+        //une valeur entre deux niveaux 8 bits: sans dithering, tous les pixels auraient le même niveau
+        let linear = Color::new(0.2140, 0.2140, 0.2140);
+        let encoded = display.apply(linear).red * 255.99;
+        let levels: Vec<u32> = (0..64)
+            .flat_map(|y| (0..64).map(move |x| (x, y)))
+            .map(|(x, y)| display.pixel(linear, x, y, 255.99).scale(255.99).0)
+            .collect();
+        let mean = levels.iter().sum::<u32>() as f64 / levels.len() as f64;
+        assert!((mean - encoded).abs() < 0.05, "{} {}", mean, encoded);
+        assert!(levels.iter().any(|&l| l != levels[0]));
+        assert!(levels.iter().all(|&l| (l as f64 - encoded).abs() < 1.5));
+        //même position, même bruit
+        assert_eq!(
+            display.pixel(linear, 3, 5, 255.99).red,
+            display.pixel(linear, 3, 5, 255.99).red
+        );
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let display = DisplayTransform {
+            exposure: 2.,
+            ..DisplayTransform::default()
+        };
+        let Color { red, .. } = display.apply(Color::new(0.0625, 0., 0.));
+        assert_eq!(red, srgb_encode(0.25));
+    }
+
+    #[test]
+    fn operators_keep_highlights_below_one() {
+        for tone_map in [ToneMap::Reinhard, ToneMap::Aces] {
+            let mut previous = -1.;
+            for i in 0..100 {
+                let v = i as f64 * 0.5;
+                let Color { red, green, blue } = tone_map.apply(Color::new(v, v, v));
+                assert!(red > previous || red == 1., "{:?} {}", tone_map, v);
+                assert!(red <= 1. && (red - green).abs() < 0.01 && (red - blue).abs() < 0.01);
+                previous = red;
+            }
+            assert!(tone_map.apply(Color::EMPTY).red < 0.01);
+            assert_eq!(ToneMap::parse(tone_map.name()), Some(tone_map));
+        }
+    }
+
+    #[test]
+    fn white_balance_neutralises_the_light() {
+        assert!(close(white_balance(6504.), Color::new(1., 1., 1.), 1e-9));
+        //une lumière de tungstène est orangée: on retire du rouge et on ajoute du bleu
+        let tungsten = white_balance(3200.);
+        assert!(tungsten.red < 1. && tungsten.blue > 1.);
+        let lit = blackbody(3200.) * tungsten;
+        let white = blackbody(6504.);
+        assert!(close(
+            lit * (1. / luminance(lit)),
+            white * (1. / luminance(white)),
+            1e-9
+        ));
+    }
+}
@@ -0,0 +1,264 @@
+use std::hash::{Hash, Hasher};
+
+use crate::aov::Aov;
+use crate::color::Color;
+use crate::film::Film;
+use crate::geometry::Intersection;
+use crate::ray::Ray;
+use crate::vec::Vec3;
+
+//rendu stylisé (non photoréaliste): chaque point vu par la caméra est éclairé par une lumière unique placée au dessus
+//de l'observateur, l'éclairage diffus est découpé en aplats et les bords des objets reçoivent un liseré clair.
+//aucun rayon secondaire n'est lancé: reflets, transparence et ombres portées disparaissent.
+//le contour des objets est ajouté ensuite sur l'image, à partir des aovs (cf outline)
+
+//part du blanc mélangée à la couleur dans le liseré
+const RIM_STRENGTH: f64 = 0.5;
+
+//style d'un matériau dessiné: nombre d'aplats, luminosité du plus sombre, largeur du liseré dans [0, 1]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Toon {
+    pub bands: u32,
+    pub shadow: f64,
+    pub rim: f64,
+}
+
+impl Default for Toon {
+    //trois aplats, ombre au tiers, liseré fin: le style des matériaux qui n'en donnent pas
+    fn default() -> Toon {
+        Toon {
+            bands: 3,
+            shadow: 0.3,
+            rim: 0.2,
+        }
+    }
+}
+
+impl Hash for Toon {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bands.hash(state);
+        [self.shadow, self.rim].map(f64::to_bits).hash(state);
+    }
+}
+
+impl Toon {
+    //couleur d'un point de normale `normal` vu dans la direction `view`, éclairé depuis la direction `light`
+    pub fn shade(&self, color: Color, normal: Vec3, view: Vec3, light: Vec3) -> Color {
+        let (n, v) = (normal.unit(), view.unit());
+        let lambert = n.scalar_product(light.unit()).max(0.);
+        let bands = self.bands.max(1);
+        let band = ((lambert * bands as f64) as u32).min(bands - 1);
+        let level = if bands == 1 {
+            1.
+        } else {
+            self.shadow + (1. - self.shadow) * band as f64 / (bands - 1) as f64
+        };
+        let lit = color * level;
+        //liseré: la surface est vue presque de profil
+        if n.scalar_product(-v) < self.rim {
+            lit.map_each(|c| c + (1. - c) * RIM_STRENGTH)
+        } else {
+            lit
+        }
+    }
+}
+
+//couleur (sRGB) d'un point touché par un rayon caméra: style du matériau, ou style par défaut
+pub(crate) fn shade(hit: &Intersection, ray: &Ray) -> Color {
+    let view = ray.direction.unit();
+    //lumière au dessus de la caméra: les faces visibles sont toujours au moins en partie éclairées
+    let light = Vec3(0., 1., 0.) - view;
+    hit.material
+        .toon
+        .unwrap_or_default()
+        .shade(hit.material.color, hit.normale, view, light)
+}
+
+//détection des contours: un pixel est sur un contour quand un de ses voisins montre un autre objet, une profondeur
+//relative trop différente ou une normale trop écartée. le trait est posé du côté de l'objet le plus proche
+#[derive(Clone, Copy, Debug)]
+pub struct Outline {
+    //couleur du trait, sRGB
+    pub color: Color,
+    //écart relatif de profondeur
+    pub depth: f64,
+    //cosinus minimal entre normales voisines
+    pub normal: f64,
+}
+
+impl Default for Outline {
+    fn default() -> Outline {
+        Outline {
+            color: Color::new(0., 0., 0.),
+            depth: 0.1,
+            normal: 0.5,
+        }
+    }
+}
+
+//retourne un film dont l'image porte les contours; nombres d'échantillons et aovs sont conservés.
+//utilise les aovs object_id, depth et normal présents dans le film, sans aucun d'eux le film est inchangé
+pub fn outline(film: &Film, outline: &Outline) -> Film {
+    let (width, height) = (film.width() as usize, film.height() as usize);
+    let layer = |aov| {
+        film.aovs().contains(&aov).then(|| {
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x as u32, y as u32)))
+                .map(|(x, y)| film.aov(aov, x, y).unwrap())
+                .collect::<Vec<_>>()
+        })
+    };
+    let ids = layer(Aov::ObjectId);
+    let normals = layer(Aov::Normal);
+    //le fond (profondeur nulle) est infiniment loin
+    let depths = layer(Aov::Depth).map(|depth| {
+        depth
+            .iter()
+            .map(|d| if d[0] > 0. { d[0] } else { f64::INFINITY })
+            .collect::<Vec<_>>()
+    });
+
+    let depth = |p: usize| depths.as_ref().map_or(0., |depths| depths[p]);
+    let edge = |p: usize, q: usize| {
+        //le pixel le plus lointain laisse le trait à son voisin
+        if depth(p) > depth(q) {
+            return false;
+        }
+        if let Some(ids) = &ids {
+            if ids[p][0] != ids[q][0] {
+                return true;
+            }
+        }
+        if let Some(depths) = &depths {
+            let (dp, dq) = (depths[p], depths[q]);
+            if dq.is_infinite() || (dq - dp) > outline.depth * dp {
+                return dp.is_finite();
+            }
+        }
+        if let Some(normals) = &normals {
+            let vector = |n: [f64; 3]| Vec3(n[0], n[1], n[2]);
+            let (np, nq) = (vector(normals[p]), vector(normals[q]));
+            let (lp, lq) = (np.len(), nq.len());
+            if lp > 0. && lq > 0. && np.scalar_product(nq) < outline.normal * lp * lq {
+                return true;
+            }
+        }
+        false
+    };
+
+    let color = film.space().from_srgb(outline.color);
+    let (sums, samples) = film.raw();
+    let mut pixels = sums.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let p = y * width + x;
+            let neighbours = [
+                (x > 0).then(|| p - 1),
+                (x + 1 < width).then(|| p + 1),
+                (y > 0).then(|| p - width),
+                (y + 1 < height).then(|| p + width),
+            ];
+            if neighbours.iter().flatten().any(|&q| edge(p, q)) {
+                pixels[p] = color * samples[p] as f64;
+            }
+        }
+    }
+    Film::from_raw(
+        film.width(),
+        film.height(),
+        pixels,
+        samples.to_vec(),
+        film.aovs().to_vec(),
+        film.raw_layers().to_vec(),
+    )
+    .in_space(film.space())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::TileSamples;
+    use crate::tile::Tile;
+
+    #[test]
+    fn diffuse_light_is_quantised_into_bands() {
+        let toon = Toon {
+            bands: 3,
+            shadow: 0.25,
+            rim: 0.,
+        };
+        let white = Color::new(1., 1., 1.);
+        let view = Vec3(0., 0., -1.);
+        let light = Vec3(0., 0., 1.);
+        let level = |angle: f64| {
+            let normal = Vec3(angle.sin(), 0., angle.cos());
+            toon.shade(white, normal, view, light).green
+        };
+        //n.l = 1, 0.9, 0.5, 0.2 et presque 0
+        assert_eq!(level(0.), 1.);
+        assert_eq!(level(0.9f64.acos()), 1.);
+        assert_eq!(level(0.5f64.acos()), 0.625);
+        assert_eq!(level(0.2f64.acos()), 0.25);
+        assert_eq!(level(1.5), 0.25);
+    }
+
+    #[test]
+    fn grazing_surfaces_get_a_rim() {
+        let toon = Toon {
+            bands: 1,
+            shadow: 0.,
+            rim: 0.3,
+        };
+        let red = Color::new(0.8, 0., 0.);
+        let view = Vec3(0., 0., -1.);
+        let facing = toon.shade(red, Vec3(0., 0., 1.), view, Vec3(0., 1., 1.));
+        assert_eq!((facing.red, facing.green), (0.8, 0.));
+        let grazing = toon.shade(red, Vec3(1., 0., 0.1), view, Vec3(0., 1., 1.));
+        assert!((grazing.red - 0.9).abs() < 1e-12 && (grazing.green - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn outlines_follow_object_and_depth_edges() {
+        //objet 1 à gauche, objet 2 plus loin à droite, fond sur la dernière colonne
+        let (width, height) = (6, 2);
+        let aovs = [Aov::Depth, Aov::Normal, Aov::ObjectId];
+        let mut film = Film::with_aovs(width, height, &aovs);
+        let tile = Tile {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        let mut values = Vec::new();
+        for _ in 0..height {
+            for x in 0..width {
+                let (depth, id) = match x {
+                    0..=2 => (1., 1.),
+                    3 | 4 => (2., 2.),
+                    _ => (0., 0.),
+                };
+                let normal = if id > 0. { 1. } else { 0. };
+                values.extend([depth, 0., 0., normal, id]);
+            }
+        }
+        let samples = TileSamples {
+            colors: vec![Color::new(1., 1., 1.); (width * height) as usize],
+            aovs: values,
+        };
+        film.add_tile(&tile, &samples, 1, 0);
+
+        let outlined = outline(&film, &Outline::default());
+        let lines: Vec<_> = (0..width).map(|x| outlined.pixel(x, 1).red).collect();
+        //le trait est sur l'objet proche, et sur le bord de l'objet lointain contre le fond
+        assert_eq!(lines, vec![1., 1., 0., 1., 0., 1.]);
+        assert_eq!(outlined.raw().1, film.raw().1);
+        //sans aovs, rien n'est tracé
+        let mut plain = Film::new(width, height);
+        let samples = TileSamples {
+            aovs: Vec::new(),
+            ..samples
+        };
+        plain.add_tile(&tile, &samples, 1, 0);
+        assert_eq!(outline(&plain, &Outline::default()).pixel(2, 0).red, 1.);
+    }
+}
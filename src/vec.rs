@@ -1,11 +1,21 @@
 use crate::point::Point3;
+use crate::sampler::rng;
 use rand::Rng;
+use std::hash::{Hash, Hasher};
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
 //TODO regarder les crates existantes pour le calcul vectoriel
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub struct Vec3(pub f64, pub f64, pub f64);
 
+impl Hash for Vec3 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+        self.1.to_bits().hash(state);
+        self.2.to_bits().hash(state);
+    }
+}
+
 impl Neg for Vec3 {
     type Output = Vec3;
 
@@ -94,9 +104,9 @@ impl Vec3 {
             //pour qu'un vecteur soit dans la sphere il faut |vec| < r
             // pour une sphere unitaire r = r2 = 1, donc |vec|^2 < 1
             let dir = Vec3(
-                rand::thread_rng().gen_range(-1.0..=1.),
-                rand::thread_rng().gen_range(-1.0..=1.),
-                rand::thread_rng().gen_range(-1.0..=1.),
+                rng().gen_range(-1.0..=1.),
+                rng().gen_range(-1.0..=1.),
+                rng().gen_range(-1.0..=1.),
             );
             if dir.sqr_len() < 1. {
                 return dir;
@@ -107,8 +117,8 @@ impl Vec3 {
     pub fn random_unit_disk() -> Vec3 {
         loop {
             let dir = Vec3(
-                rand::thread_rng().gen_range(-1.0..=1.),
-                rand::thread_rng().gen_range(-1.0..=1.),
+                rng().gen_range(-1.0..=1.),
+                rng().gen_range(-1.0..=1.),
                 0.,
             );
             if dir.sqr_len() < 1. {
@@ -133,3 +143,78 @@ impl Vec3 {
         self.2
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).len() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn vec3() -> impl Strategy<Value = Vec3> {
+        (-100.0..100., -100.0..100., -100.0..100.).prop_map(|(x, y, z)| Vec3(x, y, z))
+    }
+
+    #[test]
+    fn scalar_product() {
+        assert_eq!(Vec3(1., 2., 3.).scalar_product(Vec3(4., -5., 6.)), 12.);
+        assert_eq!(Vec3(1., 0., 0.).scalar_product(Vec3(0., 1., 0.)), 0.);
+    }
+
+    #[test]
+    fn cross_product_of_axes() {
+        let (x, y, z) = (Vec3(1., 0., 0.), Vec3(0., 1., 0.), Vec3(0., 0., 1.));
+        assert_close(x.cross_product(y), z);
+        assert_close(y.cross_product(z), x);
+        assert_close(z.cross_product(x), y);
+        assert_close(y.cross_product(x), -z);
+    }
+
+    #[test]
+    fn points_goes_from_origin_to_destination() {
+        assert_close(
+            Vec3::points(Point3(1., 2., 3.), Point3(4., 0., 3.)),
+            Vec3(3., -2., 0.),
+        );
+    }
+
+    #[test]
+    fn unit() {
+        assert_close(Vec3(0., 3., 4.).unit(), Vec3(0., 0.6, 0.8));
+        assert_eq!(Vec3(0., 3., 4.).len(), 5.);
+    }
+
+    #[test]
+    fn random_vectors_stay_in_unit_sphere_and_disk() {
+        for _ in 0..1000 {
+            assert!(Vec3::random_unit_sphere().sqr_len() < 1.);
+            let disk = Vec3::random_unit_disk();
+            assert!(disk.sqr_len() < 1.);
+            assert_eq!(disk.z(), 0.);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn cross_product_is_orthogonal_to_operands(a in vec3(), b in vec3()) {
+            let c = a.cross_product(b);
+            let scale = a.len() * b.len() * c.len().max(1.);
+            prop_assert!(c.scalar_product(a).abs() <= 1e-9 * scale);
+            prop_assert!(c.scalar_product(b).abs() <= 1e-9 * scale);
+        }
+
+        #[test]
+        fn cross_product_is_anticommutative(a in vec3(), b in vec3()) {
+            assert_close(a.cross_product(b), -b.cross_product(a));
+        }
+
+        #[test]
+        fn unit_vector_has_length_one(a in vec3()) {
+            prop_assume!(a.len() > 1e-6);
+            prop_assert!((a.unit().len() - 1.).abs() < 1e-12);
+        }
+    }
+}
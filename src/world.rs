@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::color::Color;
+use crate::geometry::{sphere, Geometry};
+use crate::material::{colored_dielectric, dielectric, diffuse, metal, soap_bubble};
+use crate::point::Point3;
+use crate::sampler::{random, rng};
+use crate::thinfilm::Thickness;
+use crate::vec::Vec3;
+
+pub fn world_v5() -> Vec<Arc<Geometry>> {
+    let mut objects: Vec<Arc<Geometry>> = Vec::new();
+
+    let ground_material = diffuse(0.5, 0.5, 0.5);
+    objects.push(Arc::new(sphere(0., -1000., 0., 1000., ground_material)));
+
+    let material2 = diffuse(0.4, 0.2, 0.1);
+    objects.push(Arc::new(sphere(-4., 1., 0., 1.0, material2)));
+
+    //la paroi s'affine vers le haut en s'égouttant
+    let bubble = soap_bubble(Thickness::Gradient {
+        bottom: 700.,
+        top: 250.,
+    });
+    objects.push(Arc::new(sphere(0., 1., 0., 1.0, bubble)));
+
+    let material3 = metal(0.7, 0.6, 0.5, 0.0);
+    objects.push(Arc::new(sphere(4., 1., 0., 1.0, material3)));
+
+    let material2 = diffuse(0.2, 0.6, 0.1);
+    objects.push(Arc::new(sphere(-4., 1., -4., 1.0, material2)));
+
+    objects
+}
+
+pub fn world_v4() -> Vec<Arc<Geometry>> {
+    let mut objects: Vec<Arc<Geometry>> = Vec::new();
+
+    let ground_material = diffuse(0.5, 0.5, 0.5);
+    objects.push(Arc::new(sphere(0., -1000., 0., 1000., ground_material)));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random::<f64>();
+            let center = Point3(
+                a as f64 + 0.9 * random::<f64>(),
+                0.2,
+                b as f64 + 0.9 * random::<f64>(),
+            );
+
+            if Vec3::points(Point3(4., 0.2, 0.), center).len() > 0.9 {
+                let sphere_material;
+
+                if choose_mat < 0.4 {
+                    // diffuse
+                    let Color { red, green, blue } = Color::random() * Color::random();
+                    sphere_material = diffuse(red, green, blue);
+                    objects.push(Arc::new(sphere(
+                        center.0,
+                        center.1,
+                        center.2,
+                        0.2,
+                        sphere_material,
+                    )));
+                } else if choose_mat < 0.65 {
+                    // metal
+                    let Color { red, green, blue } = Color::random_range(0.5..1.);
+                    let fuzz = rng().gen_range(0.0..0.5);
+                    sphere_material = metal(red, green, blue, fuzz);
+                    objects.push(Arc::new(sphere(
+                        center.0,
+                        center.1,
+                        center.2,
+                        0.2,
+                        sphere_material,
+                    )));
+                } else if choose_mat < 0.85 {
+                    // glass
+                    let Color { red, green, blue } =
+                        Color::random().map_each(|v| v.sqrt().sqrt().sqrt().sqrt());
+                    sphere_material = colored_dielectric(red, green, blue, 1.5);
+                    objects.push(Arc::new(sphere(
+                        center.0,
+                        center.1,
+                        center.2,
+                        0.2,
+                        sphere_material,
+                    )));
+                } else {
+                    // bubble
+                    //épaisseur propre à chaque bulle
+                    let Color { red, green, .. } = Color::random();
+                    let bubble = soap_bubble(Thickness::Gradient {
+                        bottom: 400. + 500. * red,
+                        top: 100. + 300. * green,
+                    });
+                    objects.push(Arc::new(sphere(
+                        center.0,
+                        center.1 + 0.3 + 1.8 * random::<f64>(),
+                        center.2 - (0.15 * random::<f64>()),
+                        0.2,
+                        bubble,
+                    )));
+                }
+            }
+        }
+    }
+
+    let material1 = dielectric(1.5);
+    objects.push(Arc::new(sphere(0., 1., 0., 1.0, material1)));
+
+    let material2 = diffuse(0.4, 0.2, 0.1);
+    objects.push(Arc::new(sphere(-4., 1., 0., 1.0, material2)));
+
+    let material3 = metal(0.7, 0.6, 0.5, 0.0);
+    objects.push(Arc::new(sphere(4., 1., 0., 1.0, material3)));
+
+    objects
+}
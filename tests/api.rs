@@ -0,0 +1,553 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ray::{
+    conductor, denoise, diffuse, encode_exr, hair, measured, metal, outline, sphere, strands,
+    subsurface, toon, Angle, Aov, Boundary, Camera, CancelToken, Color, ColorSpace, Conductor,
+    CurveShape, Hair, Merl, Outline, Point3, RenderProgress, Renderer, Scene, Settings, Vec3,
+};
+
+//la bibliothèque doit pouvoir être utilisée avec les seuls types exportés à la racine
+#[test]
+fn render_through_public_api() {
+    let camera = Camera::new(
+        Angle::Deg(40.),
+        2.,
+        0.,
+        Point3(0., 1., 4.),
+        Point3(0., 0.5, 0.),
+        Vec3(0., 1., 0.),
+    );
+    let scene = Scene::new(
+        camera,
+        vec![
+            Arc::new(sphere(0., -100., 0., 100., diffuse(0.5, 0.5, 0.5))),
+            Arc::new(sphere(0., 0.5, 0., 0.5, metal(0.8, 0.6, 0.2, 0.1))),
+        ],
+    );
+    let settings = Settings {
+        seed: 7,
+        threads: 2,
+        tile_size: 8,
+        ..Settings::new(20, 10, 4)
+    };
+
+    let film = Renderer::render(&scene, &settings);
+    assert_eq!((film.width(), film.height()), (20, 10));
+    let (_, samples) = film.raw();
+    assert!(samples.iter().all(|&samples| samples == 4));
+    //le ciel est plus bleu que rouge, le sol gris n'en renvoie qu'une partie
+    let sky = film.pixel(0, 0);
+    assert!(sky.blue > sky.red);
+    let ground = film.pixel(10, 9);
+    assert!(ground.red > 0. && ground.red < sky.red);
+
+    //même graine, même image, quel que soit le nombre de threads
+    let single = Renderer::render(
+        &scene,
+        &Settings {
+            threads: 1,
+            ..settings
+        },
+    );
+    assert_eq!(
+        film.raw()
+            .0
+            .iter()
+            .map(|c| c.red.to_bits())
+            .collect::<Vec<_>>(),
+        single
+            .raw()
+            .0
+            .iter()
+            .map(|c| c.red.to_bits())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn observer_and_cancellation() {
+    let scene = Scene::new(
+        Camera::new(
+            Angle::Deg(40.),
+            2.,
+            0.,
+            Point3(0., 1., 4.),
+            Point3(0., 0.5, 0.),
+            Vec3(0., 1., 0.),
+        ),
+        vec![Arc::new(sphere(
+            0.,
+            -100.,
+            0.,
+            100.,
+            diffuse(0.5, 0.5, 0.5),
+        ))],
+    );
+    let settings = Settings {
+        threads: 2,
+        tile_size: 4,
+        ..Settings::new(16, 8, 2)
+    };
+
+    //chaque tuile est signalée, la dernière notification couvre toute l'image
+    let last = Mutex::new(None);
+    Renderer::render_with(
+        &scene,
+        &settings,
+        &|progress: &RenderProgress| {
+            let mut last = last.lock().unwrap();
+            if last
+                .as_ref()
+                .is_none_or(|l: &RenderProgress| progress.tiles_done > l.tiles_done)
+            {
+                *last = Some(progress.clone());
+            }
+        },
+        &CancelToken::new(),
+    );
+    let last = last.into_inner().unwrap().unwrap();
+    assert_eq!((last.tiles_done, last.tiles_total), (8, 8));
+    assert_eq!(last.samples_done, 16 * 8 * 2);
+    assert!(last.rays >= last.samples_done);
+    assert_eq!(last.eta(), Some(Duration::ZERO));
+
+    //annulé dès la première tuile: les autres ne sont pas rendues
+    let cancel = CancelToken::new();
+    let film = Renderer::render_with(
+        &scene,
+        &Settings {
+            threads: 1,
+            ..settings
+        },
+        &|_: &RenderProgress| cancel.cancel(),
+        &cancel,
+    );
+    let (_, samples) = film.raw();
+    assert_eq!(samples.iter().filter(|&&samples| samples > 0).count(), 16);
+}
+
+#[test]
+fn statistics_are_consistent() {
+    let scene = Scene::new(
+        Camera::new(
+            Angle::Deg(40.),
+            2.,
+            0.,
+            Point3(0., 1., 4.),
+            Point3(0., 0.5, 0.),
+            Vec3(0., 1., 0.),
+        ),
+        vec![
+            Arc::new(sphere(0., -100., 0., 100., diffuse(0.5, 0.5, 0.5))),
+            Arc::new(sphere(0., 0.5, 0., 0.5, metal(0.8, 0.6, 0.2, 0.1))),
+        ],
+    );
+    let settings = Settings {
+        threads: 3,
+        tile_size: 4,
+        ..Settings::new(16, 8, 3)
+    };
+    let (_, stats) = Renderer::render_stats(&scene, &settings);
+
+    assert_eq!(stats.camera_rays, 16 * 8 * 3);
+    assert_eq!(stats.path_lengths.iter().sum::<u64>(), stats.camera_rays);
+    let bounces: u64 = stats
+        .path_lengths
+        .iter()
+        .enumerate()
+        .map(|(bounces, count)| bounces as u64 * count)
+        .sum();
+    assert_eq!(bounces, stats.secondary_rays);
+    assert!(stats.primitive_tests > 0 && stats.node_visits >= stats.rays());
+    assert!(stats.phases.iter().any(|(phase, _)| phase == "tracing"));
+    assert!(stats.to_json().starts_with("{\"camera_rays\":384,"));
+}
+
+#[test]
+fn aovs_describe_the_primary_hit() {
+    //une sphère unique vue de face, à 4 unités de la caméra
+    let scene = Scene::new(
+        Camera::new(
+            Angle::Deg(40.),
+            1.,
+            0.,
+            Point3(0., 0., 5.),
+            Point3(0., 0., 0.),
+            Vec3(0., 1., 0.),
+        ),
+        vec![Arc::new(sphere(0., 0., 0., 1., diffuse(0.2, 0.4, 0.6)))],
+    );
+    let settings = Settings {
+        aovs: Aov::ALL.to_vec(),
+        threads: 2,
+        ..Settings::new(9, 9, 8)
+    };
+    let film = Renderer::render(&scene, &settings);
+
+    let centre = |aov| film.aov(aov, 4, 4).unwrap();
+    assert!((centre(Aov::Depth)[0] - 4.).abs() < 0.1);
+    assert!(centre(Aov::Normal)[2] > 0.95);
+    assert!((centre(Aov::Position)[2] - 1.).abs() < 0.05);
+    for (value, expected) in centre(Aov::Albedo).iter().zip([0.2, 0.4, 0.6]) {
+        assert!((value - expected).abs() < 1e-9);
+    }
+    assert_eq!(centre(Aov::ObjectId)[0], 1.);
+    assert!(centre(Aov::MaterialId)[0] > 0.);
+    //le coin ne voit que le ciel
+    assert_eq!(film.aov(Aov::ObjectId, 0, 0).unwrap()[0], 0.);
+    assert_eq!(film.aov(Aov::Depth, 0, 0).unwrap()[0], 0.);
+    assert_eq!(film.aov(Aov::Indirect, 0, 0).unwrap(), [0.; 3]);
+
+    //direct + indirect = image, et le ciel est la seule lumière
+    for y in 0..9 {
+        for x in 0..9 {
+            let pixel = film.pixel(x, y);
+            let direct = film.aov(Aov::Direct, x, y).unwrap();
+            let indirect = film.aov(Aov::Indirect, x, y).unwrap();
+            let sky = film.aov(Aov::LightSky, x, y).unwrap();
+            assert!((direct[0] + indirect[0] - pixel.red).abs() < 1e-9);
+            assert!((direct[2] + indirect[2] - pixel.blue).abs() < 1e-9);
+            assert!((sky[1] - pixel.green).abs() < 1e-9);
+        }
+    }
+
+    let exr = encode_exr(&film);
+    assert_eq!(exr[..4], [0x76, 0x2f, 0x31, 0x01]);
+    assert!(exr.windows(8).any(|name| name == b"normal.X"));
+}
+
+#[test]
+fn denoising_gets_closer_to_the_converged_image() {
+    let scene = Scene::new(
+        Camera::new(
+            Angle::Deg(40.),
+            2.,
+            0.,
+            Point3(0., 3., 3.),
+            Point3(0., 0.5, 0.),
+            Vec3(0., 1., 0.),
+        ),
+        vec![
+            Arc::new(sphere(0., -100., 0., 100., diffuse(0.5, 0.5, 0.5))),
+            Arc::new(sphere(0., 0.5, 0., 0.5, diffuse(0.8, 0.3, 0.2))),
+        ],
+    );
+    let settings = |samples| Settings {
+        aovs: vec![Aov::Albedo, Aov::Normal, Aov::Depth],
+        threads: 2,
+        max_reflection: 8,
+        ..Settings::new(48, 24, samples)
+    };
+    let reference = Renderer::render(&scene, &settings(256));
+    let noisy = Renderer::render(&scene, &settings(2));
+    let denoised = denoise(&noisy);
+
+    let error = |film: &ray::Film| {
+        let mut error = 0.;
+        for y in 0..24 {
+            for x in 0..48 {
+                let (a, b) = (film.pixel(x, y), reference.pixel(x, y));
+                error += (a.red - b.red).powi(2) + (a.green - b.green).powi(2);
+            }
+        }
+        error
+    };
+    assert!(error(&denoised) < error(&noisy) / 2.);
+    //les échantillons et les aovs ne changent pas
+    assert_eq!(denoised.raw().1, noisy.raw().1);
+    assert_eq!(denoised.raw_layers(), noisy.raw_layers());
+}
+
+#[test]
+fn working_space_is_carried_by_the_film() {
+    let scene = Scene::new(
+        Camera::new(
+            Angle::Deg(40.),
+            2.,
+            0.,
+            Point3(0., 1., 4.),
+            Point3(0., 0.5, 0.),
+            Vec3(0., 1., 0.),
+        ),
+        vec![Arc::new(sphere(
+            0.,
+            -100.,
+            0.,
+            100.,
+            diffuse(0.5, 0.5, 0.5),
+        ))],
+    );
+    let settings = |working_space| Settings {
+        working_space,
+        threads: 1,
+        ..Settings::new(8, 4, 1)
+    };
+    let srgb = Renderer::render(&scene, &settings(ColorSpace::Srgb));
+    let acescg = Renderer::render(&scene, &settings(ColorSpace::AcesCg));
+    assert_eq!(acescg.space(), ColorSpace::AcesCg);
+
+    //le ciel n'est pas modifié par les matériaux: seule la conversion d'espace le change
+    let sky = ColorSpace::AcesCg.to_srgb(acescg.pixel(0, 0));
+    let expected = srgb.pixel(0, 0);
+    assert!((sky.red - expected.red).abs() < 1e-9 && (sky.blue - expected.blue).abs() < 1e-9);
+    assert!((acescg.pixel(0, 0).red - expected.red).abs() > 0.01);
+
+    let exr = encode_exr(&acescg);
+    assert!(exr.windows(14).any(|name| name == b"chromaticities"));
+}
+
+#[test]
+fn spectral_rendering_converges_to_the_rgb_image() {
+    let scene = Scene::new(
+        Camera::new(
+            Angle::Deg(40.),
+            2.,
+            0.,
+            Point3(0., 1., 4.),
+            Point3(0., 0.5, 0.),
+            Vec3(0., 1., 0.),
+        ),
+        vec![
+            Arc::new(sphere(0., -100., 0., 100., diffuse(0.5, 0.5, 0.5))),
+            Arc::new(sphere(0., 0.5, 0., 0.5, diffuse(0.7, 0.4, 0.3))),
+            Arc::new(sphere(1.2, 0.5, 0., 0.5, conductor(Conductor::Gold, 0.))),
+        ],
+    );
+    let settings = |spectral| Settings {
+        spectral,
+        threads: 2,
+        max_reflection: 8,
+        ..Settings::new(12, 6, 256)
+    };
+    let rgb = Renderer::render(&scene, &settings(false));
+    let spectral = Renderer::render(&scene, &settings(true));
+
+    let mean = |film: &ray::Film| {
+        let mut sum = [0.; 3];
+        for y in 0..6 {
+            for x in 0..12 {
+                let c = film.pixel(x, y);
+                sum[0] += c.red / 72.;
+                sum[1] += c.green / 72.;
+                sum[2] += c.blue / 72.;
+            }
+        }
+        sum
+    };
+    //mêmes couleurs en moyenne, aux erreurs de conversion rgb -> spectre près
+    for (a, b) in mean(&rgb).iter().zip(mean(&spectral)) {
+        assert!(
+            (a - b).abs() < 0.03,
+            "{:?} {:?}",
+            mean(&rgb),
+            mean(&spectral)
+        );
+    }
+    //le ciel vu directement garde sa couleur
+    let (sky_rgb, sky_spectral) = (rgb.pixel(0, 0), spectral.pixel(0, 0));
+    assert!((sky_rgb.blue - sky_spectral.blue).abs() < 0.03);
+    assert!((sky_rgb.red - sky_spectral.red).abs() < 0.03);
+}
+
+#[test]
+fn subsurface_scattering_keeps_the_light_and_takes_the_albedo_colour() {
+    let render = |albedo: Color, boundary| {
+        let scene = Scene::new(
+            Camera::new(
+                Angle::Deg(20.),
+                1.,
+                0.,
+                Point3(0., 0.5, 4.),
+                Point3(0., 0.5, 0.),
+                Vec3(0., 1., 0.),
+            ),
+            vec![Arc::new(sphere(
+                0.,
+                0.5,
+                0.,
+                0.5,
+                subsurface(albedo, Color::new(0.05, 0.05, 0.05), boundary),
+            ))],
+        );
+        let film = Renderer::render(
+            &scene,
+            &Settings {
+                threads: 2,
+                max_reflection: 16,
+                ..Settings::new(8, 8, 64)
+            },
+        );
+        //moyenne du centre de l'image, entièrement couvert par la sphère
+        let mut sum = Color::EMPTY;
+        for y in 2..6 {
+            for x in 2..6 {
+                sum = sum + film.pixel(x, y) * (1. / 16.);
+            }
+        }
+        sum
+    };
+    //albedo blanc et indice 1: toute la lumière ressort, le ciel (entre bleu et blanc) est seulement mélangé
+    let white = render(Color::new(1., 1., 1.), Boundary::Refractive(1.));
+    assert!(
+        white.red > 0.6 && white.green > 0.7 && white.blue > 0.95,
+        "{:?}",
+        white
+    );
+    for boundary in [Boundary::Refractive(1.4), Boundary::Diffuse] {
+        let orange = render(Color::new(0.9, 0.5, 0.2), boundary);
+        assert!(
+            orange.red > orange.green && orange.green > orange.blue,
+            "{:?}",
+            orange
+        );
+    }
+}
+
+#[test]
+fn measured_lambertian_renders_like_diffuse() {
+    //une BRDF lambertienne tabulée au format MERL doit donner la même image que diffuse()
+    let brdf = Arc::new(Merl::from_fn(|_, _, _| {
+        Color::new(0.7, 0.4, 0.3) * (1. / std::f64::consts::PI)
+    }));
+    let render = |material| {
+        let scene = Scene::new(
+            Camera::new(
+                Angle::Deg(20.),
+                1.,
+                0.,
+                Point3(0., 0.5, 4.),
+                Point3(0., 0.5, 0.),
+                Vec3(0., 1., 0.),
+            ),
+            vec![Arc::new(sphere(0., 0.5, 0., 0.5, material))],
+        );
+        let film = Renderer::render(
+            &scene,
+            &Settings {
+                threads: 2,
+                ..Settings::new(8, 8, 256)
+            },
+        );
+        let mut sum = Color::EMPTY;
+        for y in 2..6 {
+            for x in 2..6 {
+                sum = sum + film.pixel(x, y) * (1. / 16.);
+            }
+        }
+        sum
+    };
+    let expected = render(diffuse(0.7, 0.4, 0.3));
+    let tabulated = render(measured(brdf));
+    for (a, b) in [
+        (expected.red, tabulated.red),
+        (expected.green, tabulated.green),
+        (expected.blue, tabulated.blue),
+    ] {
+        assert!((a - b).abs() < 0.02, "{:?} {:?}", expected, tabulated);
+    }
+}
+
+#[test]
+fn hair_strands_render_through_the_bvh() {
+    //frange de brins verticaux, ondulés, devant la caméra
+    let mut text = String::new();
+    for i in 0..40 {
+        let x = -0.4 + i as f64 * 0.02;
+        for j in 0..5 {
+            let y = j as f64 * 0.25;
+            text += &format!("{} {} {} {}\n", x + 0.01 * (j % 2) as f64, y, 0., 0.02);
+        }
+        text += "\n";
+    }
+    let strands = strands::read_text(text.as_bytes()).unwrap();
+    let render = |eumelanin, pheomelanin| {
+        let material = hair(Hair::melanin(eumelanin, pheomelanin, 0.3, 0.3));
+        let scene = Scene::new(
+            Camera::new(
+                Angle::Deg(20.),
+                1.,
+                0.,
+                Point3(0., 0.5, 4.),
+                Point3(0., 0.5, 0.),
+                Vec3(0., 1., 0.),
+            ),
+            strands::curves(&strands, CurveShape::Cylinder, &material).unwrap(),
+        );
+        let film = Renderer::render(
+            &scene,
+            &Settings {
+                threads: 2,
+                max_reflection: 16,
+                ..Settings::new(8, 8, 64)
+            },
+        );
+        let mut sum = Color::EMPTY;
+        for y in 2..6 {
+            for x in 2..6 {
+                sum = sum + film.pixel(x, y) * (1. / 16.);
+            }
+        }
+        sum
+    };
+    //des cheveux bruns assombrissent le ciel, des cheveux roux le rougissent
+    let brown = render(8., 0.);
+    assert!(brown.green < 0.3, "{:?}", brown);
+    let red = render(0.3, 4.);
+    assert!(red.red > red.blue, "{:?}", red);
+}
+
+#[test]
+fn toon_shading_draws_bands_and_outlines() {
+    let scene = Scene::new(
+        Camera::new(
+            Angle::Deg(30.),
+            1.,
+            0.,
+            Point3(0., 0.5, 4.),
+            Point3(0., 0.5, 0.),
+            Vec3(0., 1., 0.),
+        ),
+        vec![Arc::new(sphere(0., 0.5, 0., 0.6, toon(0.8, 0.4, 0.2, 2)))],
+    );
+    let film = Renderer::render(
+        &scene,
+        &Settings {
+            aovs: vec![Aov::Depth, Aov::Normal, Aov::ObjectId],
+            toon: true,
+            ..Settings::new(24, 24, 1)
+        },
+    );
+    let object = |x: u32, y: u32| film.aov(Aov::ObjectId, x, y).unwrap()[0];
+    //deux aplats (ombre à 0.3 par défaut), éventuellement éclaircis par le liseré
+    let allowed = [0.24, 0.8, 0.62, 0.9];
+    let mut bands = std::collections::HashSet::new();
+    for y in 0..24 {
+        for x in 0..24 {
+            if object(x, y) == 1. {
+                let red = film.pixel(x, y).red;
+                let band = allowed.iter().position(|v| (v - red).abs() < 1e-9);
+                assert!(band.is_some(), "{} at {} {}", red, x, y);
+                bands.insert(band);
+            }
+        }
+    }
+    assert!(bands.len() >= 2);
+
+    let outlined = outline(&film, &Outline::default());
+    assert!(outlined.pixel(12, 12).red > 0.2);
+    let mut edges = 0;
+    for y in 1..23 {
+        for x in 1..23 {
+            let silhouette = object(x, y) == 1.
+                && [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .iter()
+                    .any(|&(x, y)| object(x, y) == 0.);
+            if silhouette {
+                edges += 1;
+                assert_eq!(outlined.pixel(x, y).red, 0.);
+            }
+        }
+    }
+    assert!(edges > 0);
+}
@@ -0,0 +1,77 @@
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const RAY: &str = env!("CARGO_BIN_EXE_ray");
+const SCENE: [&str; 8] = [
+    "--width",
+    "32",
+    "--samples",
+    "32",
+    "--seed",
+    "3",
+    "--tile-size",
+    "8",
+];
+
+fn output(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+//un coordinateur et des workers locaux, dont un qui meurt en cours de rendu,
+//doivent produire exactement l'image d'un rendu local de même graine
+#[test]
+fn distributed_render_survives_a_dead_worker() {
+    let local = output("local.ppm");
+    let status = Command::new(RAY)
+        .args(SCENE)
+        .arg("--output")
+        .arg(&local)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let distributed = output("distributed.ppm");
+    let mut coordinator = Command::new(RAY)
+        .args(SCENE)
+        .args(["--coordinator", "127.0.0.1:0", "--output"])
+        .arg(&distributed)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(coordinator.stdout.take().unwrap()).lines();
+    let mut wait_for = |prefix: &str| loop {
+        let line = lines.next().expect("coordinator exited").unwrap();
+        if let Some(rest) = line.strip_prefix(prefix) {
+            return rest.to_string();
+        }
+    };
+    let address = wait_for("coordinator listening on ");
+
+    let worker = |threads: &str| {
+        Command::new(RAY)
+            .args(["--worker", &address, "--threads", threads])
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap()
+    };
+
+    //le premier worker est tué pendant le rendu de sa première tuile
+    let mut dying = worker("1");
+    wait_for("worker ");
+    dying.kill().unwrap();
+    dying.wait().unwrap();
+    let lost = wait_for("worker ");
+    assert!(lost.contains("lost"), "unexpected: {}", lost);
+
+    let mut survivor = worker("2");
+    while lines.next().is_some() {}
+    assert!(coordinator.wait().unwrap().success());
+    survivor.wait().unwrap();
+
+    assert_eq!(
+        std::fs::read(local).unwrap(),
+        std::fs::read(distributed).unwrap()
+    );
+}
@@ -0,0 +1,268 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ray::angle::Angle;
+use ray::bvh::Bvh;
+use ray::cam::Camera;
+use ray::color::Color;
+use ray::colorspace::ColorSpace;
+use ray::geometry::{sphere, Geometry};
+use ray::material::{colored_dielectric, dielectric, diffuse, metal};
+use ray::output::display;
+use ray::point::Point3;
+use ray::ppm::Ppm;
+use ray::render::{render_tile, Scene, Settings};
+use ray::sampler::reseed;
+use ray::tile::Tile;
+use ray::vec::Vec3;
+use ray::world::{world_v4, world_v5};
+
+//rendus de scènes canoniques à graine fixe, comparés aux images de tests/references.
+//en cas d'écart, l'image obtenue et une image des différences (amplifiées) sont écrites dans target/tmp/reference.
+//UPDATE_REFERENCES=1 cargo test --test reference régénère les références après un changement de rendu voulu
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 36;
+const SAMPLES: u32 = 16;
+const SEED: u64 = 1;
+//écart quadratique moyen toléré, par composante sur 0..254
+const RMSE: f64 = 2.;
+//même mesure sur les images floutées (3x3): insensible au bruit, elle détecte un changement d'aspect
+const BLURRED_RMSE: f64 = 1.;
+
+struct Image {
+    width: usize,
+    height: usize,
+    values: Vec<f64>,
+}
+
+impl Image {
+    fn parse(text: &str) -> Image {
+        let mut tokens = text
+            .split_whitespace()
+            .skip(1)
+            .map(|t| t.parse::<f64>().unwrap());
+        let width = tokens.next().unwrap() as usize;
+        let height = tokens.next().unwrap() as usize;
+        let _max = tokens.next();
+        Image {
+            width,
+            height,
+            values: tokens.collect(),
+        }
+    }
+
+    //moyenne 3x3 de chaque composante
+    fn blurred(&self) -> Image {
+        let mut values = Vec::with_capacity(self.values.len());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                for c in 0..3 {
+                    let (mut sum, mut count) = (0., 0.);
+                    for ny in y.saturating_sub(1)..(y + 2).min(self.height) {
+                        for nx in x.saturating_sub(1)..(x + 2).min(self.width) {
+                            sum += self.values[(ny * self.width + nx) * 3 + c];
+                            count += 1.;
+                        }
+                    }
+                    values.push(sum / count);
+                }
+            }
+        }
+        Image {
+            width: self.width,
+            height: self.height,
+            values,
+        }
+    }
+
+    fn rmse(&self, other: &Image) -> f64 {
+        let sum: f64 = self
+            .values
+            .iter()
+            .zip(&other.values)
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum();
+        (sum / self.values.len() as f64).sqrt()
+    }
+}
+
+fn camera(from: Point3, to: Point3, fov: f64) -> Camera {
+    Camera::new(
+        Angle::Deg(fov),
+        WIDTH as f64 / HEIGHT as f64,
+        0.,
+        from,
+        to,
+        Vec3(0., 1., 0.),
+    )
+}
+
+fn close_up(objects: Vec<Arc<Geometry>>) -> Scene {
+    Scene {
+        camera: camera(Point3(0., 1.5, 6.), Point3(0., 0.8, 0.), 35.),
+        world: Bvh::new(objects),
+    }
+}
+
+fn ground() -> Arc<Geometry> {
+    Arc::new(sphere(0., -1000., 0., 1000., diffuse(0.5, 0.5, 0.5)))
+}
+
+fn ppm(colors: &[Color]) -> String {
+    let mut ppm = Ppm::new(Vec::new(), WIDTH, HEIGHT, 255).unwrap();
+    ppm.next_pixels(colors).unwrap();
+    String::from_utf8(ppm.into_inner()).unwrap()
+}
+
+fn check(name: &str, scene: Scene) {
+    let settings = Settings {
+        width: WIDTH,
+        height: HEIGHT,
+        samples_per_pixel: SAMPLES,
+        max_reflection: 50,
+        tile_size: WIDTH,
+        threads: 1,
+        seed: SEED,
+        aovs: Vec::new(),
+        working_space: ColorSpace::Srgb,
+        spectral: false,
+        toon: false,
+    };
+    let tile = Tile {
+        x: 0,
+        y: 0,
+        width: WIDTH,
+        height: HEIGHT,
+    };
+    let colors: Vec<Color> = render_tile(&scene, &settings, 0, SAMPLES, &tile)
+        .colors
+        .into_iter()
+        .map(|color| display(color / SAMPLES as f64))
+        .collect();
+    let actual = ppm(&colors);
+
+    let reference_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/references")
+        .join(format!("{}.ppm", name));
+    if std::env::var_os("UPDATE_REFERENCES").is_some() {
+        std::fs::write(&reference_path, &actual).unwrap();
+        return;
+    }
+    let reference = Image::parse(
+        &std::fs::read_to_string(&reference_path).unwrap_or_else(|e| {
+            panic!(
+                "{}: {} (UPDATE_REFERENCES=1 to create it)",
+                reference_path.display(),
+                e
+            )
+        }),
+    );
+    let image = Image::parse(&actual);
+    assert_eq!(
+        (image.width, image.height),
+        (reference.width, reference.height)
+    );
+
+    let rmse = image.rmse(&reference);
+    let blurred_rmse = image.blurred().rmse(&reference.blurred());
+    if rmse > RMSE || blurred_rmse > BLURRED_RMSE {
+        let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("reference");
+        std::fs::create_dir_all(&out).unwrap();
+        let diff: Vec<Color> = image
+            .values
+            .chunks(3)
+            .zip(reference.values.chunks(3))
+            .map(|(a, b)| {
+                Color::new(
+                    (a[0] - b[0]).abs(),
+                    (a[1] - b[1]).abs(),
+                    (a[2] - b[2]).abs(),
+                ) * (4. / 254.)
+            })
+            .map(|color| color.map_each(|v| v.min(1.)))
+            .collect();
+        let actual_path = out.join(format!("{}.actual.ppm", name));
+        let diff_path = out.join(format!("{}.diff.ppm", name));
+        std::fs::write(&actual_path, &actual).unwrap();
+        std::fs::write(&diff_path, ppm(&diff)).unwrap();
+        panic!(
+            "{} differs from its reference: rmse {:.3} (max {}), blurred rmse {:.3} (max {})\nactual: {}\ndiff: {}",
+            name,
+            rmse,
+            RMSE,
+            blurred_rmse,
+            BLURRED_RMSE,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn diffuse_spheres() {
+    check(
+        "diffuse",
+        close_up(vec![
+            ground(),
+            Arc::new(sphere(-1.1, 1., 0., 1., diffuse(0.4, 0.2, 0.1))),
+            Arc::new(sphere(1.1, 1., 0., 1., diffuse(0.2, 0.6, 0.1))),
+        ]),
+    );
+}
+
+#[test]
+fn metal_spheres() {
+    check(
+        "metal",
+        close_up(vec![
+            ground(),
+            Arc::new(sphere(-1.1, 1., 0., 1., metal(0.7, 0.6, 0.5, 0.))),
+            Arc::new(sphere(1.1, 1., 0., 1., metal(0.8, 0.8, 0.8, 0.3))),
+        ]),
+    );
+}
+
+#[test]
+fn glass_spheres() {
+    check(
+        "glass",
+        close_up(vec![
+            ground(),
+            Arc::new(sphere(-1.1, 1., 0., 1., dielectric(1.5))),
+            //sphère creuse: le rayon négatif retourne les normales de la sphère intérieure
+            Arc::new(sphere(1.1, 1., 0., 1., dielectric(1.5))),
+            Arc::new(sphere(1.1, 1., 0., -0.9, dielectric(1.5))),
+            Arc::new(sphere(
+                0.,
+                0.4,
+                1.5,
+                0.4,
+                colored_dielectric(0.9, 0.5, 0.5, 1.5),
+            )),
+        ]),
+    );
+}
+
+#[test]
+fn world_v5_scene() {
+    check(
+        "world_v5",
+        Scene {
+            camera: camera(Point3(13., 2., 3.), Point3(0., 0., 0.), 20.),
+            world: Bvh::new(world_v5()),
+        },
+    );
+}
+
+#[test]
+fn world_v4_scene() {
+    reseed(SEED);
+    check(
+        "world_v4",
+        Scene {
+            camera: camera(Point3(13., 2., 3.), Point3(0., 0., 0.), 20.),
+            world: Bvh::new(world_v4()),
+        },
+    );
+}